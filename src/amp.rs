@@ -0,0 +1,184 @@
+use crate::{escape_xml, urls, Site};
+use html5ever::{
+    tendril::StrTendril,
+    tokenizer::{
+        states::RawKind, BufferQueue, Tag, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer,
+        TokenizerOpts,
+    },
+};
+use std::{
+    cell::{Cell, RefCell},
+    path::PathBuf,
+};
+
+// Dropped along with everything inside them, AMP pages can't run their own
+// scripts or embed other documents
+const DISALLOWED_ELEMENTS: &[&str] = &[
+    "script", "style", "iframe", "frame", "frameset", "object", "embed", "form", "noscript",
+];
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+struct Sanitizer<'a> {
+    site: &'a Site,
+    output: RefCell<String>,
+    // Depth inside a disallowed element, nothing is written while above zero
+    skipping: Cell<usize>,
+}
+
+impl Sanitizer<'_> {
+    fn write_tag(&self, tag: &Tag) {
+        let mut output = self.output.borrow_mut();
+        if tag.kind == TagKind::EndTag {
+            output.push_str(&format!("</{}>", tag.name));
+            return;
+        }
+        output.push_str(&format!("<{}", tag.name));
+        for attribute in &tag.attrs {
+            let name = &*attribute.name.local;
+            // Inline styles and event handlers aren't valid AMP
+            if name == "style" || name.starts_with("on") {
+                continue;
+            }
+            output.push_str(&format!(" {}=\"{}\"", name, escape_xml(&attribute.value)));
+        }
+        output.push('>');
+    }
+
+    // `<amp-img>` needs the image's size up front. Images whose file can't be
+    // found are turned into links to them instead.
+    fn write_image(&self, tag: &Tag) {
+        let attribute = |name: &str| {
+            tag.attrs
+                .iter()
+                .find(|attribute| &*attribute.name.local == name)
+                .map(|attribute| attribute.value.to_string())
+        };
+        let src = attribute("src").unwrap_or_default();
+        let alt = attribute("alt").unwrap_or_default();
+        let size = match (attribute("width"), attribute("height")) {
+            (Some(width), Some(height)) => width.parse().ok().zip(height.parse().ok()),
+            _ => source_file(self.site, &src).and_then(|path| image::image_dimensions(path).ok()),
+        };
+        let mut output = self.output.borrow_mut();
+        match size {
+            Some((width, height)) => output.push_str(&format!(
+                "<amp-img src=\"{}\" alt=\"{}\" width=\"{}\" height=\"{}\" layout=\"responsive\"></amp-img>",
+                escape_xml(&src),
+                escape_xml(&alt),
+                width,
+                height
+            )),
+            None => output.push_str(&format!(
+                "<a href=\"{}\">{}</a>",
+                escape_xml(&src),
+                escape_xml(if alt.is_empty() { &src } else { &alt })
+            )),
+        }
+    }
+}
+
+impl TokenSink for Sanitizer<'_> {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line: u64) -> TokenSinkResult<()> {
+        match token {
+            Token::TagToken(tag) => {
+                let name = &*tag.name;
+                if DISALLOWED_ELEMENTS.contains(&name) {
+                    let void = VOID_ELEMENTS.contains(&name) || tag.self_closing;
+                    match tag.kind {
+                        TagKind::StartTag if !void => self.skipping.set(self.skipping.get() + 1),
+                        TagKind::EndTag if !void => {
+                            self.skipping.set(self.skipping.get().saturating_sub(1))
+                        }
+                        _ => {}
+                    }
+                    // Keep the tokenizer from reading a script's code as markup
+                    return match (tag.kind, name) {
+                        (TagKind::StartTag, "script") => {
+                            TokenSinkResult::RawData(RawKind::ScriptData)
+                        }
+                        (TagKind::StartTag, "style" | "iframe" | "noscript") => {
+                            TokenSinkResult::RawData(RawKind::Rawtext)
+                        }
+                        _ => TokenSinkResult::Continue,
+                    };
+                }
+                if self.skipping.get() > 0 {
+                    return TokenSinkResult::Continue;
+                }
+                if name == "img" && tag.kind == TagKind::StartTag {
+                    self.write_image(&tag);
+                } else {
+                    self.write_tag(&tag);
+                }
+            }
+            Token::CharacterTokens(text) if self.skipping.get() == 0 => {
+                let text = text
+                    .replace('&', "&amp;")
+                    .replace('<', "&lt;")
+                    .replace('>', "&gt;");
+                self.output.borrow_mut().push_str(&text);
+            }
+            _ => {}
+        }
+        TokenSinkResult::Continue
+    }
+}
+
+// Rendered post content made AMP friendly: disallowed elements, inline styles
+// and event handlers are removed and images become `<amp-img>`
+pub(crate) fn sanitize(site: &Site, html: &str) -> String {
+    let sanitizer = Sanitizer {
+        site,
+        output: RefCell::new(String::with_capacity(html.len())),
+        skipping: Cell::new(0),
+    };
+    let tokenizer = Tokenizer::new(sanitizer, TokenizerOpts::default());
+    let input = BufferQueue::default();
+    input.push_back(StrTendril::from_slice(html));
+    let _ = tokenizer.feed(&input);
+    tokenizer.end();
+    tokenizer.sink.output.take()
+}
+
+// `!important` isn't allowed in AMP stylesheets
+pub(crate) fn amp_css(css: &str) -> String {
+    css.replace("!important", "")
+}
+
+// The source of an image URL in rendered content, under `src/assets` or next
+// to a post, also for images on `image_cdn`
+fn source_file(site: &Site, url: &str) -> Option<PathBuf> {
+    // Images on the CDN are copies of the ones in `dist`
+    let cdn = site
+        .config
+        .image_cdn
+        .as_deref()
+        .map(|cdn| cdn.trim_end_matches('/'));
+    let url = match cdn.and_then(|cdn| url.strip_prefix(cdn)) {
+        Some(path) if path.starts_with('/') => path,
+        _ => url,
+    };
+    if urls::is_external(url) {
+        return None;
+    }
+    let path = url.split(['?', '#']).next()?;
+    let base_path = site.config.base_path.trim_matches('/');
+    let path = match base_path.is_empty() {
+        true => path,
+        false => path
+            .strip_prefix(&format!("/{}", base_path))
+            .unwrap_or(path),
+    };
+    let path = path.strip_prefix('/')?;
+    let file = match path.strip_prefix("assets/") {
+        Some(asset) => site.source_dir.join("assets").join(asset),
+        None => site.content_dir().join(path),
+    };
+    file.is_file().then_some(file)
+}
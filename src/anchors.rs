@@ -0,0 +1,163 @@
+use crate::{check::resolve_dots, urls, Site};
+use anyhow::{Context, Result};
+use html5ever::{
+    tendril::StrTendril,
+    tokenizer::{
+        states::RawKind, BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer,
+        TokenizerOpts,
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashSet},
+    fs,
+};
+use walkdir::WalkDir;
+
+// The ids of a page and the fragments its links point at
+#[derive(Default)]
+struct Anchors {
+    ids: RefCell<HashSet<String>>,
+    links: RefCell<Vec<String>>,
+}
+
+impl TokenSink for Anchors {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, _line: u64) -> TokenSinkResult<()> {
+        let Token::TagToken(tag) = token else {
+            return TokenSinkResult::Continue;
+        };
+        if tag.kind != TagKind::StartTag {
+            return TokenSinkResult::Continue;
+        }
+        let name = &*tag.name;
+        for attribute in &tag.attrs {
+            match &*attribute.name.local {
+                // `<a name="...">` is an old way to set an anchor
+                "id" => {
+                    self.ids.borrow_mut().insert(attribute.value.to_string());
+                }
+                "name" if name == "a" => {
+                    self.ids.borrow_mut().insert(attribute.value.to_string());
+                }
+                "href" if (name == "a" || name == "area") && attribute.value.contains('#') => {
+                    self.links.borrow_mut().push(attribute.value.to_string());
+                }
+                _ => {}
+            }
+        }
+        // Links in scripts and styles are just text
+        match name {
+            "script" => TokenSinkResult::RawData(RawKind::ScriptData),
+            "style" | "iframe" | "noembed" | "noframes" | "xmp" => {
+                TokenSinkResult::RawData(RawKind::Rawtext)
+            }
+            "textarea" | "title" => TokenSinkResult::RawData(RawKind::Rcdata),
+            _ => TokenSinkResult::Continue,
+        }
+    }
+}
+
+fn page_anchors(html: &str) -> Anchors {
+    let tokenizer = Tokenizer::new(Anchors::default(), TokenizerOpts::default());
+    let input = BufferQueue::default();
+    input.push_back(StrTendril::from_slice(html));
+    let _ = tokenizer.feed(&input);
+    tokenizer.end();
+    tokenizer.sink
+}
+
+// Links in the built pages to fragments that no element has an id for, on
+// the same page (`#setup`) or another one (`/blog/post.html#setup`). Links to
+// pages the build didn't write are left to `check`.
+pub(crate) fn check_anchors(site: &Site) -> Result<Vec<String>> {
+    let mut pages = BTreeMap::new();
+    for entry in WalkDir::new(&site.dist_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "html"))
+    {
+        let html = fs::read_to_string(entry.path())
+            .with_context(|| format!("Failed to read {}", entry.path().display()))?;
+        let url = entry
+            .path()
+            .strip_prefix(&site.dist_dir)?
+            .to_string_lossy()
+            .replace('\\', "/");
+        pages.insert(format!("/{}", url), page_anchors(&html));
+    }
+
+    let mut problems = vec![];
+    for (url, anchors) in &pages {
+        for link in anchors.links.borrow().iter() {
+            let Some((path, id)) = link.split_once('#') else {
+                continue;
+            };
+            // `#` and `#top` scroll to the top of any page
+            if id.is_empty() || id == "top" {
+                continue;
+            }
+            let Some((target, target_anchors)) = target_pages(site, url, path)
+                .into_iter()
+                .find_map(|target| pages.get_key_value(&target))
+            else {
+                continue;
+            };
+            if target_anchors.ids.borrow().contains(id) {
+                continue;
+            }
+            if target == url {
+                problems.push(format!(
+                    "{} links to `#{}`, which isn't an id on the page",
+                    url, id
+                ));
+            } else {
+                problems.push(format!(
+                    "{} links to `{}`, but {} has no id `{}`",
+                    url, link, target, id
+                ));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+// The pages under `dist` a link's path could lead to, none for external links
+fn target_pages(site: &Site, page_url: &str, path: &str) -> Vec<String> {
+    let path = path.split('?').next().unwrap_or_default();
+    if path.is_empty() {
+        return vec![page_url.to_string()];
+    }
+    let base_url = site.config.base_url.trim_end_matches('/');
+    let path = match path.strip_prefix(base_url) {
+        Some(path) if !base_url.is_empty() => path,
+        _ => path,
+    };
+    if urls::is_external(path) {
+        return vec![];
+    }
+
+    let path = match path.strip_prefix('/') {
+        Some(_) => {
+            let base_path = format!("/{}", site.config.base_path.trim_matches('/'));
+            match path.strip_prefix(base_path.as_str()) {
+                Some(rest) if base_path != "/" && (rest.is_empty() || rest.starts_with('/')) => {
+                    format!("/{}", rest.trim_start_matches('/'))
+                }
+                _ => path.to_string(),
+            }
+        }
+        None => {
+            let dir = page_url.rsplit_once('/').map_or("", |(dir, _)| dir);
+            resolve_dots(&format!("{}/{}", dir, path))
+        }
+    };
+    // Static hosts serve `/blog`, `/blog/` and `/blog.html` alike
+    let trimmed = path.trim_end_matches('/');
+    vec![
+        path.clone(),
+        format!("{}.html", trimmed),
+        format!("{}/index.html", trimmed),
+    ]
+}
@@ -0,0 +1,255 @@
+use crate::{config::AssetsConfig, ignore::IgnoreMatcher, markdown::local_asset};
+use anyhow::{Context, Result};
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+    imageops, DynamicImage,
+};
+use rayon::prelude::*;
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+#[derive(Debug, Default)]
+pub(crate) struct AssetStats {
+    pub copied: usize,
+    // Unchanged since the previous build
+    pub skipped: usize,
+    pub images_optimized: usize,
+    pub bytes_saved: u64,
+    // Resized copies written for responsive images
+    pub variants: usize,
+    // Each source file with the copy and variants it stands for
+    pub outputs: Vec<(PathBuf, Vec<PathBuf>)>,
+}
+
+enum Outcome {
+    Copied,
+    Skipped,
+    Optimized(u64),
+}
+
+pub(crate) fn copy_assets(src: &Path, dest: &Path, config: &AssetsConfig) -> Result<AssetStats> {
+    let ignore = IgnoreMatcher::new(&config.ignore, config.include_hidden)?;
+
+    // Directories are created up front so the parallel copies don't race on them
+    let mut files = vec![];
+    for entry in WalkDir::new(src).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if path.is_file() {
+            let relative_path = path.strip_prefix(src)?;
+            if ignore.is_ignored(relative_path) {
+                continue;
+            }
+            let dest_path = dest.join(relative_path);
+            if let Some(parent) = dest_path.parent() {
+                fs::create_dir_all(parent).context("Failed to create asset directories")?;
+            }
+            files.push((path.to_path_buf(), dest_path));
+        }
+    }
+
+    let outcomes = files
+        .par_iter()
+        .map(|(path, dest_path)| {
+            let outcome = copy_asset(path, dest_path, config)?;
+            let variants = write_variants(path, dest_path, config)?;
+            Ok((outcome, variants))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut stats = AssetStats::default();
+    for (path, dest_path) in files {
+        let dest = dest_path.to_string_lossy().into_owned();
+        let mut outputs: Vec<PathBuf> = variant_widths(&path, config)
+            .into_iter()
+            .map(|width| PathBuf::from(variant_name(&dest, width)))
+            .collect();
+        outputs.insert(0, dest_path);
+        stats.outputs.push((path, outputs));
+    }
+    for (outcome, variants) in outcomes {
+        stats.variants += variants;
+        match outcome {
+            Outcome::Copied => stats.copied += 1,
+            Outcome::Skipped => stats.skipped += 1,
+            Outcome::Optimized(saved) => {
+                stats.images_optimized += 1;
+                stats.bytes_saved += saved;
+            }
+        }
+    }
+    Ok(stats)
+}
+
+// Directories named `name` under `content_dir`, relative to it. Ones inside
+// another match are copied along with it and not listed again.
+pub(crate) fn colocated_dirs(content_dir: &Path, name: &str) -> Vec<PathBuf> {
+    let mut dirs = vec![];
+    if name.is_empty() {
+        return dirs;
+    }
+    let mut entries = WalkDir::new(content_dir).sort_by_file_name().into_iter();
+    while let Some(entry) = entries.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if entry.file_type().is_dir() && entry.depth() > 0 && entry.file_name() == name {
+            if let Ok(relative_path) = entry.path().strip_prefix(content_dir) {
+                dirs.push(relative_path.to_path_buf());
+            }
+            entries.skip_current_dir();
+        }
+    }
+    dirs
+}
+
+fn copy_asset(path: &Path, dest_path: &Path, config: &AssetsConfig) -> Result<Outcome> {
+    if config.optimize_images && image_format(path).is_some() {
+        // Re-encoding is slow, leave images from the previous build alone
+        if is_up_to_date(path, dest_path) {
+            return Ok(Outcome::Skipped);
+        }
+        if let Some(saved) = optimize_image(path, dest_path, config)? {
+            return Ok(Outcome::Optimized(saved));
+        }
+    } else if is_unchanged_copy(path, dest_path) {
+        return Ok(Outcome::Skipped);
+    }
+
+    fs::copy(path, dest_path)
+        .with_context(|| format!("Failed to copy asset {}", path.display()))?;
+    Ok(Outcome::Copied)
+}
+
+#[derive(Clone, Copy)]
+enum ImageFormat {
+    Jpeg,
+    Png,
+}
+
+fn image_format(path: &Path) -> Option<ImageFormat> {
+    let extension = path.extension()?.to_string_lossy().to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
+        "png" => Some(ImageFormat::Png),
+        _ => None,
+    }
+}
+
+// Widths a source image gets resized copies at, none unless responsive images
+// are enabled and the image is at least as wide as the largest of them
+pub(crate) fn variant_widths(src: &Path, config: &AssetsConfig) -> Vec<u32> {
+    if !config.responsive_images || image_format(src).is_none() {
+        return vec![];
+    }
+    let Some(largest) = config.responsive_widths.iter().max() else {
+        return vec![];
+    };
+    match image::image_dimensions(src) {
+        Ok((width, _)) if width >= *largest => config.responsive_widths.clone(),
+        _ => vec![],
+    }
+}
+
+// Width and height of an `/assets/...` image, SVGs and other formats the
+// image crate can't read have none
+pub(crate) fn image_size(source_dir: &Path, url: &str) -> Option<(u32, u32)> {
+    let path = source_dir.join(local_asset(url)?);
+    image::image_dimensions(path).ok()
+}
+
+// `photo.jpg` becomes `photo-480w.jpg`, works on paths and URLs alike
+pub(crate) fn variant_name(name: &str, width: u32) -> String {
+    let file_start = name.rfind('/').map_or(0, |slash| slash + 1);
+    match name[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = file_start + dot;
+            format!("{}-{}w{}", &name[..dot], width, &name[dot..])
+        }
+        _ => format!("{}-{}w", name, width),
+    }
+}
+
+// Write the resized copies of an image next to its output, returns how many
+// were (re)generated
+fn write_variants(src: &Path, dest: &Path, config: &AssetsConfig) -> Result<usize> {
+    let widths = variant_widths(src, config);
+    let dest = dest.to_string_lossy();
+    let pending: Vec<(u32, String)> = widths
+        .into_iter()
+        .map(|width| (width, variant_name(&dest, width)))
+        .filter(|(_, variant)| !is_up_to_date(src, Path::new(variant)))
+        .collect();
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let image = image::open(src).with_context(|| format!("Failed to decode {}", src.display()))?;
+    for (width, variant) in &pending {
+        let resized = image.resize(*width, u32::MAX, imageops::FilterType::Lanczos3);
+        let mut buffer = Cursor::new(vec![]);
+        let encoded = match image_format(src) {
+            Some(ImageFormat::Jpeg) => DynamicImage::from(resized.to_rgb8()).write_with_encoder(
+                JpegEncoder::new_with_quality(&mut buffer, config.image_quality),
+            ),
+            _ => resized.write_with_encoder(PngEncoder::new(&mut buffer)),
+        };
+        encoded.with_context(|| format!("Failed to resize {}", src.display()))?;
+        fs::write(variant, buffer.into_inner())
+            .with_context(|| format!("Failed to write {}", variant))?;
+    }
+    Ok(pending.len())
+}
+
+// A plain copy from a previous build: the same size and not older than the source
+fn is_unchanged_copy(src: &Path, dest: &Path) -> bool {
+    let size = |path: &Path| fs::metadata(path).map(|meta| meta.len()).ok();
+    size(src).is_some() && size(src) == size(dest) && is_up_to_date(src, dest)
+}
+
+fn is_up_to_date(src: &Path, dest: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    match (modified(src), modified(dest)) {
+        (Some(src), Some(dest)) => dest >= src,
+        _ => false,
+    }
+}
+
+// Re-encode an image, which also drops EXIF and other metadata. Returns the
+// bytes saved, or `None` when the original should be copied unchanged.
+fn optimize_image(src: &Path, dest: &Path, config: &AssetsConfig) -> Result<Option<u64>> {
+    let original_size = fs::metadata(src).context("Failed to read asset")?.len();
+    if original_size < config.optimize_threshold {
+        return Ok(None);
+    }
+
+    let image = image::open(src).with_context(|| format!("Failed to decode {}", src.display()))?;
+    let mut buffer = Cursor::new(vec![]);
+    let encoded = match image_format(src) {
+        Some(ImageFormat::Jpeg) => DynamicImage::from(image.to_rgb8()).write_with_encoder(
+            JpegEncoder::new_with_quality(&mut buffer, config.image_quality),
+        ),
+        Some(ImageFormat::Png) => image.write_with_encoder(PngEncoder::new_with_quality(
+            &mut buffer,
+            CompressionType::Best,
+            FilterType::Adaptive,
+        )),
+        None => return Ok(None),
+    };
+    encoded.with_context(|| format!("Failed to re-encode {}", src.display()))?;
+
+    let optimized = buffer.into_inner();
+    let optimized_size = optimized.len() as u64;
+    if optimized_size >= original_size {
+        return Ok(None);
+    }
+
+    fs::write(dest, optimized).context("Failed to write optimized image")?;
+    Ok(Some(original_size - optimized_size))
+}
@@ -0,0 +1,12 @@
+document.querySelectorAll(".code-block").forEach((block) => {
+  const button = block.querySelector(".copy-button");
+  const code = block.querySelector("code");
+  if (!button || !code) return;
+
+  button.addEventListener("click", () => {
+    navigator.clipboard.writeText(code.innerText).then(() => {
+      button.textContent = "Copied!";
+      setTimeout(() => (button.textContent = "Copy"), 2000);
+    });
+  });
+});
@@ -0,0 +1,437 @@
+use crate::{
+    assets,
+    config::FirstListingPage,
+    content::{
+        extract_list, extract_metadata, is_draft, is_missing_body, is_published, output_path,
+        parse_date, parse_split_on, read_source,
+    },
+    content_files,
+    ignore::IgnoreMatcher,
+    page_url,
+    pages::{listing_page_count, listing_path, numbered_listing_path, own_listing_path},
+    print::PRINT_TEMPLATE,
+    slugs, tags, urls, Site,
+};
+use anyhow::Result;
+use chrono::Local;
+use pulldown_cmark::{Event, Parser, Tag};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::{Path, PathBuf},
+    sync::PoisonError,
+};
+use walkdir::WalkDir;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Issue {
+    pub severity: Severity,
+    pub path: PathBuf,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(f, "{}: {}: {}", severity, self.path.display(), self.message)
+    }
+}
+
+// Validate the content the way a build would read it, without writing anything
+pub fn check(site: &Site) -> Result<Vec<Issue>> {
+    let files = content_files(site);
+    // Colliding files are reported below when they're an error
+    *site.slugs.write().unwrap_or_else(PoisonError::into_inner) =
+        slugs::resolve_slugs(site, &files).unwrap_or_default();
+    let targets = link_targets(site, &files)?;
+    let today = Local::now().date_naive();
+
+    let mut issues = vec![];
+    let mut issue = |severity, path: &Path, message: String| {
+        issues.push(Issue {
+            severity,
+            path: path.to_path_buf(),
+            message,
+        })
+    };
+
+    let mut urls_seen: HashMap<String, PathBuf> = HashMap::new();
+    for path in &files {
+        let source = match read_source(site, path) {
+            Ok(source) => source,
+            Err(err) => {
+                issue(Severity::Error, path, format!("{:#}", err));
+                continue;
+            }
+        };
+        let frontmatter = &source.frontmatter;
+        for warning in &source.warnings {
+            issue(Severity::Warning, path, warning.clone());
+        }
+
+        if let Err(err) = is_published(site, frontmatter) {
+            issue(Severity::Error, path, format!("{:#}", err));
+        }
+        if extract_metadata(frontmatter, "title").is_none() {
+            issue(Severity::Error, path, "missing `title`".to_string());
+        }
+        let relative = path.strip_prefix(site.content_dir()).unwrap_or(path);
+        let section = site.config.section_for(relative);
+        if section.is_none() && !site.config.allows_page(relative) {
+            issue(
+                Severity::Error,
+                path,
+                "not in any section's directory, which `require_section` needs".to_string(),
+            );
+        }
+        if let Some((name, _)) = section.filter(|(_, section)| {
+            section.require_date && extract_metadata(frontmatter, "date").is_none()
+        }) {
+            issue(
+                Severity::Warning,
+                path,
+                format!("no `date`, which section `{}` requires", name),
+            );
+        }
+        for key in ["date", "updated"] {
+            let Some(value) = extract_metadata(frontmatter, key) else {
+                continue;
+            };
+            match parse_date(&site.config.date_formats, &value) {
+                None => issue(
+                    Severity::Error,
+                    path,
+                    format!("unparseable {} `{}`", key, value),
+                ),
+                Some(date) if key == "date" && is_draft(frontmatter) && date < today => issue(
+                    Severity::Warning,
+                    path,
+                    format!("draft is dated in the past ({})", date),
+                ),
+                Some(_) => {}
+            }
+        }
+        if is_missing_body(frontmatter, &source.markdown) {
+            issue(Severity::Warning, path, "no content".to_string());
+        }
+
+        // Hosts may treat URLs case insensitively, so `Post.md` and `post.md` collide
+        let url = page_url(site, &output_path(site, path)?);
+        if let Some(other) = urls_seen.insert(url.to_lowercase(), path.clone()) {
+            issue(
+                Severity::Error,
+                path,
+                format!("same URL `{}` as {}", url, other.display()),
+            );
+        }
+
+        let mut image_alt: Option<(String, String)> = None;
+        for event in Parser::new(&source.markdown) {
+            match event {
+                Event::Start(Tag::Link(_, link, _)) if !is_linked(site, &targets, &url, &link) => {
+                    issue(Severity::Error, path, format!("broken link `{}`", link));
+                }
+                Event::Start(Tag::Image(_, link, _)) => {
+                    if !is_linked(site, &targets, &url, &link) {
+                        issue(Severity::Error, path, format!("broken image `{}`", link));
+                    }
+                    image_alt = Some((link.to_string(), String::new()));
+                }
+                Event::Text(text) | Event::Code(text) => {
+                    if let Some((_, alt)) = &mut image_alt {
+                        alt.push_str(&text);
+                    }
+                }
+                Event::End(Tag::Image(..)) => {
+                    if let Some((link, alt)) = image_alt.take() {
+                        if alt.trim().is_empty() {
+                            issue(
+                                Severity::Warning,
+                                path,
+                                format!("image `{}` has no alt text", link),
+                            );
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(issues)
+}
+
+// Validate the configuration against the templates and directories it names,
+// without reading content. Templates have already compiled by now, `Site::new`
+// fails on any that don't.
+pub fn check_config(site: &Site, config_path: &Path) -> Vec<Issue> {
+    let config = &site.config;
+    let mut issues = vec![];
+    let mut issue = |severity, message: String| {
+        issues.push(Issue {
+            severity,
+            path: config_path.to_path_buf(),
+            message,
+        })
+    };
+
+    for required in ["homepage.html", "page.html"] {
+        if required == "homepage.html" && config.homepage.listing.is_some() {
+            continue;
+        }
+        if !site.has_template(required) {
+            issue(
+                Severity::Error,
+                format!("Template `{}` doesn't exist", required),
+            );
+        }
+    }
+    for (name, section) in &config.sections {
+        for template in [&section.template, &section.list_template] {
+            if !site.has_template(&site.section_template(name, template)) {
+                issue(
+                    Severity::Error,
+                    format!(
+                        "Section `{}` uses template `{}`, which doesn't exist",
+                        name, template
+                    ),
+                );
+            }
+        }
+        let dir = section.dir.as_deref().unwrap_or(name);
+        if !site.content_dir().join(dir).is_dir() {
+            issue(
+                Severity::Warning,
+                format!(
+                    "Section `{}` reads `{}` under src/content, which doesn't exist",
+                    name, dir
+                ),
+            );
+        }
+        if !section.print_books.is_empty()
+            && !site.has_template(&site.section_template(name, PRINT_TEMPLATE))
+        {
+            issue(
+                Severity::Error,
+                format!(
+                    "Section `{}` has print_books but there's no `{}` template",
+                    name, PRINT_TEMPLATE
+                ),
+            );
+        }
+        if let Some(split_on) = &section.split_on {
+            if parse_split_on(split_on).is_none() {
+                issue(
+                    Severity::Error,
+                    format!(
+                        "Section `{}` has unknown split_on `{}`, expected h1 to h6 or none",
+                        name, split_on
+                    ),
+                );
+            }
+        }
+    }
+    if config.maintenance.enabled && !site.has_template("maintenance.html") {
+        issue(
+            Severity::Error,
+            "Maintenance mode needs a `maintenance.html` template".to_string(),
+        );
+    }
+    if let Some(snippet) = &config.cta.snippet {
+        if !site.source_dir.join(snippet).is_file() {
+            issue(
+                Severity::Error,
+                format!("The `[cta]` snippet `{}` doesn't exist", snippet),
+            );
+        }
+    }
+    for name in &config.cta.sections {
+        if !config.sections.contains_key(name) {
+            issue(
+                Severity::Warning,
+                format!("`[cta]` lists `{}`, which isn't a section", name),
+            );
+        }
+    }
+    for hook in &config.hooks {
+        if hook.command.trim().is_empty() {
+            issue(Severity::Error, "A hook has an empty command".to_string());
+        }
+    }
+    issues
+}
+
+// Every site path a build writes that content may link to
+fn link_targets(site: &Site, files: &[PathBuf]) -> Result<HashSet<String>> {
+    let mut targets: HashSet<String> = [
+        "/index.html",
+        "/sitemap.xml",
+        "/index.json",
+        "/tags.json",
+        "/index.xml",
+        "/humans.txt",
+    ]
+    .map(String::from)
+    .into();
+    if site.config.tags_full_json {
+        targets.insert("/tags-full.json".to_string());
+    }
+    // The listing's own URL redirects to the homepage that shows it
+    if let Some((name, section)) = site
+        .config
+        .homepage
+        .listing
+        .as_ref()
+        .and_then(|name| site.config.sections.get_key_value(name))
+    {
+        targets.insert(tags::file_path(&own_listing_path(name, section)));
+    }
+    targets.extend(site.config.sections.iter().flat_map(|(name, section)| {
+        [
+            tags::file_path(&listing_path(&site.config, name, section, 1)),
+            format!("/{}/index.xml", name),
+        ]
+    }));
+
+    for (name, section) in &site.config.sections {
+        let dir = section.dir.as_deref().unwrap_or(name);
+        targets.extend(
+            section
+                .print_books
+                .iter()
+                .map(|book| format!("/{}/{}/print.html", dir, book)),
+        );
+    }
+
+    if site.has_template(tags::TAGS_TEMPLATE) {
+        targets.insert(tags::file_path(&tags::tags_index_path(site)));
+    }
+    let tag_pages = site.has_template(tags::TAG_TEMPLATE);
+    let content_dir = site.content_dir();
+    let mut section_posts: HashMap<&str, usize> = HashMap::new();
+    for path in files {
+        let Ok(source) = read_source(site, path) else {
+            continue;
+        };
+        // Drafts and posts under review aren't built, so links to them would break
+        if !is_published(site, &source.frontmatter).unwrap_or(true) {
+            continue;
+        }
+        targets.insert(page_url(site, &output_path(site, path)?));
+        if let Some((name, _)) = site.config.section_for(path.strip_prefix(&content_dir)?) {
+            *section_posts.entry(name).or_default() += 1;
+        }
+        if tag_pages {
+            for tag in extract_list(&source.frontmatter, "tags") {
+                targets.insert(tags::file_path(&tags::tag_path(site, &urls::slugify(&tag))));
+            }
+        }
+    }
+
+    // Later pages of paginated listings
+    for (name, section) in &site.config.sections {
+        let count = section_posts
+            .get(name.as_str())
+            .copied()
+            .unwrap_or_default();
+        for number in 2..=listing_page_count(section, count) {
+            targets.insert(tags::file_path(&listing_path(
+                &site.config,
+                name,
+                section,
+                number,
+            )));
+        }
+        if section.paginate.is_some_and(|per_page| per_page > 0)
+            && site.config.first_listing_page == FirstListingPage::Redirect
+        {
+            let first = listing_path(&site.config, name, section, 1);
+            targets.insert(tags::file_path(&numbered_listing_path(&first, 1)));
+        }
+    }
+
+    let assets_dir = site.source_dir.join("assets");
+    let ignore = IgnoreMatcher::new(
+        &site.config.assets.ignore,
+        site.config.assets.include_hidden,
+    )?;
+    for entry in WalkDir::new(&assets_dir).into_iter().filter_map(|e| e.ok()) {
+        let relative_path = entry.path().strip_prefix(&assets_dir)?;
+        if entry.file_type().is_file() && !ignore.is_ignored(relative_path) {
+            let relative_path = relative_path.to_string_lossy().replace('\\', "/");
+            targets.insert(format!("/assets/{}", relative_path));
+        }
+    }
+
+    for dir in assets::colocated_dirs(&content_dir, &site.config.assets.colocated_dir) {
+        let dir = content_dir.join(dir);
+        for entry in WalkDir::new(&dir).into_iter().filter_map(|e| e.ok()) {
+            let relative_path = entry.path().strip_prefix(&content_dir)?;
+            let ignored = ignore.is_ignored(entry.path().strip_prefix(&dir)?);
+            if entry.file_type().is_file() && !ignored {
+                let relative_path = relative_path.to_string_lossy().replace('\\', "/");
+                targets.insert(format!("/{}", relative_path));
+            }
+        }
+    }
+    Ok(targets)
+}
+
+// Whether an internal link points at something the build writes. External
+// links and pure fragments aren't checked.
+fn is_linked(site: &Site, targets: &HashSet<String>, page_url: &str, link: &str) -> bool {
+    let base_url = site.config.base_url.trim_end_matches('/');
+    let link = match link.strip_prefix(base_url) {
+        Some(path) if !base_url.is_empty() => path,
+        _ => link,
+    };
+    let path = link.split(['?', '#']).next().unwrap_or_default();
+    if path.is_empty() || urls::is_external(link) {
+        return true;
+    }
+
+    let path = match path.strip_prefix('/') {
+        Some(_) => path.to_string(),
+        None => {
+            let dir = page_url.rsplit_once('/').map_or("", |(dir, _)| dir);
+            resolve_dots(&format!("{}/{}", dir, path))
+        }
+    };
+
+    // Static hosts serve `/blog`, `/blog/` and `/blog.html` alike
+    let trimmed = path.trim_end_matches('/');
+    [
+        path.clone(),
+        format!("{}.html", trimmed),
+        format!("{}/index.html", trimmed),
+    ]
+    .iter()
+    .any(|candidate| targets.contains(candidate))
+}
+
+pub(crate) fn resolve_dots(path: &str) -> String {
+    let mut segments: Vec<&str> = vec![];
+    for segment in path.split('/') {
+        match segment {
+            ".." => {
+                segments.pop();
+            }
+            "." => {}
+            segment => segments.push(segment),
+        }
+    }
+    let resolved = segments.join("/");
+    if resolved.starts_with('/') {
+        resolved
+    } else {
+        format!("/{}", resolved)
+    }
+}
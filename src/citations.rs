@@ -0,0 +1,111 @@
+use crate::{escape_xml, Site};
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs};
+
+// Bibliography under `src`, one table per citation key
+const REFERENCES_FILE: &str = "references.toml";
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct Reference {
+    title: String,
+    author: Option<String>,
+    year: Option<i32>,
+    publisher: Option<String>,
+    url: Option<String>,
+}
+
+pub(crate) fn load_references(site: &Site) -> Result<BTreeMap<String, Reference>> {
+    let path = site.source_dir.join(REFERENCES_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(&path).context("Failed to read references")?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+// Number `[@key]` citations in order of first use, linking each to its entry
+// in a references list appended to the markdown. Returns the keys that aren't
+// in the bibliography, which are left as written. Code blocks are skipped.
+pub(crate) fn resolve_citations(
+    markdown: &str,
+    references: &BTreeMap<String, Reference>,
+) -> Result<(String, Vec<String>)> {
+    let citation = Regex::new(r"\[@([A-Za-z0-9_:.\-]+)\]")?;
+    let mut cited: Vec<&str> = vec![];
+    let mut unknown: Vec<String> = vec![];
+    let mut output = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        if in_fence {
+            output.push_str(line);
+        } else {
+            let line = citation.replace_all(line, |captures: &Captures| {
+                let key = &captures[1];
+                let Some((key, _)) = references.get_key_value(key) else {
+                    if !unknown.iter().any(|unknown| unknown == key) {
+                        unknown.push(key.to_string());
+                    }
+                    return captures[0].to_string();
+                };
+                let number = match cited.iter().position(|cited| cited == key) {
+                    Some(index) => index + 1,
+                    None => {
+                        cited.push(key);
+                        cited.len()
+                    }
+                };
+                format!(
+                    "<sup class=\"citation\"><a href=\"#ref-{}\">[{}]</a></sup>",
+                    escape_xml(key),
+                    number
+                )
+            });
+            output.push_str(&line);
+        }
+        output.push('\n');
+    }
+
+    if !cited.is_empty() {
+        output.push_str("\n<section class=\"references\">\n<h2>References</h2>\n<ol>\n");
+        for key in cited {
+            output.push_str(&format!(
+                "<li id=\"ref-{}\">{}</li>\n",
+                escape_xml(key),
+                format_reference(&references[key])
+            ));
+        }
+        output.push_str("</ol>\n</section>\n");
+    }
+    Ok((output, unknown))
+}
+
+// `Author (Year). Title. Publisher.` with whatever parts the entry has
+fn format_reference(reference: &Reference) -> String {
+    let mut formatted = String::new();
+    if let Some(author) = &reference.author {
+        formatted.push_str(&escape_xml(author));
+        if let Some(year) = reference.year {
+            formatted.push_str(&format!(" ({})", year));
+        }
+        formatted.push_str(". ");
+    }
+    let title = escape_xml(&reference.title);
+    match &reference.url {
+        Some(url) => formatted.push_str(&format!(
+            "<a href=\"{}\"><cite>{}</cite></a>.",
+            escape_xml(url),
+            title
+        )),
+        None => formatted.push_str(&format!("<cite>{}</cite>.", title)),
+    }
+    if let Some(publisher) = &reference.publisher {
+        formatted.push_str(&format!(" {}.", escape_xml(publisher)));
+    }
+    formatted
+}
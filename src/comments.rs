@@ -0,0 +1,48 @@
+use crate::{config::CommentsProvider, escape_xml, Site};
+
+// The provider's script tag, placed by templates where the thread goes. `None`
+// without a provider.
+pub(crate) fn comments_embed(site: &Site) -> Option<String> {
+    let config = &site.config.comments;
+    let attributes: Vec<(&str, &str)> = match config.provider? {
+        CommentsProvider::Giscus => vec![
+            ("src", "https://giscus.app/client.js"),
+            ("data-repo", &config.repo),
+            ("data-repo-id", &config.repo_id),
+            ("data-category", &config.category),
+            ("data-category-id", &config.category_id),
+            ("data-mapping", &config.mapping),
+            ("data-reactions-enabled", "1"),
+            ("data-input-position", "bottom"),
+            (
+                "data-theme",
+                config.theme.as_deref().unwrap_or("preferred_color_scheme"),
+            ),
+            ("data-lang", &site.config.lang),
+        ],
+        CommentsProvider::Utterances => {
+            let mut attributes = vec![
+                ("src", "https://utteranc.es/client.js"),
+                ("repo", config.repo.as_str()),
+                ("issue-term", &config.mapping),
+                (
+                    "theme",
+                    config.theme.as_deref().unwrap_or("preferred-color-scheme"),
+                ),
+            ];
+            if let Some(label) = &config.label {
+                attributes.push(("label", label));
+            }
+            attributes
+        }
+    };
+
+    let attributes: String = attributes
+        .iter()
+        .map(|(name, value)| format!(" {}=\"{}\"", name, escape_xml(value)))
+        .collect();
+    Some(format!(
+        "<script{} crossorigin=\"anonymous\" async></script>",
+        attributes
+    ))
+}
@@ -0,0 +1,184 @@
+use crate::{
+    config::{CompressConfig, CompressFormat},
+    has_extension, Site,
+};
+use anyhow::{Context, Result};
+use brotli::CompressorWriter;
+use flate2::{write::GzEncoder, Compression};
+use serde::Serialize;
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+// Text files worth compressing, images and fonts already are
+const COMPRESSIBLE: &[&str] = &[
+    "css",
+    "htm",
+    "html",
+    "js",
+    "json",
+    "mjs",
+    "svg",
+    "txt",
+    "webmanifest",
+    "xml",
+];
+
+// What one format did to the files it compressed
+#[derive(Debug, Serialize)]
+pub struct Compressed {
+    pub format: CompressFormat,
+    pub files: usize,
+    pub bytes: u64,
+    pub compressed_bytes: u64,
+}
+
+// Out of range levels fail the build up front rather than halfway through
+pub(crate) fn check_levels(config: &CompressConfig) -> Result<()> {
+    if config.gzip_level > 9 {
+        anyhow::bail!(
+            "`gzip_level = {}` under `[compress]` is out of range, it goes from 0 to 9",
+            config.gzip_level
+        );
+    }
+    if config.brotli_quality > 11 {
+        anyhow::bail!(
+            "`brotli_quality = {}` under `[compress]` is out of range, it goes from 0 to 11",
+            config.brotli_quality
+        );
+    }
+    Ok(())
+}
+
+// Write `<file>.gz` and `<file>.br` next to the text files in dist. Every
+// copy is written again each build, so none goes stale, and ones whose file
+// is gone, too small now or no longer wanted are removed. A copy that
+// wouldn't be smaller than the file isn't written.
+pub(crate) fn compress_dist(site: &Site) -> Result<Vec<Compressed>> {
+    let config = &site.config.compress;
+    let mut totals: Vec<Compressed> = vec![];
+    for format in &config.formats {
+        if !totals.iter().any(|total| total.format == *format) {
+            totals.push(Compressed {
+                format: *format,
+                files: 0,
+                bytes: 0,
+                compressed_bytes: 0,
+            });
+        }
+    }
+
+    let files: Vec<_> = WalkDir::new(&site.dist_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.into_path())
+        .collect();
+    for path in &files {
+        if has_extension(path, &["gz", "br"]) {
+            let original = path.with_extension("");
+            if has_extension(&original, COMPRESSIBLE) && !original.is_file() {
+                remove_copy(path)?;
+            }
+            continue;
+        }
+        if !has_extension(path, COMPRESSIBLE) {
+            continue;
+        }
+        let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        for format in [CompressFormat::Gzip, CompressFormat::Brotli] {
+            let copy = copy_path(path, format);
+            let total = totals
+                .iter_mut()
+                .find(|total| total.format == format)
+                .filter(|_| (bytes.len() as u64) >= config.min_size);
+            let Some(total) = total else {
+                remove_copy(&copy)?;
+                continue;
+            };
+            let compressed = match format {
+                CompressFormat::Gzip => gzip(&bytes, config.gzip_level)?,
+                CompressFormat::Brotli => brotli(&bytes, config.brotli_quality)?,
+            };
+            if compressed.len() >= bytes.len() {
+                remove_copy(&copy)?;
+                continue;
+            }
+            fs::write(&copy, &compressed)
+                .with_context(|| format!("Failed to write {}", copy.display()))?;
+            total.files += 1;
+            total.bytes += bytes.len() as u64;
+            total.compressed_bytes += compressed.len() as u64;
+        }
+    }
+    Ok(totals)
+}
+
+fn copy_path(path: &Path, format: CompressFormat) -> PathBuf {
+    let mut copy = path.as_os_str().to_owned();
+    copy.push(".");
+    copy.push(format.extension());
+    copy.into()
+}
+
+fn remove_copy(path: &Path) -> Result<()> {
+    if path.is_file() {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+// No modification time in the header, so unchanged files compress the same
+fn gzip(bytes: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(bytes).context("Failed to gzip")?;
+    encoder.finish().context("Failed to gzip")
+}
+
+// The reference encoder's 4 MB window
+fn brotli(bytes: &[u8], quality: u32) -> Result<Vec<u8>> {
+    let mut encoder = CompressorWriter::new(Vec::new(), 4096, quality, 22);
+    encoder
+        .write_all(bytes)
+        .context("Failed to compress with brotli")?;
+    Ok(encoder.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_levels;
+    use crate::config::CompressConfig;
+
+    #[test]
+    fn rejects_out_of_range_levels() {
+        check_levels(&CompressConfig {
+            gzip_level: 9,
+            brotli_quality: 11,
+            ..CompressConfig::default()
+        })
+        .unwrap();
+
+        let err = check_levels(&CompressConfig {
+            gzip_level: 10,
+            ..CompressConfig::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`gzip_level = 10` under `[compress]` is out of range, it goes from 0 to 9"
+        );
+        let err = check_levels(&CompressConfig {
+            brotli_quality: 12,
+            ..CompressConfig::default()
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "`brotli_quality = 12` under `[compress]` is out of range, it goes from 0 to 11"
+        );
+    }
+}
@@ -0,0 +1,1159 @@
+use crate::urls;
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    // Scheme and host the site is served from, used for absolute URLs
+    pub base_url: String,
+    // `example.com` or `www.example.com`, whichever all absolute URLs should
+    // use. Visitors on the other one are sent over by a script in the page.
+    pub canonical_host: Option<String>,
+    // Language of the content for `<html lang>`, a post can set its own `lang`
+    pub lang: String,
+    // `ltr` or `rtl` for `<html dir>`, follows `lang` when unset
+    pub dir: Option<String>,
+    // Other languages the site is translated into, each a directory under
+    // `src/content` that mirrors its layout: `fr/blog/hello.md` is the French
+    // `blog/hello.md`
+    pub locales: Vec<String>,
+    // Write section posts a locale has no translation of under its directory
+    // too, with the default language's content and `translation_fallback` set
+    pub i18n_fallback: bool,
+    // Path the site is served under, e.g. `/blog` for `example.com/blog/`
+    pub base_path: String,
+    // Build environment, anything but `production` is kept out of search engines
+    pub env: String,
+    // Build posts marked `draft: true` too
+    pub drafts: bool,
+    // Values of the `status` frontmatter key from least to most finished. Only
+    // the last one is built, posts without a status count as that one.
+    pub statuses: Vec<String>,
+    // Also build posts with this status and the ones after it, e.g. `review`
+    // for a preview deploy
+    pub status: Option<String>,
+    // Fail the build on warnings, e.g. posts without a body
+    pub strict: bool,
+    // Fail the build when a template tests a variable with `{% if %}` that
+    // the page doesn't have, instead of taking it as false
+    pub strict_templates: bool,
+    // Threads for parallel work like processing images, one per logical CPU
+    // when unset. `1` does everything in order.
+    pub threads: Option<usize>,
+    // Check rendered posts for unclosed tags and duplicate ids, warning about them
+    pub validate_html: bool,
+    // Warn about links to `#fragments` no element of the target page has as
+    // its id, on by default
+    pub check_anchors: bool,
+    // Warn about local images posts show that the build didn't write, on by
+    // default
+    pub check_images: bool,
+    // Use a fixed build time so unchanged sources give byte-identical output,
+    // the Unix epoch unless `source_date_epoch` is set
+    pub reproducible: bool,
+    // Seconds since the Unix epoch to use as the build time, from the
+    // `SOURCE_DATE_EPOCH` environment variable. Implies `reproducible`.
+    #[serde(skip)]
+    pub source_date_epoch: Option<i64>,
+    // Only write pages of posts dated, updated or edited on or after this day.
+    // Set from the command line.
+    #[serde(skip)]
+    pub since: Option<NaiveDate>,
+    // Only render this content file, plus the listings, feeds and other indexes
+    // with `with_listings`. Set from the command line.
+    #[serde(skip)]
+    pub only: Option<PathBuf>,
+    #[serde(skip)]
+    pub with_listings: bool,
+    // Posts dated more than this many days before the build are left out of
+    // the homepage and listings, and listed as `archived` on a section's last
+    // listing page. Their pages are still built.
+    pub listing_cutoff_days: Option<i64>,
+    // Posts dated more than this many days before the build are left out of feeds
+    pub feed_cutoff_days: Option<i64>,
+    // Posts `updated` this many days before the build or later are
+    // `recently_updated`, for an "Updated" badge in listings
+    pub updated_badge_days: Option<i64>,
+    // Section posts not `updated` in more than this many days are listed in
+    // the build report, up to `stale_posts_listed` of them
+    pub stale_after_days: Option<i64>,
+    pub stale_posts_listed: usize,
+    // Whether internal links to directories end with a slash
+    pub trailing_slash: TrailingSlash,
+    // What to do when two content files would be written to the same page
+    pub on_slug_collision: SlugCollision,
+    // What a build does when another one is writing the same output
+    pub on_locked_build: LockedBuild,
+    // Remove the output's lock before building, left by a crashed build
+    #[serde(skip)]
+    pub force_unlock: bool,
+    // Write tag pages as `tags/<slug>/index.html` and link them by directory
+    pub clean_urls: bool,
+    // Posts only show their `updated` date when it's more than this many days
+    // after `date`, so a same-day typo fix doesn't count
+    pub show_updated_after_days: i64,
+    // Accepted formats for `date` and `updated` in frontmatter, tried in order
+    pub date_formats: Vec<String>,
+    // Old frontmatter keys mapped to the ones they stand for, e.g. `thumbnail = "image"`
+    pub frontmatter_aliases: BTreeMap<String, String>,
+    // Values for frontmatter keys a file leaves out, e.g. `tags = []`
+    pub frontmatter_defaults: BTreeMap<String, serde_yaml::Value>,
+    // Types frontmatter values are converted to, e.g. `weight = "integer"`.
+    // Values that can't be converted fail the build.
+    pub frontmatter_types: BTreeMap<String, FrontmatterType>,
+    // Also list `redirect_from` entries in a Netlify style `_redirects` file
+    pub redirects_file: bool,
+    // Write every post as `<slug>.json` next to its page too
+    pub emit_json: bool,
+    // Write each post's table of contents to `<slug>/toc.json` too
+    pub toc_json: bool,
+    // Write `stats.json` with word counts and the most frequent words
+    pub stats: bool,
+    // Write `.manifest.json` with the files each source file produced
+    pub build_manifest: bool,
+    // List the pages the build added, removed and changed in dist
+    pub diff_report: bool,
+    // Write `posts.csv` with a row of metadata per post
+    pub csv: bool,
+    // Write `tags-full.json` with every tag's posts
+    pub tags_full_json: bool,
+    // Strip trailing whitespace and long runs of blank lines from the HTML
+    // written, keeping diffs of a committed `dist` readable
+    pub tidy: bool,
+    // Add a `content-hash` meta tag to every page, leaving the build time and
+    // commit out of it
+    pub content_hash: bool,
+    // Reading speed behind the reading time estimate, and how it's shown with
+    // `{}` standing for the minutes
+    pub reading_time_wpm: usize,
+    pub reading_time_label: String,
+    // Labels like "Quick read" for ranges of reading times, the first bucket
+    // a post's minutes fit in wins
+    pub reading_buckets: Vec<ReadingBucket>,
+    // Image of posts that don't set one in their frontmatter
+    pub default_image: String,
+    // Where readers can edit a post's source, `{path}` is replaced with the
+    // file's path from the directory holding `src`, e.g.
+    // `https://github.com/mumudevx/personal-website/edit/main/{path}`
+    pub edit_url_template: Option<String>,
+    // Origin serving a copy of `dist`, e.g. `https://cdn.example.com`, local
+    // images are linked there instead of the site
+    pub image_cdn: Option<String>,
+    // How many of the latest posts the homepage lists as `posts`
+    pub homepage_post_count: usize,
+    // Keep listing pages after the first out of search results and the
+    // sitemap, while their links are still followed
+    pub noindex_pagination: bool,
+    // Whether paginated listings also answer at `<section>-1.html`, with a
+    // redirect to the first page, for links from generators that used it
+    pub first_listing_page: FirstListingPage,
+    // How many of the latest posts of all sections every template gets as
+    // `latest_across`, zero leaves it out
+    pub latest_across: usize,
+    // How many `related` posts post pages get, other entries of their
+    // `series` first and then the ones sharing tags, zero leaves it out
+    pub related_posts: usize,
+    // Stylesheets under `src/assets` whose contents go into a `<style>` block on
+    // every page. They're still copied as usual.
+    pub inline_css: Vec<String>,
+    // Unix permissions every output file gets, e.g. `0o644`. Copied assets
+    // otherwise keep the mode of their source, exec bit included.
+    pub file_mode: Option<u32>,
+    // Site-wide rules for the `_redirects` and `_headers` files
+    pub redirects: Vec<RedirectRule>,
+    pub headers: Vec<HeaderRule>,
+    // Shell commands run before and after every build
+    pub hooks: Vec<HookConfig>,
+    // Directory of the Tera templates, `template` under the source directory
+    // when unset
+    pub template_dir: Option<PathBuf>,
+    // Directory the site is written to, `dist` when unset. `DIST_DIR` and
+    // `--dist` take precedence.
+    pub dist_dir: Option<PathBuf>,
+    // Sites built from the same checkout, each sharing the rest of this config
+    pub sites: Vec<SiteEntry>,
+    pub author: AuthorConfig,
+    // Profiles for the author box, keyed by what posts put in `author`
+    pub authors: BTreeMap<String, AuthorProfile>,
+    pub sections: BTreeMap<String, SectionConfig>,
+    // Section for files in a directory under `src/content` that isn't any
+    // section's, which are generic pages without one
+    pub default_section: Option<String>,
+    // Fail the build on those files instead, when there's no default_section
+    pub require_section: bool,
+    pub nav: Vec<NavItem>,
+    pub markdown: MarkdownConfig,
+    pub assets: AssetsConfig,
+    pub homepage: HomepageConfig,
+    pub not_found: NotFoundConfig,
+    pub feed: FeedConfig,
+    pub maintenance: MaintenanceConfig,
+    pub amp: AmpConfig,
+    pub updates: UpdatesConfig,
+    pub favicon: FaviconConfig,
+    pub comments: CommentsConfig,
+    pub remote: RemoteConfig,
+    pub cta: CtaConfig,
+    pub compress: CompressConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        // Without a config the site keeps its original blog and books sections
+        Self {
+            base_url: String::new(),
+            canonical_host: None,
+            lang: "en".to_string(),
+            dir: None,
+            locales: vec![],
+            i18n_fallback: false,
+            base_path: String::new(),
+            env: "production".to_string(),
+            drafts: false,
+            statuses: ["draft", "review", "published"].map(String::from).to_vec(),
+            status: None,
+            strict: false,
+            strict_templates: false,
+            threads: None,
+            validate_html: false,
+            check_anchors: true,
+            check_images: true,
+            reproducible: false,
+            source_date_epoch: None,
+            since: None,
+            only: None,
+            with_listings: false,
+            listing_cutoff_days: None,
+            feed_cutoff_days: None,
+            updated_badge_days: None,
+            stale_after_days: None,
+            stale_posts_listed: 10,
+            trailing_slash: TrailingSlash::default(),
+            on_slug_collision: SlugCollision::default(),
+            on_locked_build: LockedBuild::default(),
+            force_unlock: false,
+            clean_urls: false,
+            show_updated_after_days: 0,
+            date_formats: vec!["%Y-%m-%d".to_string()],
+            frontmatter_aliases: BTreeMap::new(),
+            frontmatter_defaults: BTreeMap::new(),
+            frontmatter_types: BTreeMap::new(),
+            redirects_file: false,
+            emit_json: false,
+            toc_json: false,
+            stats: false,
+            build_manifest: false,
+            diff_report: false,
+            csv: false,
+            tags_full_json: false,
+            tidy: false,
+            content_hash: false,
+            reading_time_wpm: 200,
+            homepage_post_count: 5,
+            noindex_pagination: false,
+            first_listing_page: FirstListingPage::default(),
+            latest_across: 5,
+            related_posts: 3,
+            reading_time_label: "{} min read".to_string(),
+            reading_buckets: vec![
+                ReadingBucket::new(Some(3), "Quick read"),
+                ReadingBucket::new(Some(10), "Medium read"),
+                ReadingBucket::new(None, "Long read"),
+            ],
+            default_image: "/assets/images/rubber-duck.jpg".to_string(),
+            edit_url_template: None,
+            image_cdn: None,
+            inline_css: vec![],
+            file_mode: None,
+            redirects: vec![],
+            headers: vec![],
+            hooks: vec![],
+            template_dir: None,
+            dist_dir: None,
+            sites: vec![],
+            author: AuthorConfig::default(),
+            authors: BTreeMap::new(),
+            sections: BTreeMap::from([
+                (
+                    "blog".to_string(),
+                    SectionConfig::new("blog_detail.html", "blog_list.html"),
+                ),
+                (
+                    "books".to_string(),
+                    SectionConfig::new("book_detail.html", "book_list.html"),
+                ),
+            ]),
+            default_section: None,
+            require_section: false,
+            nav: vec![
+                NavItem::new("Home", "/"),
+                NavItem::new("Blog", "/blog.html"),
+                NavItem::new("Books", "/books.html"),
+            ],
+            markdown: MarkdownConfig::default(),
+            assets: AssetsConfig::default(),
+            homepage: HomepageConfig::default(),
+            not_found: NotFoundConfig::default(),
+            feed: FeedConfig::default(),
+            maintenance: MaintenanceConfig::default(),
+            amp: AmpConfig::default(),
+            updates: UpdatesConfig::default(),
+            favicon: FaviconConfig::default(),
+            comments: CommentsConfig::default(),
+            remote: RemoteConfig::default(),
+            cta: CtaConfig::default(),
+            compress: CompressConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SlugCollision {
+    // Stop the build
+    #[default]
+    Error,
+    // Give the later files `-2`, `-3`, ...
+    Suffix,
+    // Name the later files after their whole file name, extension included
+    Path,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LockedBuild {
+    // Stop right away
+    #[default]
+    Fail,
+    // Start once the other build is done
+    Wait,
+}
+
+// Files `image_cdn` serves, icons stay on the site
+const IMAGE_EXTENSIONS: &[&str] = &["avif", "gif", "jpeg", "jpg", "png", "svg", "webp"];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrailingSlash {
+    Always,
+    Never,
+    #[default]
+    Preserve,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FirstListingPage {
+    #[default]
+    Skip,
+    Redirect,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RedirectRule {
+    pub from: String,
+    pub to: String,
+    #[serde(default = "default_redirect_status")]
+    pub status: u16,
+}
+
+fn default_redirect_status() -> u16 {
+    301
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HeaderRule {
+    // Path pattern the headers apply to, e.g. `/*` or `/assets/*`
+    #[serde(rename = "for")]
+    pub path: String,
+    pub values: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct HookConfig {
+    pub stage: HookStage,
+    pub command: String,
+}
+
+// One of the `[[sites]]`, its paths are relative to the project root
+#[derive(Debug, Deserialize)]
+pub struct SiteEntry {
+    pub name: String,
+    #[serde(default = "default_site_source")]
+    pub source: PathBuf,
+    #[serde(default = "default_site_dist")]
+    pub dist: PathBuf,
+    pub base_url: Option<String>,
+    pub template_dir: Option<PathBuf>,
+}
+
+fn default_site_source() -> PathBuf {
+    PathBuf::from("src")
+}
+
+fn default_site_dist() -> PathBuf {
+    PathBuf::from("dist")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookStage {
+    PreBuild,
+    PostBuild,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct AuthorConfig {
+    pub name: Option<String>,
+    pub contact: Option<String>,
+    pub location: Option<String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthorProfile {
+    pub name: String,
+    pub avatar: Option<String>,
+    pub bio: Option<String>,
+    pub links: Vec<AuthorLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuthorLink {
+    pub label: String,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MarkdownConfig {
+    // Wrap code blocks with the `_code_copy.html` copy-to-clipboard button
+    pub code_copy: bool,
+    // Heading levels that get an anchor and show up in the table of contents
+    pub toc_min_level: u8,
+    pub toc_max_level: u8,
+    // Posts with fewer headings than this get an empty `toc`
+    pub toc_min_headings: usize,
+    // Heading of the table of contents, unless a post sets `toc_title`
+    pub toc_title: String,
+    // Directory under `src` for markdown shared through `{{< include "..." >}}`
+    pub includes_dir: String,
+    // Encoding of content files that aren't UTF-8, e.g. `windows-1254`.
+    // Guessed from each file's bytes when unset.
+    pub fallback_encoding: Option<String>,
+    // `Term` / `: Definition` paragraphs become `<dl>` lists
+    pub definition_lists: bool,
+    // `*[HTML]: HyperText Markup Language` lines mark the first use with `<abbr>`
+    pub abbreviations: bool,
+    // `{#id .class key=value}` after a heading or paragraph, or on a
+    // paragraph of its own after a quote or list, sets its attributes
+    pub attributes: bool,
+    // Names of `<!-- layout: wide -->` comments that set template variables
+    // instead of ending up in the page, none by default
+    pub directives: Vec<String>,
+    // Markers ending a post's excerpt, the first one found in the body wins
+    pub excerpt_separators: Vec<String>,
+    // Whether `post.excerpt` is the rendered HTML or its plain text, listings
+    // get both as `excerpt_html` and `excerpt_text` either way
+    pub excerpt_format: ExcerptFormat,
+    // `<Note>` ... `</Note>` blocks wrapped in HTML, `{}` marks where the
+    // rendered markdown inside goes, e.g. `Note = "<div class=\"note\">{}</div>"`
+    pub components: BTreeMap<String, String>,
+    // Give the first paragraph of a post `lead_class`, for a drop cap or
+    // larger text
+    pub lead_paragraph: bool,
+    pub lead_class: String,
+    // Bare `https://...` URLs outside code become links
+    pub autolink: bool,
+    // Paragraphs get an id from a hash of their text, for links to them
+    pub paragraph_ids: bool,
+    // Quotes opening with `[!NOTE]`, `[!TIP]`, `[!WARNING]` or `[!DANGER]`
+    // become callouts, titled with the kind's label
+    pub admonitions: bool,
+    // Titles of the callouts by kind, e.g. `warning = "Careful"`. Kinds left
+    // out are titled `Note`, `Tip`, `Warning` and `Danger`.
+    pub admonition_labels: BTreeMap<String, String>,
+}
+
+impl Default for MarkdownConfig {
+    fn default() -> Self {
+        Self {
+            code_copy: false,
+            toc_min_level: 2,
+            toc_max_level: 3,
+            toc_min_headings: 0,
+            toc_title: "Contents".to_string(),
+            includes_dir: "includes".to_string(),
+            fallback_encoding: None,
+            definition_lists: false,
+            abbreviations: false,
+            attributes: false,
+            directives: vec![],
+            excerpt_separators: vec!["<!-- more -->".to_string()],
+            excerpt_format: ExcerptFormat::default(),
+            components: BTreeMap::new(),
+            lead_paragraph: false,
+            lead_class: "lead".to_string(),
+            autolink: false,
+            paragraph_ids: false,
+            admonitions: false,
+            admonition_labels: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExcerptFormat {
+    #[default]
+    Html,
+    Text,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct AssetsConfig {
+    // Re-encode JPEG and PNG assets, stripping their metadata
+    pub optimize_images: bool,
+    // JPEG quality from 1 to 100
+    pub image_quality: u8,
+    // Images smaller than this many bytes are copied as they are
+    pub optimize_threshold: u64,
+    // Glob patterns of files that are never copied, e.g. editor leftovers
+    pub ignore: Vec<String>,
+    // Copy dotfiles and the contents of dot directories too
+    pub include_hidden: bool,
+    // Write `name-480w.jpg` style copies of JPEG and PNG assets at these widths
+    // and give post images a `srcset`. Images narrower than the largest width
+    // are left as they are.
+    pub responsive_images: bool,
+    pub responsive_widths: Vec<u32>,
+    // `sizes` attribute of the responsive post images
+    pub responsive_sizes: String,
+    // Directories of this name under `src/content`, e.g. `blog/trip/images`
+    // next to `blog/trip/index.md`, are copied to the same place in `dist`.
+    // Empty to treat them as content.
+    pub colocated_dir: String,
+    // Images smaller than this many bytes are written into pages as `data:`
+    // URIs, 0 never inlines
+    pub inline_images_below: u64,
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self {
+            optimize_images: false,
+            image_quality: 80,
+            optimize_threshold: 100 * 1024,
+            ignore: [".DS_Store", "Thumbs.db", "desktop.ini", "*.swp", "*~"]
+                .map(String::from)
+                .to_vec(),
+            include_hidden: false,
+            responsive_images: false,
+            responsive_widths: vec![480, 800, 1200],
+            responsive_sizes: "(max-width: 800px) 100vw, 800px".to_string(),
+            colocated_dir: "images".to_string(),
+            inline_images_below: 0,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct HomepageConfig {
+    // How many posts after the featured latest one the homepage lists
+    pub recent_posts: usize,
+    // Section whose listing is the homepage, for a flat blog. Its own listing
+    // URL redirects there and `homepage.html` isn't needed.
+    pub listing: Option<String>,
+}
+
+impl Default for HomepageConfig {
+    fn default() -> Self {
+        Self {
+            recent_posts: 3,
+            listing: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NotFoundConfig {
+    // How many of the latest posts the 404 page suggests, none by default
+    pub recent_posts: usize,
+    // Where the search page lives, linked from the 404 page when set
+    pub search_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct RemoteConfig {
+    // Whether `{{< remote "https://..." >}}` fetches anything, off by default
+    pub enabled: bool,
+    pub timeout_secs: u64,
+    // How long a fetched copy is used before it's fetched again
+    pub cache_hours: u64,
+    pub cache_dir: PathBuf,
+}
+
+impl Default for RemoteConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout_secs: 10,
+            cache_hours: 24,
+            cache_dir: PathBuf::from(".remote-cache"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CompressConfig {
+    // Write `.gz` and `.br` copies of the text files in dist for servers that
+    // send precompressed files as they are, off by default
+    pub enabled: bool,
+    pub formats: Vec<CompressFormat>,
+    // 0 to 9
+    pub gzip_level: u32,
+    // 0 to 11, higher ones take much longer for a few percent
+    pub brotli_quality: u32,
+    // Files smaller than this many bytes are left alone, they can even grow
+    pub min_size: u64,
+}
+
+impl Default for CompressConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            formats: vec![CompressFormat::Gzip, CompressFormat::Brotli],
+            gzip_level: 9,
+            brotli_quality: 5,
+            min_size: 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressFormat {
+    Gzip,
+    Brotli,
+}
+
+impl CompressFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            CompressFormat::Gzip => "gz",
+            CompressFormat::Brotli => "br",
+        }
+    }
+}
+
+impl fmt::Display for CompressFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            CompressFormat::Gzip => "gzip",
+            CompressFormat::Brotli => "brotli",
+        })
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct UpdatesConfig {
+    // How far back, in days, `updated` dates count for the updates page
+    pub days: i64,
+}
+
+impl Default for UpdatesConfig {
+    fn default() -> Self {
+        Self { days: 90 }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CtaConfig {
+    // Markdown or HTML file under `src` appended to every post, e.g. a
+    // newsletter signup. Posts turn it off with `no_cta: true`.
+    pub snippet: Option<String>,
+    // Sections whose posts get it, all of them when empty
+    pub sections: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct FaviconConfig {
+    // Generate `favicon.ico`, PNG icons and `apple-touch-icon.png` from `source`
+    pub enabled: bool,
+    // Square image under `src` the icons are made from
+    pub source: String,
+    // Sizes of the `favicon-<size>x<size>.png` icons
+    pub sizes: Vec<u32>,
+    pub apple_touch_size: u32,
+}
+
+impl Default for FaviconConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            source: "assets/favicon.png".to_string(),
+            sizes: vec![16, 32, 192, 512],
+            apple_touch_size: 180,
+        }
+    }
+}
+
+// Hosted comments embedded in posts of sections with the `comments` feature
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct CommentsConfig {
+    pub provider: Option<CommentsProvider>,
+    // GitHub repository holding the discussions or issues, `owner/name`
+    pub repo: String,
+    // IDs giscus shows on its configuration page
+    pub repo_id: String,
+    pub category: String,
+    pub category_id: String,
+    // What ties a post to its thread: `pathname`, `url`, `title`, ...
+    pub mapping: String,
+    // Defaults to following the reader's light or dark preference
+    pub theme: Option<String>,
+    // Label utterances puts on the issues it opens
+    pub label: Option<String>,
+}
+
+impl Default for CommentsConfig {
+    fn default() -> Self {
+        Self {
+            provider: None,
+            repo: String::new(),
+            repo_id: String::new(),
+            category: String::new(),
+            category_id: String::new(),
+            mapping: "pathname".to_string(),
+            theme: None,
+            label: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentsProvider {
+    Giscus,
+    Utterances,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceConfig {
+    // Build only the `maintenance.html` placeholder instead of the site
+    pub enabled: bool,
+    // Files under `src/assets` the placeholder needs, nothing else is copied
+    pub assets: Vec<String>,
+}
+
+impl Default for MaintenanceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            assets: vec!["styles.css".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct AmpConfig {
+    // Also write an AMP version of every section post at `<slug>/amp.html`
+    pub enabled: bool,
+    pub template: String,
+    // Stylesheet under `src/assets` inlined into the AMP pages
+    pub stylesheet: String,
+}
+
+impl Default for AmpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            template: "amp.html".to_string(),
+            stylesheet: "styles.css".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct FeedConfig {
+    // Channel title, section feeds append the section name
+    pub title: String,
+    // Channel description, the title when unset
+    pub description: Option<String>,
+    // Most items a feed lists, newest first
+    pub limit: usize,
+    // Whether items carry just the description or the whole post too
+    pub content: FeedContent,
+    // Also write a feed per tag at `/tags/<tag>/feed.xml`
+    pub tags: bool,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            title: "Latest posts".to_string(),
+            description: None,
+            limit: 20,
+            content: FeedContent::default(),
+            tags: false,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedContent {
+    #[default]
+    Summary,
+    Full,
+}
+
+// A section feed's own settings, the `[feed]` ones apply to anything left out
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct SectionFeedConfig {
+    // Instead of the `[feed]` title with the section name appended
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub limit: Option<usize>,
+    pub content: Option<FeedContent>,
+}
+
+// Features a section's posts get, passed to their templates as `features`.
+// Flags besides these are passed on as they are for templates to check.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SectionFeatures {
+    pub toc: bool,
+    pub reading_time: bool,
+    pub related_posts: bool,
+    // Whether templates embed comments, posts can turn them off with `comments: false`
+    pub comments: bool,
+    #[serde(flatten)]
+    pub custom: BTreeMap<String, bool>,
+}
+
+impl Default for SectionFeatures {
+    fn default() -> Self {
+        Self {
+            toc: true,
+            reading_time: true,
+            related_posts: true,
+            comments: false,
+            custom: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SectionConfig {
+    // Subdirectory of `src/content`, defaults to the section name
+    pub dir: Option<String>,
+    pub template: String,
+    pub list_template: String,
+    // Where under the template directory the section's own templates are
+    // looked up first, defaults to the section name
+    #[serde(default)]
+    pub template_dir: Option<String>,
+    // Listing order: `date`, `title`, `weight` or any other frontmatter key, e.g.
+    // `chapter`. Posts without the key come last, unsorted listings keep file
+    // name order.
+    #[serde(default)]
+    pub sort_by: Option<String>,
+    #[serde(default)]
+    pub sort_order: SortOrder,
+    // Weight of posts without one when sorting by `weight`, so they sink to
+    // the end by default
+    #[serde(default = "default_weight")]
+    pub default_weight: i64,
+    // `month` gives the listing template `groups` of posts under month headers
+    #[serde(default)]
+    pub group_listing_by: ListingGroup,
+    // Posts per listing page, later pages are written as `<section>-2.html`, ...
+    #[serde(default)]
+    pub paginate: Option<usize>,
+    // Warn about posts without a `date`, for sections sorted by it
+    #[serde(default)]
+    pub require_date: bool,
+    // Site path of the listing instead of `/<section>.html`, e.g. `/blog/`
+    // for `blog/index.html`
+    #[serde(default)]
+    pub list_path: Option<String>,
+    // Whether posts get heading anchors and a table of contents, unless their
+    // frontmatter says otherwise with `toc: true|false`
+    #[serde(default = "default_toc")]
+    pub toc: bool,
+    // How many levels posts' headings move down, e.g. `1` turns `#` into `<h2>`,
+    // unless their frontmatter sets its own `heading_shift`
+    #[serde(default)]
+    pub heading_shift: u8,
+    // Prefix anchored headings with `1`, `1.1`, `1.1.1` numbers, unless their
+    // frontmatter says otherwise with `number_headings: true|false`
+    #[serde(default)]
+    pub number_headings: bool,
+    // Split posts into a page per heading of this level, e.g. `h2`, unless
+    // their frontmatter says otherwise with `split_on: h3|none`
+    #[serde(default)]
+    pub split_on: Option<String>,
+    // Resolve `[@key]` citations against `src/references.toml`
+    #[serde(default)]
+    pub citations: bool,
+    #[serde(default)]
+    pub feed: SectionFeedConfig,
+    #[serde(default)]
+    pub features: SectionFeatures,
+    // Books, directories under the section's, that get a `print.html` with all
+    // their chapters on one page
+    #[serde(default)]
+    pub print_books: Vec<String>,
+    // How many of its latest posts the homepage shows in `sections_preview`
+    #[serde(default = "default_homepage_posts")]
+    pub homepage_posts: usize,
+    // `og:type` of the section's posts, `article` unless set. Posts can set
+    // their own with `og_type`.
+    #[serde(default)]
+    pub og_type: Option<String>,
+}
+
+fn default_toc() -> bool {
+    true
+}
+
+fn default_weight() -> i64 {
+    i64::MAX
+}
+
+fn default_homepage_posts() -> usize {
+    3
+}
+
+impl SectionConfig {
+    pub fn new(template: &str, list_template: &str) -> Self {
+        Self {
+            dir: None,
+            template: template.to_string(),
+            list_template: list_template.to_string(),
+            template_dir: None,
+            sort_by: None,
+            sort_order: SortOrder::default(),
+            default_weight: default_weight(),
+            group_listing_by: ListingGroup::default(),
+            paginate: None,
+            require_date: false,
+            list_path: None,
+            toc: true,
+            heading_shift: 0,
+            number_headings: false,
+            split_on: None,
+            citations: false,
+            feed: SectionFeedConfig::default(),
+            features: SectionFeatures::default(),
+            print_books: vec![],
+            homepage_posts: default_homepage_posts(),
+            og_type: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrontmatterType {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    List,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListingGroup {
+    #[default]
+    None,
+    Month,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReadingBucket {
+    // Most minutes a post can take to be in the bucket, any without it
+    pub up_to: Option<usize>,
+    pub label: String,
+}
+
+impl ReadingBucket {
+    pub fn new(up_to: Option<usize>, label: &str) -> Self {
+        Self {
+            up_to,
+            label: label.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NavItem {
+    pub label: String,
+    pub url: String,
+}
+
+impl NavItem {
+    fn new(label: &str, url: &str) -> Self {
+        Self {
+            label: label.to_string(),
+            url: url.to_string(),
+        }
+    }
+}
+
+impl Config {
+    // Load the config, with the `[profile.<name>]` table merged over the base
+    // one when a profile is given. Keys the profile leaves out are inherited.
+    pub fn load(path: &str, profile: Option<&str>) -> Result<Self> {
+        // A missing config file is fine, everything falls back to defaults
+        if !Path::new(path).exists() {
+            match profile {
+                Some(profile) => {
+                    anyhow::bail!("Unknown profile `{}`, {} is missing", profile, path)
+                }
+                None => return Ok(Self::default()),
+            }
+        }
+
+        let content = fs::read_to_string(path).context("Failed to read config file")?;
+        let mut table: toml::Table =
+            toml::from_str(&content).with_context(|| format!("Failed to parse {}", path))?;
+        let profiles = table.remove("profile");
+        if let Some(profile) = profile {
+            let overrides = profiles
+                .as_ref()
+                .and_then(|profiles| profiles.get(profile))
+                .and_then(toml::Value::as_table)
+                .with_context(|| format!("Unknown profile `{}` in {}", profile, path))?;
+            merge_tables(&mut table, overrides);
+        }
+
+        table
+            .try_into()
+            .with_context(|| format!("Failed to parse {}", path))
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.env == "production"
+    }
+
+    // Turn a site path into an absolute URL, external URLs are left untouched
+    pub fn absolute_url(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            return path.to_string();
+        }
+        format!("{}{}", self.base_url.trim_end_matches('/'), path)
+    }
+
+    pub fn normalize_link(&self, link: &str) -> String {
+        urls::normalize_link(self.trailing_slash, &self.base_url, link)
+    }
+
+    // The URL an internal link is served at: under the base path and with the
+    // trailing slash policy applied
+    pub fn site_url(&self, link: &str) -> String {
+        self.normalize_link(&urls::join_base_path(&self.base_path, link))
+    }
+
+    // The URL a local image is served at, on `image_cdn` when there is one.
+    // Links to anything else get `site_url`.
+    pub fn image_url(&self, link: &str) -> String {
+        let path = link.split(['?', '#']).next().unwrap_or_default();
+        let is_image = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            });
+        match &self.image_cdn {
+            Some(cdn) if is_image && link.starts_with('/') && !link.starts_with("//") => {
+                format!("{}{}", cdn.trim_end_matches('/'), link)
+            }
+            _ => self.site_url(link),
+        }
+    }
+
+    // Find the section a content file belongs to from its path relative to
+    // `src/content`: the one whose directory the file is in, the deepest one
+    // when a section's `dir` is inside another's, like `books/series`. Files
+    // right in `src/content` are pages outside of any section, and so are
+    // files in other directories unless there's a `default_section`.
+    pub fn section_for(&self, relative_path: &Path) -> Option<(&str, &SectionConfig)> {
+        let dir = relative_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())?;
+        let section_dir = |name: &String, section: &SectionConfig| {
+            PathBuf::from(section.dir.as_deref().unwrap_or(name))
+        };
+        self.sections
+            .iter()
+            .filter(|(name, section)| dir.starts_with(section_dir(name, section)))
+            .max_by_key(|(name, section)| section_dir(name, section).components().count())
+            .or_else(|| {
+                self.default_section
+                    .as_ref()
+                    .and_then(|name| self.sections.get_key_value(name))
+            })
+            .map(|(name, section)| (name.as_str(), section))
+    }
+
+    // Whether a file outside of every section can still be built as a page:
+    // always unless `require_section` is set, and then only right in
+    // `src/content` or as a locale's translation
+    pub fn allows_page(&self, relative_path: &Path) -> bool {
+        let mut components = relative_path.components();
+        let first = components.next();
+        !self.require_section
+            || components.next().is_none()
+            || first.is_some_and(|dir| {
+                self.locales
+                    .iter()
+                    .any(|locale| dir.as_os_str() == locale.as_str())
+            })
+    }
+}
+
+// Nested tables are merged key by key, anything else is replaced outright
+fn merge_tables(base: &mut toml::Table, overrides: &toml::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_tables(base, overrides)
+            }
+            _ => {
+                base.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
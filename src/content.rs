@@ -0,0 +1,1159 @@
+use crate::{
+    amp,
+    assets::image_size,
+    citations::{load_references, resolve_citations},
+    comments::comments_embed,
+    config::{AuthorProfile, ExcerptFormat, SectionConfig, SectionFeatures},
+    cta::cta_for,
+    encoding::read_text,
+    includes::expand_includes,
+    is_html,
+    markdown::{
+        expand_components, extract_directives, mark_lead_paragraph, render_markdown,
+        split_at_headings, split_excerpt, RenderedMarkdown, TocEntry,
+    },
+    page_url,
+    pages::{base_context, insert_latest_across, text_direction},
+    remote::expand_remote,
+    schema::apply_schema,
+    urls,
+    validate::{validate_html, VOID_ELEMENTS},
+    BuildReport, ExclusionReason, Site, SiteError,
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use serde_yaml::{Mapping, Value};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, PoisonError, RwLock},
+};
+
+#[derive(Debug, Serialize)]
+pub struct PostMetadata {
+    pub title: String,
+    pub slug: String,
+    // Site-absolute URL of the rendered page, keeps nested directories intact
+    pub url: String,
+    pub image: String,
+    // Pixel size of a local image, `None` for external or unreadable ones
+    pub image_width: Option<u32>,
+    pub image_height: Option<u32>,
+    pub description: String,
+    pub date: String,
+    // When the post was last revised, if it says so
+    pub updated: Option<String>,
+    // Whether that was within `updated_badge_days` of the build
+    pub recently_updated: bool,
+    pub tags: Vec<String>,
+    // Terms for the keywords meta tag and client-side search, none by default
+    pub keywords: Vec<String>,
+    // Body up to the excerpt separator, if the post has one, as HTML or plain
+    // text depending on `excerpt_format`
+    pub excerpt: Option<String>,
+    pub excerpt_html: Option<String>,
+    pub excerpt_text: Option<String>,
+    // Name of the section the post belongs to, `None` for standalone pages
+    pub category: Option<String>,
+    // Estimated reading time in minutes
+    pub reading_time: usize,
+    // Label of the `reading_buckets` range the reading time falls in
+    pub reading_bucket: Option<String>,
+    // Still generated, but kept out of search engines, the sitemap and feeds
+    pub noindex: bool,
+    // Hand-picked for `featured.html`, whatever section it's in
+    pub featured: bool,
+    // Old URLs that should redirect to this page
+    pub redirect_from: Vec<String>,
+    // Where the post was first published, when it's cross-posted
+    pub canonical_url: Option<String>,
+    // The raw frontmatter, for lookups of keys without a dedicated field
+    #[serde(skip)]
+    pub frontmatter: Mapping,
+    // The rendered body of all its pages, for feeds with the full content
+    #[serde(skip)]
+    pub content: String,
+}
+
+// Posts by slug as template values, for the `post()` function
+pub(crate) type PostIndex = Arc<RwLock<BTreeMap<String, tera::Value>>>;
+
+// A page of a post, ready to render
+#[derive(Clone)]
+pub(crate) struct Page {
+    source: PathBuf,
+    path: PathBuf,
+    template: String,
+    context: tera::Context,
+}
+
+// Render a content file into its metadata and the pages to write. Drafts are
+// skipped unless the config asks for them.
+pub(crate) fn process_markdown(
+    site: &Site,
+    src_path: &Path,
+    section: Option<(&str, &SectionConfig)>,
+    report: &mut BuildReport,
+) -> Result<Option<(PostMetadata, Vec<Page>)>> {
+    // The file name is the slug, dotfiles and names that aren't UTF-8 can't be one
+    let Some(slug) = site
+        .slug(src_path)
+        .filter(|stem| !stem.is_empty() && !stem.starts_with('.'))
+    else {
+        report.warnings.push(format!(
+            "{} has no file name to take a slug from, skipping it",
+            src_path.display()
+        ));
+        report.exclude(src_path, ExclusionReason::NoSlug);
+        return Ok(None);
+    };
+
+    let Source {
+        frontmatter,
+        markdown: markdown_content,
+        warnings,
+    } = read_source(site, src_path)?;
+
+    let draft = is_draft(&frontmatter);
+    let published = is_published(site, &frontmatter).map_err(|err| SiteError::Validation {
+        path: src_path.to_path_buf(),
+        message: format!("Invalid frontmatter in {}: {:#}", src_path.display(), err),
+    })?;
+    if !published {
+        let reason = if draft {
+            ExclusionReason::Draft
+        } else {
+            ExclusionReason::Unpublished
+        };
+        report.exclude(src_path, reason);
+        return Ok(None);
+    }
+    for warning in warnings {
+        report
+            .warnings
+            .push(format!("{} {}", src_path.display(), warning));
+    }
+    if is_missing_body(&frontmatter, &markdown_content) {
+        report
+            .warnings
+            .push(format!("{} has no content", src_path.display()));
+    }
+
+    // Extract metadata from frontmatter
+    let title = extract_metadata(&frontmatter, "title").unwrap_or_else(|| "Untitled".to_string());
+    let image = extract_metadata(&frontmatter, "image")
+        .unwrap_or_else(|| site.config.default_image.clone());
+    // Listings can reserve the image's space before it loads
+    let image_size = image_size(&site.source_dir, &image);
+    let image = site.config.image_url(&image);
+    let description = extract_metadata(&frontmatter, "description")
+        .unwrap_or_else(|| "No description".to_string());
+    let date = extract_date(site, &frontmatter, "date", src_path)?;
+    if let Some((name, _)) = section.filter(|(_, section)| section.require_date && date.is_none()) {
+        report.warnings.push(format!(
+            "{} has no `date`, which section `{}` requires",
+            src_path.display(),
+            name
+        ));
+    }
+    let date = date.unwrap_or_else(|| "No date".to_string());
+    let updated = extract_date(site, &frontmatter, "updated", src_path)?;
+    let tags = extract_list(&frontmatter, "tags");
+    let keywords = extract_list(&frontmatter, "keywords");
+    let noindex = extract_bool(&frontmatter, "noindex").unwrap_or(false);
+    let featured = extract_bool(&frontmatter, "featured").unwrap_or(false);
+    let redirect_from = extract_list(&frontmatter, "redirect_from");
+    // Cross-posted articles point search engines at the original
+    let canonical_url = extract_metadata(&frontmatter, "canonical_url");
+    let extra_css = extra_assets(site, &frontmatter, "extra_css", src_path, report);
+    let extra_js = extra_assets(site, &frontmatter, "extra_js", src_path, report);
+
+    let mut features = section.map_or_else(SectionFeatures::default, |(_, section)| {
+        section.features.clone()
+    });
+    if let Some(comments) = extract_bool(&frontmatter, "comments") {
+        features.comments = comments;
+    }
+
+    let (markdown_content, directives) =
+        extract_directives(&markdown_content, &site.config.markdown.directives);
+    let (markdown_content, excerpt) =
+        match split_excerpt(&markdown_content, &site.config.markdown.excerpt_separators) {
+            Some((excerpt, rest)) => (
+                format!("{}{}", excerpt, rest),
+                Some(excerpt.trim_end().to_string()),
+            ),
+            None => (markdown_content, None),
+        };
+
+    // Links into co-located images are relative to the post's directory
+    let page_dir = src_path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(site.content_dir()).ok())
+        .unwrap_or(Path::new(""));
+
+    // Parse markdown to HTML (using only the content part), HTML content is used as is.
+    // Long markdown can be split into several pages at its headings.
+    let (parts, excerpt, reading_time) = if is_html(src_path) {
+        let rendered = RenderedMarkdown {
+            html: markdown_content.clone(),
+            has_code: false,
+            toc: vec![],
+        };
+        let minutes = if features.reading_time {
+            reading_time(&strip_tags(&markdown_content), site.config.reading_time_wpm)
+        } else {
+            0
+        };
+        (vec![rendered], excerpt, minutes)
+    } else {
+        let markdown_content = if section.is_some_and(|(_, section)| section.citations) {
+            let references = load_references(site)?;
+            let (resolved, unknown) = resolve_citations(&markdown_content, &references)?;
+            for key in unknown {
+                report.warnings.push(format!(
+                    "{} cites unknown reference `{}`",
+                    src_path.display(),
+                    key
+                ));
+            }
+            resolved
+        } else {
+            markdown_content
+        };
+        let (markdown_content, unknown) =
+            expand_components(&markdown_content, &site.config.markdown.components);
+        for name in unknown {
+            report.warnings.push(format!(
+                "{} uses unknown component `{}`",
+                src_path.display(),
+                name
+            ));
+        }
+        let toc = extract_bool(&frontmatter, "toc").unwrap_or_else(|| {
+            section.is_none_or(|(_, section)| section.toc && section.features.toc)
+        });
+        // Chapters shown below a wrapper's own `<h1>` can shift their headings down
+        let heading_shift = extract_metadata(&frontmatter, "heading_shift")
+            .and_then(|shift| shift.parse().ok())
+            .unwrap_or_else(|| section.map_or(0, |(_, section)| section.heading_shift));
+        let number_headings = extract_bool(&frontmatter, "number_headings")
+            .unwrap_or_else(|| section.is_some_and(|(_, section)| section.number_headings));
+        let parts = match split_level(&frontmatter, section, src_path)? {
+            Some(level) => split_at_headings(&markdown_content, level),
+            None => vec![markdown_content.clone()],
+        };
+        let mut parts = parts
+            .iter()
+            .map(|part| render_markdown(site, part, toc, heading_shift, number_headings, page_dir))
+            .collect::<Result<Vec<_>>>()?;
+        if site.config.markdown.lead_paragraph {
+            if let Some(first) = parts.first_mut() {
+                first.html = mark_lead_paragraph(&first.html, &site.config.markdown.lead_class);
+            }
+        }
+        let excerpt = match excerpt {
+            Some(excerpt) => {
+                Some(render_markdown(site, &excerpt, false, heading_shift, false, page_dir)?.html)
+            }
+            None => None,
+        };
+        let minutes = if features.reading_time {
+            reading_time(&markdown_content, site.config.reading_time_wpm)
+        } else {
+            0
+        };
+        (parts, excerpt, minutes)
+    };
+
+    // Code heavy or mostly visual posts can set their own estimate
+    let reading_time = extract_metadata(&frontmatter, "reading_time")
+        .and_then(|minutes| minutes.parse().ok())
+        .unwrap_or(reading_time);
+    let reading_bucket = features
+        .reading_time
+        .then(|| reading_bucket(site, reading_time))
+        .flatten();
+    if site.config.stats {
+        let html: String = parts.iter().map(|part| part.html.as_str()).collect();
+        report.stats.add(
+            section.map(|(name, _)| name),
+            &strip_tags(&html),
+            reading_time,
+        );
+    }
+
+    let output_path = output_path(site, src_path)?;
+
+    // Create parent directories if necessary
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).context("Failed to create output directories")?;
+    }
+
+    // Pages outside of any section are rendered as generic pages
+    let template_name = section.map_or("page.html".to_string(), |(name, s)| {
+        site.section_template(name, &s.template)
+    });
+
+    // Variables shared by all pages of the post. Directives go in first so they
+    // can't shadow the page's own variables.
+    let mut post_context = tera::Context::new();
+    for (name, value) in &directives {
+        post_context.insert(name.as_str(), value);
+    }
+    // Every frontmatter key as written, for templates that need one without a
+    // dedicated variable
+    let meta: BTreeMap<&str, &Value> = frontmatter
+        .iter()
+        .filter_map(|(key, value)| Some((key.as_str()?, value)))
+        .collect();
+    post_context.insert("meta", &meta);
+    post_context.insert(
+        "toc_title",
+        &extract_metadata(&frontmatter, "toc_title")
+            .unwrap_or_else(|| site.config.markdown.toc_title.clone()),
+    );
+    post_context.insert("toc_min_level", &site.config.markdown.toc_min_level);
+    post_context.insert("toc_max_level", &site.config.markdown.toc_max_level);
+    post_context.insert("title", &title);
+    let body_class = extract_metadata(&frontmatter, "body_class").unwrap_or_else(|| {
+        section.map_or("page".to_string(), |(name, _)| format!("{} post", name))
+    });
+    post_context.insert("body_class", &body_class);
+    // A post in another language than the site's sets its own
+    if let Some(lang) = extract_metadata(&frontmatter, "lang") {
+        let dir = extract_metadata(&frontmatter, "dir")
+            .unwrap_or_else(|| text_direction(&lang).to_string());
+        post_context.insert("lang", &lang);
+        post_context.insert("dir", &dir);
+    } else if let Some(dir) = extract_metadata(&frontmatter, "dir") {
+        post_context.insert("dir", &dir);
+    }
+    post_context.insert("image", &image);
+    if let Some((width, height)) = image_size {
+        post_context.insert("image_width", &width);
+        post_context.insert("image_height", &height);
+    }
+    post_context.insert("description", &description);
+    post_context.insert("date", &date);
+    if let Some(updated) = &updated {
+        post_context.insert("updated", updated);
+    }
+    post_context.insert(
+        "show_updated",
+        &show_updated(site, &date, updated.as_deref()),
+    );
+    post_context.insert("tags", &tags);
+    if let Some(author) = extract_metadata(&frontmatter, "author") {
+        post_context.insert("author_profile", &author_profile(site, &author));
+    }
+    if !keywords.is_empty() {
+        post_context.insert("keywords", &keywords.join(", "));
+    }
+    // A short summary shown in a box above the post, apart from the description
+    if let Some(tldr) = extract_metadata(&frontmatter, "tldr") {
+        let tldr = render_markdown(site, &tldr, false, 0, false, page_dir)?;
+        post_context.insert("tldr_html", &tldr.html);
+    }
+    post_context.insert("features", &features);
+    // Drafts built for a preview would open threads nobody can find later
+    if features.comments && !draft {
+        if let Some(embed) = comments_embed(site) {
+            post_context.insert("comments_html", &embed);
+        }
+    }
+    post_context.insert("reading_time", &reading_time);
+    if let Some(bucket) = &reading_bucket {
+        post_context.insert("reading_bucket", bucket);
+    }
+    if features.reading_time {
+        post_context.insert(
+            "reading_time_label",
+            &site
+                .config
+                .reading_time_label
+                .replace("{}", &reading_time.to_string()),
+        );
+    }
+    if noindex {
+        post_context.insert("noindex", &true);
+    }
+    post_context.insert("og_image", &site.config.absolute_url(&image));
+    let og_type = extract_metadata(&frontmatter, "og_type")
+        .or_else(|| section.and_then(|(_, section)| section.og_type.clone()))
+        .unwrap_or_else(|| "article".to_string());
+    post_context.insert("og_type", &og_type);
+    if let Some(canonical_url) = &canonical_url {
+        post_context.insert("canonical_url", canonical_url);
+    }
+    if let Some(edit_url) = edit_url(site, src_path) {
+        post_context.insert("edit_url", &edit_url);
+    }
+    // Stylesheets and scripts only this page needs, linked from the layout
+    if !extra_css.is_empty() {
+        post_context.insert("extra_css", &extra_css);
+    }
+    if !extra_js.is_empty() {
+        post_context.insert("extra_js", &extra_js);
+    }
+
+    // Split pages after the first are written next to it as `<slug>-2.html`, ...
+    let page_paths: Vec<PathBuf> = (0..parts.len())
+        .map(|index| match index {
+            0 => output_path.clone(),
+            _ => output_path.with_file_name(format!("{}-{}.html", slug, index + 1)),
+        })
+        .collect();
+    let page_urls: Vec<String> = page_paths
+        .iter()
+        .map(|path| site.config.site_url(&page_url(site, path)))
+        .collect();
+
+    // AMP copies of section posts go next to them as `<slug>/amp.html`
+    let amp_path = (site.config.amp.enabled && section.is_some())
+        .then(|| output_path.with_file_name(&slug).join("amp.html"));
+    if let Some(amp_path) = &amp_path {
+        post_context.insert("amp_url", &site.config.site_url(&page_url(site, amp_path)));
+    }
+
+    // Structured data for section posts, once there is an absolute URL to name
+    let main_url = canonical_url.clone().or_else(|| {
+        (!site.config.base_url.is_empty()).then(|| site.config.absolute_url(&page_urls[0]))
+    });
+    if let (Some(main_url), Some(_)) = (&main_url, section) {
+        let mut json_ld = serde_json::json!({
+            "@context": "https://schema.org",
+            "@type": "BlogPosting",
+            "headline": title,
+            "description": description,
+            "image": site.config.absolute_url(&image),
+            "mainEntityOfPage": main_url,
+        });
+        if NaiveDate::parse_from_str(&date, DATE_FORMAT).is_ok() {
+            json_ld["datePublished"] = date.clone().into();
+            json_ld["dateModified"] = updated.clone().unwrap_or_else(|| date.clone()).into();
+        }
+        // `</script>` in a title mustn't end the script element
+        post_context.insert("json_ld", &json_ld.to_string().replace("</", "<\\/"));
+    }
+
+    // One table of contents across all pages, headings on other pages link there
+    let toc: Vec<TocEntry> = if parts.len() > 1 {
+        parts
+            .iter()
+            .zip(&page_urls)
+            .flat_map(|(part, url)| with_toc_url(part.toc.clone(), url))
+            .collect()
+    } else {
+        parts[0].toc.clone()
+    };
+    // A table of contents with a single entry or two isn't worth showing
+    let toc = if count_toc_entries(&toc) < site.config.markdown.toc_min_headings {
+        vec![]
+    } else {
+        toc
+    };
+
+    // With `since` set, older posts only contribute their metadata to listings
+    let changed = is_changed_since(site, src_path, &date, updated.as_deref());
+    let written_parts = if changed { parts.as_slice() } else { &[] };
+    if !changed {
+        report.exclude(src_path, ExclusionReason::Unchanged);
+    } else if noindex {
+        report.exclude(src_path, ExclusionReason::Noindex);
+    }
+
+    // The same table of contents for client-side widgets, at `<slug>/toc.json`
+    if site.config.toc_json && changed && !toc.is_empty() {
+        let entries = if parts.len() > 1 {
+            toc.clone()
+        } else {
+            with_toc_url(toc.clone(), &page_urls[0])
+        };
+        let dir = output_path.with_file_name(&slug);
+        fs::create_dir_all(&dir).context("Failed to create TOC directory")?;
+        let json = serde_json::to_string_pretty(&entries).context("Failed to serialize TOC")?;
+        let toc_path = dir.join("toc.json");
+        fs::write(&toc_path, json).context("Failed to write toc.json")?;
+        report.record_output(src_path, &toc_path);
+    }
+
+    // The site-wide call to action goes after the last part, unless the post
+    // opts out with `no_cta: true`
+    let cta = section
+        .filter(|_| !extract_bool(&frontmatter, "no_cta").unwrap_or(false))
+        .and_then(|(name, _)| cta_for(site, name));
+
+    let mut pages = vec![];
+    for (index, part) in written_parts.iter().enumerate() {
+        let page_path = page_url(site, &page_paths[index]);
+        let mut context = base_context(site, &page_path, section.map(|(name, _)| name));
+        context.extend(post_context.clone());
+        match cta.as_ref().filter(|_| index + 1 == parts.len()) {
+            Some(cta) => context.insert("content", &format!("{}{}", part.html, cta)),
+            None => context.insert("content", &part.html),
+        }
+        context.insert("toc", &without_toc_url(toc.clone(), &page_urls[index]));
+        if parts.len() > 1 {
+            context.insert(
+                "part",
+                &Part {
+                    number: index + 1,
+                    count: parts.len(),
+                    prev_url: index.checked_sub(1).map(|prev| page_urls[prev].as_str()),
+                    next_url: page_urls.get(index + 1).map(String::as_str),
+                },
+            );
+        }
+        // Pages only pull in the copy button script when they actually have code
+        context.insert(
+            "code_copy",
+            &(site.config.markdown.code_copy && part.has_code),
+        );
+        pages.push(Page {
+            source: src_path.to_path_buf(),
+            path: page_paths[index].clone(),
+            template: template_name.clone(),
+            context,
+        });
+    }
+
+    if let Some(amp_path) = amp_path.filter(|_| changed) {
+        fs::create_dir_all(amp_path.parent().unwrap_or(&site.dist_dir))
+            .context("Failed to create AMP directory")?;
+        let content: String = parts.iter().map(|part| part.html.as_str()).collect();
+        let mut context = base_context(
+            site,
+            &page_url(site, &amp_path),
+            section.map(|(name, _)| name),
+        );
+        context.extend(post_context.clone());
+        context.remove("amp_url");
+        context.insert("content", &amp::sanitize(site, &content));
+        context.insert("amp_styles", &site.amp_styles);
+        // AMP pages name the regular page as their canonical
+        let canonical_url = canonical_url
+            .clone()
+            .unwrap_or_else(|| site.config.absolute_url(&page_urls[0]));
+        context.insert("canonical_url", &canonical_url);
+        pages.push(Page {
+            source: src_path.to_path_buf(),
+            path: amp_path,
+            template: site.config.amp.template.clone(),
+            context,
+        });
+    }
+
+    // Raw HTML cut at the separator can leave elements open
+    let excerpt_html = excerpt.map(|excerpt| close_tags(&excerpt));
+    let excerpt_text = excerpt_html.as_deref().map(excerpt_text);
+    let excerpt = match site.config.markdown.excerpt_format {
+        ExcerptFormat::Html => excerpt_html.clone(),
+        ExcerptFormat::Text => excerpt_text.clone(),
+    };
+
+    let url = page_urls[0].clone();
+    let post = PostMetadata {
+        title,
+        slug,
+        url,
+        image,
+        image_width: image_size.map(|(width, _)| width),
+        image_height: image_size.map(|(_, height)| height),
+        description,
+        date,
+        recently_updated: recently_updated(site, updated.as_deref()),
+        updated,
+        tags,
+        keywords,
+        excerpt,
+        excerpt_html,
+        excerpt_text,
+        category: section.map(|(name, _)| name.to_string()),
+        reading_time,
+        reading_bucket,
+        noindex,
+        featured,
+        redirect_from,
+        canonical_url,
+        frontmatter,
+        content: parts.iter().map(|part| part.html.as_str()).collect(),
+    };
+
+    // The same post as JSON next to the page, for clients that render it themselves
+    if site.config.emit_json && changed {
+        let sidecar = JsonSidecar {
+            post: &post,
+            content: &post.content,
+        };
+        let json = serde_json::to_string_pretty(&sidecar).context("Failed to serialize post")?;
+        let json_path = output_path.with_extension("json");
+        fs::write(&json_path, json).context("Failed to write JSON sidecar")?;
+        report.record_output(src_path, &json_path);
+    }
+    Ok(Some((post, pages)))
+}
+
+impl Page {
+    pub(crate) fn source(&self) -> &Path {
+        &self.source
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub(crate) fn context_mut(&mut self) -> &mut tera::Context {
+        &mut self.context
+    }
+
+    // The same page written somewhere else
+    pub(crate) fn with_path(&self, path: PathBuf) -> Page {
+        Page {
+            path,
+            ..self.clone()
+        }
+    }
+}
+
+// Render and write a page of a post, once every post is known
+pub(crate) fn write_page(site: &Site, page: &Page, report: &mut BuildReport) -> Result<()> {
+    let mut context = page.context.clone();
+    insert_latest_across(site, &mut context);
+    let rendered = site
+        .render(&page.template, &context)
+        .map_err(|source| SiteError::Template {
+            path: page.source.clone(),
+            source,
+        })?;
+    if site.config.validate_html {
+        for problem in validate_html(&rendered) {
+            report
+                .warnings
+                .push(format!("{} renders {}", page.source.display(), problem));
+        }
+    }
+
+    // Write to the output HTML file
+    fs::write(&page.path, rendered).map_err(|source| SiteError::Write {
+        path: page.path.clone(),
+        source,
+    })?;
+    report.record_output(&page.source, &page.path);
+    Ok(())
+}
+
+// `{{ post(slug="hello-world").url }}` in templates. Slugs shared by posts of
+// different sections find the first one by path.
+pub(crate) fn post_function(
+    posts: PostIndex,
+) -> impl Fn(&HashMap<String, tera::Value>) -> tera::Result<tera::Value> {
+    move |args| {
+        let Some(tera::Value::String(slug)) = args.get("slug") else {
+            return Err(tera::Error::msg("`post` needs a `slug` string argument"));
+        };
+        let posts = posts.read().unwrap_or_else(PoisonError::into_inner);
+        posts
+            .get(slug)
+            .cloned()
+            .ok_or_else(|| tera::Error::msg(format!("There is no post with slug `{}`", slug)))
+    }
+}
+
+// The `[authors.<key>]` profile of a post's `author`, or one with just the
+// name for authors without a profile
+fn author_profile(site: &Site, author: &str) -> AuthorProfile {
+    let Some(profile) = site.config.authors.get(author) else {
+        return AuthorProfile {
+            name: author.to_string(),
+            ..AuthorProfile::default()
+        };
+    };
+    let mut profile = profile.clone();
+    if profile.name.is_empty() {
+        profile.name = author.to_string();
+    }
+    profile.avatar = profile.avatar.map(|avatar| site.config.image_url(&avatar));
+    for link in &mut profile.links {
+        link.url = site.config.site_url(&link.url);
+    }
+    profile
+}
+
+// `edit_url_template` with the source file's path, which is relative to the
+// directory holding the source one, the repository root with the usual layout
+fn edit_url(site: &Site, src_path: &Path) -> Option<String> {
+    let template = site.config.edit_url_template.as_ref()?;
+    let root = site.source_dir.parent().unwrap_or(Path::new(""));
+    let path = src_path.strip_prefix(root).unwrap_or(src_path);
+    let path = path.to_string_lossy().replace('\\', "/");
+    Some(template.replace("{path}", &path))
+}
+
+// Whether the post was published, updated or edited on or after `since`
+fn is_changed_since(site: &Site, src_path: &Path, date: &str, updated: Option<&str>) -> bool {
+    let Some(since) = site.config.since else {
+        return true;
+    };
+    let on_or_after =
+        |date: &str| NaiveDate::parse_from_str(date, DATE_FORMAT).is_ok_and(|date| date >= since);
+    let modified = fs::metadata(src_path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .map(|modified| DateTime::<Local>::from(modified).date_naive());
+    on_or_after(date) || updated.is_some_and(on_or_after) || modified.is_some_and(|m| m >= since)
+}
+
+// Where a page of a split post sits among the others
+#[derive(Serialize)]
+struct Part<'a> {
+    number: usize,
+    count: usize,
+    prev_url: Option<&'a str>,
+    next_url: Option<&'a str>,
+}
+
+fn count_toc_entries(entries: &[TocEntry]) -> usize {
+    entries
+        .iter()
+        .map(|entry| 1 + count_toc_entries(&entry.children))
+        .sum()
+}
+
+fn with_toc_url(entries: Vec<TocEntry>, url: &str) -> Vec<TocEntry> {
+    entries
+        .into_iter()
+        .map(|entry| TocEntry {
+            url: url.to_string(),
+            children: with_toc_url(entry.children, url),
+            ..entry
+        })
+        .collect()
+}
+
+// Headings on the page itself are linked by their fragment alone
+fn without_toc_url(entries: Vec<TocEntry>, url: &str) -> Vec<TocEntry> {
+    entries
+        .into_iter()
+        .map(|entry| TocEntry {
+            url: if entry.url == url {
+                String::new()
+            } else {
+                entry.url
+            },
+            children: without_toc_url(entry.children, url),
+            ..entry
+        })
+        .collect()
+}
+
+// The heading level to split a post at, from its `split_on` frontmatter or
+// else its section's. `none` turns splitting off for a single post.
+fn split_level(
+    frontmatter: &Mapping,
+    section: Option<(&str, &SectionConfig)>,
+    src_path: &Path,
+) -> Result<Option<u8>> {
+    let split_on = extract_metadata(frontmatter, "split_on")
+        .or_else(|| section.and_then(|(_, section)| section.split_on.clone()));
+    let Some(split_on) = split_on else {
+        return Ok(None);
+    };
+    match parse_split_on(&split_on) {
+        Some(level) => Ok(level),
+        None => anyhow::bail!(
+            "Unknown split_on `{}` in {}, expected h1 to h6 or none",
+            split_on,
+            src_path.display()
+        ),
+    }
+}
+
+// The heading level of `h1` to `h6`, `Some(None)` for `none` and `None` for
+// anything else
+pub(crate) fn parse_split_on(split_on: &str) -> Option<Option<u8>> {
+    if split_on == "none" {
+        return Some(None);
+    }
+    match split_on
+        .strip_prefix('h')
+        .and_then(|level| level.parse().ok())
+    {
+        Some(level @ 1..=6) => Some(Some(level)),
+        _ => None,
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSidecar<'a> {
+    #[serde(flatten)]
+    post: &'a PostMetadata,
+    content: &'a str,
+}
+
+// A content file with its frontmatter parsed and includes expanded
+pub(crate) struct Source {
+    pub frontmatter: Mapping,
+    pub markdown: String,
+    // Deprecated frontmatter keys the file still uses, without the file name
+    pub warnings: Vec<String>,
+}
+
+pub(crate) fn read_source(site: &Site, src_path: &Path) -> Result<Source> {
+    // Read markdown file
+    let fallback = site.config.markdown.fallback_encoding.as_deref();
+    let (content, encoding) = read_text(src_path, fallback)?;
+
+    // Extract metadata and content, skipping frontmatter
+    let (frontmatter, markdown) = split_frontmatter(&content);
+    let frontmatter = parse_frontmatter(&frontmatter).map_err(|source| SiteError::Frontmatter {
+        path: src_path.to_path_buf(),
+        source,
+    })?;
+    let markdown = expand_includes(site, src_path, &markdown)?;
+    let (markdown, mut warnings) = expand_remote(site, &markdown)
+        .with_context(|| format!("Failed to inline remote content in {}", src_path.display()))?;
+
+    let mut frontmatter = frontmatter;
+    if let Some(encoding) = encoding {
+        warnings.push(format!("isn't UTF-8, read it as {}", encoding.name()));
+    }
+    for (alias, key) in &site.config.frontmatter_aliases {
+        let Some(value) = frontmatter.remove(alias.as_str()) else {
+            continue;
+        };
+        warnings.push(format!(
+            "uses deprecated frontmatter key `{}`, use `{}` instead",
+            alias, key
+        ));
+        // The current key wins when a file has both
+        if !frontmatter.contains_key(key.as_str()) {
+            frontmatter.insert(Value::from(key.as_str()), value);
+        }
+    }
+    apply_schema(site, src_path, &mut frontmatter)?;
+
+    Ok(Source {
+        frontmatter,
+        markdown,
+        warnings,
+    })
+}
+
+// Where a content file ends up under dist
+pub(crate) fn output_path(site: &Site, src_path: &Path) -> Result<PathBuf> {
+    let relative_path = src_path.strip_prefix(site.content_dir())?;
+    let output_path = site.dist_dir.join(relative_path);
+    match site.slug(src_path) {
+        Some(slug) => Ok(output_path.with_file_name(format!("{}.html", slug))),
+        None => Ok(output_path.with_extension("html")),
+    }
+}
+
+pub(crate) fn is_draft(frontmatter: &Mapping) -> bool {
+    extract_bool(frontmatter, "draft").unwrap_or(false)
+        || extract_metadata(frontmatter, "status").as_deref() == Some("draft")
+}
+
+// Whether the file is built: `draft: true` only with `drafts`, and a `status`
+// before the last of `statuses` only with `drafts` or a `status` to build
+// from that comes before it
+pub(crate) fn is_published(site: &Site, frontmatter: &Mapping) -> Result<bool> {
+    let config = &site.config;
+    if extract_bool(frontmatter, "draft").unwrap_or(false) && !config.drafts {
+        return Ok(false);
+    }
+    let Some(status) = extract_metadata(frontmatter, "status") else {
+        return Ok(true);
+    };
+    let position = |status: &str| config.statuses.iter().position(|known| known == status);
+    let Some(index) = position(&status) else {
+        anyhow::bail!(
+            "unknown status `{}`, expected one of: {}",
+            status,
+            config.statuses.join(", ")
+        );
+    };
+    if config.drafts || index + 1 == config.statuses.len() {
+        return Ok(true);
+    }
+    let Some(from) = &config.status else {
+        return Ok(false);
+    };
+    let from = position(from).with_context(|| {
+        format!(
+            "Status `{}` to build isn't one of: {}",
+            from,
+            config.statuses.join(", ")
+        )
+    })?;
+    Ok(index >= from)
+}
+
+// Drafts are work in progress, an empty one is expected
+pub(crate) fn is_missing_body(frontmatter: &Mapping, markdown: &str) -> bool {
+    !is_draft(frontmatter) && markdown.trim().is_empty()
+}
+
+// Dates are written out as ISO 8601 whatever format they were given in
+pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d";
+
+// Try the configured formats in order, the first one that matches wins
+pub(crate) fn parse_date(formats: &[String], value: &str) -> Option<NaiveDate> {
+    formats
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value.trim(), format).ok())
+}
+
+fn extract_date(
+    site: &Site,
+    frontmatter: &Mapping,
+    key: &str,
+    src_path: &Path,
+) -> Result<Option<String>> {
+    let Some(value) = extract_metadata(frontmatter, key) else {
+        return Ok(None);
+    };
+    let formats = &site.config.date_formats;
+    match parse_date(formats, &value) {
+        Some(date) => Ok(Some(date.format(DATE_FORMAT).to_string())),
+        None => Err(SiteError::Validation {
+            path: src_path.to_path_buf(),
+            message: format!(
+                "Unrecognized {} `{}` in {}, expected one of: {}",
+                key,
+                value,
+                src_path.display(),
+                formats.join(", ")
+            ),
+        }
+        .into()),
+    }
+}
+
+fn recently_updated(site: &Site, updated: Option<&str>) -> bool {
+    let (Some(days), Some(updated)) = (
+        site.config.updated_badge_days,
+        updated.and_then(|updated| NaiveDate::parse_from_str(updated, DATE_FORMAT).ok()),
+    ) else {
+        return false;
+    };
+    (site.build_date() - updated).num_days() <= days
+}
+
+// Whether the revision is worth mentioning, posts without a date always show it
+fn show_updated(site: &Site, date: &str, updated: Option<&str>) -> bool {
+    let Some(updated) =
+        updated.and_then(|updated| NaiveDate::parse_from_str(updated, DATE_FORMAT).ok())
+    else {
+        return false;
+    };
+    match NaiveDate::parse_from_str(date, DATE_FORMAT) {
+        Ok(date) => (updated - date).num_days() > site.config.show_updated_after_days,
+        Err(_) => true,
+    }
+}
+
+fn parse_frontmatter(frontmatter: &str) -> Result<Mapping, serde_yaml::Error> {
+    if frontmatter.trim().is_empty() {
+        return Ok(Mapping::new());
+    }
+    serde_yaml::from_str(frontmatter)
+}
+
+pub(crate) fn extract_metadata(frontmatter: &Mapping, key: &str) -> Option<String> {
+    frontmatter.get(key).and_then(scalar_to_string)
+}
+
+fn extract_bool(frontmatter: &Mapping, key: &str) -> Option<bool> {
+    frontmatter.get(key).and_then(Value::as_bool)
+}
+
+// Lists can be written as YAML sequences or as a comma separated string
+pub(crate) fn extract_list(frontmatter: &Mapping, key: &str) -> Vec<String> {
+    match frontmatter.get(key) {
+        Some(Value::Sequence(items)) => items.iter().filter_map(scalar_to_string).collect(),
+        Some(value) => scalar_to_string(value)
+            .map(|value| {
+                value
+                    .split(',')
+                    .map(|item| item.trim().to_string())
+                    .filter(|item| !item.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default(),
+        None => vec![],
+    }
+}
+
+// URLs of the assets listed under `key`, given as `/assets/page.css` or as a
+// path relative to the assets directory. Missing files are warned about but
+// still linked, external URLs are taken as they are.
+fn extra_assets(
+    site: &Site,
+    frontmatter: &Mapping,
+    key: &str,
+    src_path: &Path,
+    report: &mut BuildReport,
+) -> Vec<String> {
+    extract_list(frontmatter, key)
+        .into_iter()
+        .map(|asset| {
+            if urls::is_external(&asset) {
+                return asset;
+            }
+            let relative_path = asset.trim_start_matches('/');
+            let relative_path = relative_path
+                .strip_prefix("assets/")
+                .unwrap_or(relative_path);
+            if !site.source_dir.join("assets").join(relative_path).is_file() {
+                report.warnings.push(format!(
+                    "{} lists `{}` in {}, but there is no such asset",
+                    src_path.display(),
+                    asset,
+                    key
+                ));
+            }
+            site.config.site_url(&format!("/assets/{}", relative_path))
+        })
+        .collect()
+}
+
+fn scalar_to_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(value) => Some(value.clone()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn reading_time(text: &str, words_per_minute: usize) -> usize {
+    let words = text.split_whitespace().count();
+    words.div_ceil(words_per_minute.max(1)).max(1)
+}
+
+fn reading_bucket(site: &Site, minutes: usize) -> Option<String> {
+    site.config
+        .reading_buckets
+        .iter()
+        .find(|bucket| bucket.up_to.is_none_or(|up_to| minutes <= up_to))
+        .map(|bucket| bucket.label.clone())
+}
+
+// The text of an HTML fragment, good enough for counting words
+pub(crate) fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text
+}
+
+// Tags that sit inside a line of text rather than starting a new one
+const INLINE_ELEMENTS: &[&str] = &[
+    "a", "abbr", "b", "cite", "code", "del", "em", "i", "ins", "kbd", "mark", "q", "s", "small",
+    "span", "strong", "sub", "sup", "time", "u",
+];
+
+static OPEN_OR_CLOSE_TAG: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<(/?)([A-Za-z][A-Za-z0-9-]*)[^>]*?(/?)>").unwrap());
+static TAG_OR_COMMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"</?([A-Za-z][A-Za-z0-9]*)[^>]*>|<!--.*?-->").unwrap());
+
+// Close the elements an HTML fragment leaves open, dropping a tag cut off at
+// the end
+fn close_tags(html: &str) -> String {
+    let html = match html.rfind('<') {
+        Some(index) if !html[index..].contains('>') => &html[..index],
+        _ => html,
+    };
+    let mut open: Vec<String> = vec![];
+    for captures in OPEN_OR_CLOSE_TAG.captures_iter(html) {
+        let name = captures[2].to_ascii_lowercase();
+        if VOID_ELEMENTS.contains(&name.as_str()) || !captures[3].is_empty() {
+            continue;
+        }
+        if captures[1].is_empty() {
+            open.push(name);
+        } else if let Some(index) = open.iter().rposition(|open| *open == name) {
+            open.truncate(index);
+        }
+    }
+
+    let mut closed = html.to_string();
+    for name in open.iter().rev() {
+        closed.push_str(&format!("</{}>", name));
+    }
+    closed
+}
+
+// Plain text of a rendered excerpt on one line, with the entities the markdown
+// renderer writes turned back into characters. Only block level tags separate
+// words, so `<em>world</em>.` stays `world.`
+fn excerpt_text(html: &str) -> String {
+    let text = TAG_OR_COMMENT.replace_all(html, |captures: &Captures| {
+        match captures
+            .get(1)
+            .map(|name| name.as_str().to_ascii_lowercase())
+        {
+            Some(name) if !INLINE_ELEMENTS.contains(&name.as_str()) => " ",
+            _ => "",
+        }
+    });
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+// Frontmatter is the block between a `---` first line and the next `---`
+// line. Files where a code fence opens before that, like a post starting with
+// a thematic break and showing YAML in a fence, or where it's never closed,
+// have none and are all content.
+fn split_frontmatter(content: &str) -> (String, String) {
+    let mut lines = content.lines();
+    if lines.next() != Some("---") {
+        return (String::new(), content.to_string());
+    }
+
+    let mut frontmatter = String::new();
+    for line in lines.by_ref() {
+        if line == "---" {
+            let mut markdown_content = String::new();
+            for line in lines {
+                markdown_content.push_str(line);
+                markdown_content.push('\n');
+            }
+            return (frontmatter, markdown_content);
+        }
+        if is_fence_opener(line) {
+            break;
+        }
+        frontmatter.push_str(line);
+        frontmatter.push('\n');
+    }
+    (String::new(), content.to_string())
+}
+
+// Whether a line opens a fenced code block. YAML can't have one at the start
+// of a line, fences indented inside a block scalar like `tldr` are fine.
+pub(crate) fn is_fence_opener(line: &str) -> bool {
+    line.starts_with("```") || line.starts_with("~~~")
+}
@@ -0,0 +1,35 @@
+use crate::{markdown::render_markdown, Site};
+use anyhow::{Context, Result};
+use std::{fs, sync::PoisonError};
+
+// The `[cta] snippet` as HTML, rendered once per build: markdown for `.md`
+// files, anything else as it is. `None` without a snippet.
+pub(crate) fn render_cta(site: &Site) -> Result<Option<String>> {
+    let Some(snippet) = &site.config.cta.snippet else {
+        return Ok(None);
+    };
+    let path = site.source_dir.join(snippet);
+    let text = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read call to action {}", path.display()))?;
+    if path.extension().is_some_and(|ext| ext == "md") {
+        let page_dir = path.parent().unwrap_or(&site.source_dir);
+        Ok(Some(
+            render_markdown(site, &text, false, 0, false, page_dir)?.html,
+        ))
+    } else {
+        Ok(Some(text))
+    }
+}
+
+// The snippet for the end of a post in `section`, unless it isn't one of
+// `[cta] sections`. Every section gets it when none are listed.
+pub(crate) fn cta_for(site: &Site, section: &str) -> Option<String> {
+    let sections = &site.config.cta.sections;
+    if !sections.is_empty() && !sections.iter().any(|name| name == section) {
+        return None;
+    }
+    site.cta_html
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
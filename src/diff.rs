@@ -0,0 +1,58 @@
+use crate::{hashes::fnv1a, is_html};
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use walkdir::WalkDir;
+
+// Pages under dist by path, with when they were written and a hash of them
+pub(crate) type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, String)>;
+
+// How the pages of a build differ from the ones the previous build left
+#[derive(Debug, Default, Serialize)]
+pub struct BuildDiff {
+    pub added: Vec<PathBuf>,
+    // Left over from the previous build, this one didn't write them
+    pub removed: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>,
+}
+
+// Pages with a `content-hash` meta tag are compared by it, so the build time
+// they show doesn't make every page look changed
+pub(crate) fn snapshot(dist_dir: &Path) -> Result<Snapshot> {
+    let content_hash = Regex::new(r#"<meta name="content-hash" content="([0-9a-f]+)" />"#)?;
+    let mut pages = Snapshot::new();
+    for entry in WalkDir::new(dist_dir).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_html(path) {
+            continue;
+        }
+        let html = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let hash = match content_hash.captures(&String::from_utf8_lossy(&html)) {
+            Some(found) => found[1].to_string(),
+            None => format!("{:016x}", fnv1a(&html)),
+        };
+        let modified = entry.metadata().ok().and_then(|meta| meta.modified().ok());
+        pages.insert(path.strip_prefix(dist_dir)?.to_path_buf(), (modified, hash));
+    }
+    Ok(pages)
+}
+
+pub(crate) fn diff(before: &Snapshot, after: &Snapshot) -> BuildDiff {
+    let mut diff = BuildDiff::default();
+    for (path, (modified, hash)) in after {
+        match before.get(path) {
+            None => diff.added.push(path.clone()),
+            // Not written again, so not part of this build
+            Some((previous, _)) if previous == modified => diff.removed.push(path.clone()),
+            Some((_, previous)) if previous != hash => diff.changed.push(path.clone()),
+            Some(_) => {}
+        }
+    }
+    diff
+}
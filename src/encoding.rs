@@ -0,0 +1,46 @@
+use crate::SiteError;
+use anyhow::{Context, Result};
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use encoding_rs::Encoding;
+use std::{fs, path::Path};
+
+// The encoding named by `fallback_encoding`, e.g. `windows-1254`
+pub(crate) fn fallback_encoding(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .with_context(|| format!("Unknown fallback_encoding `{}`", label))
+}
+
+// Read a text file as UTF-8, or failing that in the fallback encoding, guessed
+// from the bytes when none is configured. Also returns the encoding used when
+// it wasn't UTF-8.
+pub(crate) fn read_text(
+    path: &Path,
+    fallback: Option<&str>,
+) -> Result<(String, Option<&'static Encoding>)> {
+    let bytes = fs::read(path).map_err(|source| SiteError::Read {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let bytes = match String::from_utf8(bytes) {
+        Ok(text) => return Ok((text, None)),
+        Err(error) => error.into_bytes(),
+    };
+
+    let encoding = match fallback {
+        Some(label) => fallback_encoding(label)?,
+        None => {
+            let mut detector = EncodingDetector::new(Iso2022JpDetection::Deny);
+            detector.feed(&bytes, true);
+            detector.guess(None, Utf8Detection::Deny)
+        }
+    };
+    let (text, _, malformed) = encoding.decode(&bytes);
+    if malformed {
+        anyhow::bail!(
+            "{} is neither UTF-8 nor valid {}",
+            path.display(),
+            encoding.name()
+        );
+    }
+    Ok((text.into_owned(), Some(encoding)))
+}
@@ -0,0 +1,139 @@
+use std::{fmt, io, path::PathBuf};
+
+// Errors library users may want to tell apart. Functions still return
+// `anyhow::Result`, find these with `error.downcast_ref::<SiteError>()`.
+#[derive(Debug)]
+pub enum SiteError {
+    // The templates under this directory don't compile
+    TemplateLoad {
+        dir: PathBuf,
+        source: tera::Error,
+    },
+    // There's no template directory, or nothing in it
+    NoTemplates {
+        dir: PathBuf,
+    },
+    // Templates every build renders that the template directory lacks
+    MissingTemplates {
+        dir: PathBuf,
+        names: Vec<String>,
+    },
+    // A content file's page failed to render
+    Template {
+        path: PathBuf,
+        source: tera::Error,
+    },
+    // Frontmatter that isn't valid YAML
+    Frontmatter {
+        path: PathBuf,
+        source: serde_yaml::Error,
+    },
+    Read {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Write {
+        path: PathBuf,
+        source: io::Error,
+    },
+    // Content the build refuses, e.g. an unparseable date or unknown `status`
+    Validation {
+        path: PathBuf,
+        message: String,
+    },
+    // Another build holds the lock of the output directory, or left it behind
+    Locked {
+        path: PathBuf,
+        pid: Option<u32>,
+        running: bool,
+    },
+    // A program the build runs, like `curl` for remote content, isn't installed
+    MissingProgram {
+        program: String,
+        needed_for: String,
+        source: io::Error,
+    },
+}
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SiteError::TemplateLoad { dir, .. } => {
+                write!(f, "Failed to load templates from {}", dir.display())
+            }
+            SiteError::NoTemplates { dir } => write!(
+                f,
+                "No templates found in {}, it needs at least `homepage.html`, `page.html` and \
+                 each section's templates",
+                dir.display()
+            ),
+            SiteError::MissingTemplates { dir, names } => {
+                let names: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+                write!(
+                    f,
+                    "Missing templates in {}: {}",
+                    dir.display(),
+                    names.join(", ")
+                )
+            }
+            SiteError::Template { path, .. } => {
+                write!(f, "Failed to render template for {}", path.display())
+            }
+            SiteError::Frontmatter { path, .. } => {
+                write!(f, "Invalid frontmatter in {}", path.display())
+            }
+            SiteError::Read { path, .. } => write!(f, "Failed to read {}", path.display()),
+            SiteError::Write { path, .. } => write!(f, "Failed to write {}", path.display()),
+            SiteError::Validation { message, .. } => f.write_str(message),
+            SiteError::Locked { path, pid, running } => {
+                let holder = match pid {
+                    Some(pid) => format!("process {}", pid),
+                    None => "another process".to_string(),
+                };
+                if *running {
+                    write!(
+                        f,
+                        "Another build ({}) is writing the output, it holds {}",
+                        holder,
+                        path.display()
+                    )
+                } else {
+                    write!(
+                        f,
+                        "{} was left by {}, which isn't running anymore, build with \
+                         `--force-unlock` to remove it",
+                        path.display(),
+                        holder
+                    )
+                }
+            }
+            SiteError::MissingProgram {
+                program,
+                needed_for,
+                ..
+            } => write!(
+                f,
+                "`{}` isn't installed or isn't on PATH, {} needs it",
+                program, needed_for
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SiteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SiteError::TemplateLoad { source, .. } | SiteError::Template { source, .. } => {
+                Some(source)
+            }
+            SiteError::Frontmatter { source, .. } => Some(source),
+            SiteError::Read { source, .. }
+            | SiteError::Write { source, .. }
+            | SiteError::MissingProgram { source, .. } => Some(source),
+            SiteError::NoTemplates { .. }
+            | SiteError::MissingTemplates { .. }
+            | SiteError::Validation { .. }
+            | SiteError::Locked { .. } => None,
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{escape_xml, Site};
+use anyhow::{Context, Result};
+use image::{
+    codecs::{
+        ico::{IcoEncoder, IcoFrame},
+        png::PngEncoder,
+    },
+    imageops::FilterType,
+    DynamicImage, ExtendedColorType,
+};
+use std::{fs, io::Cursor, path::Path};
+
+// Sizes packed into `favicon.ico` for browsers that still ask for it
+const ICO_SIZES: &[u32] = &[16, 32, 48];
+
+fn png_name(size: u32) -> String {
+    format!("favicon-{}x{}.png", size, size)
+}
+
+// Write the icons from `favicon.source` to the root of dist. Icons newer than
+// the source are kept from the previous build.
+pub(crate) fn generate_favicons(site: &Site) -> Result<()> {
+    let config = &site.config.favicon;
+    if !config.enabled {
+        return Ok(());
+    }
+    let src = site.source_dir.join(&config.source);
+    let mut outputs: Vec<(String, u32)> = config
+        .sizes
+        .iter()
+        .map(|size| (png_name(*size), *size))
+        .collect();
+    outputs.push(("apple-touch-icon.png".to_string(), config.apple_touch_size));
+    let ico = site.dist_dir.join("favicon.ico");
+    let unchanged = |path: &Path| {
+        let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+        matches!((modified(&src), modified(path)), (Some(src), Some(dest)) if dest >= src)
+    };
+    if unchanged(&ico)
+        && outputs
+            .iter()
+            .all(|(name, _)| unchanged(&site.dist_dir.join(name)))
+    {
+        return Ok(());
+    }
+
+    let image = image::open(&src).with_context(|| format!("Failed to decode {}", src.display()))?;
+    for (name, size) in &outputs {
+        fs::write(site.dist_dir.join(name), encode_png(&image, *size)?)
+            .with_context(|| format!("Failed to write {}", name))?;
+    }
+
+    let frames = ICO_SIZES
+        .iter()
+        .map(|size| {
+            IcoFrame::with_encoded(
+                encode_png(&image, *size)?,
+                *size,
+                *size,
+                ExtendedColorType::Rgba8,
+            )
+            .context("Failed to encode favicon.ico")
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut buffer = vec![];
+    IcoEncoder::new(&mut buffer)
+        .encode_images(&frames)
+        .context("Failed to encode favicon.ico")?;
+    fs::write(ico, buffer).context("Failed to write favicon.ico")?;
+    Ok(())
+}
+
+fn encode_png(image: &DynamicImage, size: u32) -> Result<Vec<u8>> {
+    let resized = image
+        .resize_exact(size, size, FilterType::Lanczos3)
+        .to_rgba8();
+    let mut buffer = Cursor::new(vec![]);
+    DynamicImage::from(resized)
+        .write_with_encoder(PngEncoder::new(&mut buffer))
+        .context("Failed to encode favicon")?;
+    Ok(buffer.into_inner())
+}
+
+// The `<link>` tags for the generated icons, `favicon_links` in templates
+pub(crate) fn favicon_links(site: &Site) -> Option<String> {
+    let config = &site.config;
+    if !config.favicon.enabled {
+        return None;
+    }
+    let url = |name: &str| escape_xml(&config.site_url(&format!("/{}", name)));
+    let mut links = vec![format!(
+        "<link rel=\"icon\" href=\"{}\" sizes=\"{}\" />",
+        url("favicon.ico"),
+        ICO_SIZES
+            .iter()
+            .map(|size| format!("{}x{}", size, size))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )];
+    for size in &config.favicon.sizes {
+        links.push(format!(
+            "<link rel=\"icon\" type=\"image/png\" sizes=\"{}x{}\" href=\"{}\" />",
+            size,
+            size,
+            url(&png_name(*size))
+        ));
+    }
+    links.push(format!(
+        "<link rel=\"apple-touch-icon\" sizes=\"{}x{}\" href=\"{}\" />",
+        config.favicon.apple_touch_size,
+        config.favicon.apple_touch_size,
+        url("apple-touch-icon.png")
+    ));
+    Some(links.join("\n"))
+}
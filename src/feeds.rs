@@ -0,0 +1,283 @@
+use crate::{
+    config::FeedContent,
+    content::{extract_metadata, DATE_FORMAT},
+    escape_xml,
+    pages::{is_past_cutoff, listing_path},
+    PostMetadata, Site,
+};
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use serde::Serialize;
+use std::{cmp::Reverse, fs};
+
+// A feed advertised in a page's head for browsers and feed readers
+#[derive(Serialize)]
+pub(crate) struct FeedLink {
+    title: String,
+    url: String,
+}
+
+// The feed that goes with a page: the combined one for the homepage, its
+// section's for the section's listing pages and posts. Feeds need a base URL,
+// so there's none without it.
+pub(crate) fn page_feeds(site: &Site, page_url: &str, section: Option<&str>) -> Vec<FeedLink> {
+    let config = &site.config;
+    if config.base_url.is_empty() {
+        return vec![];
+    }
+    let feed = |title: String, path: &str| FeedLink {
+        title,
+        url: config.absolute_url(&config.site_url(path)),
+    };
+    if page_url == "/index.html" || page_url == "/" {
+        return vec![feed(config.feed.title.clone(), "/index.xml")];
+    }
+    match section {
+        Some(section) => vec![feed(
+            section_feed_title(site, section),
+            &format!("/{}/index.xml", section),
+        )],
+        None => vec![],
+    }
+}
+
+// A section feed's `title`, or the `[feed]` one with the section name appended
+fn section_feed_title(site: &Site, name: &str) -> String {
+    let config = &site.config;
+    config.sections[name]
+        .feed
+        .title
+        .clone()
+        .unwrap_or_else(|| format!("{} - {}", config.feed.title, name))
+}
+
+// What a feed says about itself in its `<channel>`
+struct Channel<'a> {
+    title: &'a str,
+    description: &'a str,
+    // Site path of the page the feed goes with
+    link: &'a str,
+    self_path: &'a str,
+}
+
+// Write an RSS feed per section at `/<section>/index.xml` and one at
+// `/index.xml` with the latest posts of every section
+pub(crate) fn generate_feeds(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let config = &site.config;
+
+    // Feed readers need absolute links, like the sitemap
+    if config.base_url.is_empty() {
+        return Ok(());
+    }
+
+    let title = &config.feed.title;
+    let description = config.feed.description.as_deref().unwrap_or(title);
+    for (name, section) in &config.sections {
+        let section_posts: Vec<&PostMetadata> = posts
+            .iter()
+            .filter(|post| post.category.as_ref() == Some(name))
+            .collect();
+        let section_title = section_feed_title(site, name);
+        let feed = render_feed(
+            site,
+            Channel {
+                title: &section_title,
+                description: section_feed_description(site, name, &section_title),
+                link: &config.site_url(&listing_path(config, name, section, 1)),
+                self_path: &format!("/{}/index.xml", name),
+            },
+            section_posts,
+            section.feed.limit.unwrap_or(config.feed.limit),
+            section.feed.content.unwrap_or(config.feed.content),
+        );
+        let dir = site.dist_dir.join(name);
+        fs::create_dir_all(&dir).context("Failed to create feed directory")?;
+        fs::write(dir.join("index.xml"), feed)
+            .with_context(|| format!("Failed to write {} feed", name))?;
+    }
+
+    let section_posts = posts
+        .iter()
+        .filter(|post| post.category.is_some())
+        .collect();
+    let feed = render_feed(
+        site,
+        Channel {
+            title,
+            description,
+            link: &config.site_url("/"),
+            self_path: "/index.xml",
+        },
+        section_posts,
+        config.feed.limit,
+        config.feed.content,
+    );
+    fs::write(site.dist_dir.join("index.xml"), feed).context("Failed to write feed")?;
+    Ok(())
+}
+
+// A section feed's `description`, then the `[feed]` one, then its title
+fn section_feed_description<'a>(site: &'a Site, name: &str, title: &'a str) -> &'a str {
+    let config = &site.config;
+    config.sections[name]
+        .feed
+        .description
+        .as_deref()
+        .or(config.feed.description.as_deref())
+        .unwrap_or(title)
+}
+
+// Site path of a tag's feed, the same with or without `clean_urls`
+fn tag_feed_path(slug: &str) -> String {
+    format!("/tags/{}/feed.xml", slug)
+}
+
+// The feed a tag's page advertises, none unless `[feed]` has `tags = true`
+pub(crate) fn tag_feeds(site: &Site, name: &str, slug: &str) -> Vec<FeedLink> {
+    let config = &site.config;
+    if !config.feed.tags || config.base_url.is_empty() {
+        return vec![];
+    }
+    vec![FeedLink {
+        title: format!("{} - {}", config.feed.title, name),
+        url: config.absolute_url(&config.site_url(&tag_feed_path(slug))),
+    }]
+}
+
+// Write the feed of a tag's posts, with `[feed]`'s limit and content
+pub(crate) fn generate_tag_feed(
+    site: &Site,
+    name: &str,
+    slug: &str,
+    page_url: &str,
+    posts: &[&PostMetadata],
+) -> Result<()> {
+    let config = &site.config;
+    if !config.feed.tags || config.base_url.is_empty() {
+        return Ok(());
+    }
+    let path = tag_feed_path(slug);
+    let title = format!("{} - {}", config.feed.title, name);
+    let feed = render_feed(
+        site,
+        Channel {
+            title: &title,
+            description: config.feed.description.as_deref().unwrap_or(&title),
+            link: page_url,
+            self_path: &path,
+        },
+        posts.to_vec(),
+        config.feed.limit,
+        config.feed.content,
+    );
+    let output = site.dist_dir.join(path.trim_start_matches('/'));
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).context("Failed to create feed directory")?;
+    }
+    fs::write(&output, feed).with_context(|| format!("Failed to write the feed of tag {}", name))
+}
+
+// The newest dated posts first, at most `limit` of them. Full content goes in
+// `content:encoded` next to the description.
+fn render_feed(
+    site: &Site,
+    channel: Channel,
+    posts: Vec<&PostMetadata>,
+    limit: usize,
+    content: FeedContent,
+) -> String {
+    let config = &site.config;
+    let mut posts: Vec<(NaiveDateTime, &PostMetadata)> = posts
+        .into_iter()
+        .filter(|post| !post.noindex)
+        .filter(|post| !is_past_cutoff(site, post, config.feed_cutoff_days))
+        .filter_map(|post| {
+            let date = NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok()?;
+            Some((date.and_time(post_time(site, post)), post))
+        })
+        .collect();
+    // Newest first, posts of the same moment by `weight` and then by slug, so
+    // the order doesn't depend on the order files were read in
+    posts.sort_by_key(|(date, post)| (Reverse(*date), post_weight(post), post.slug.clone()));
+    posts.truncate(limit);
+
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\"{}>\n  \
+         <channel>\n    \
+         <title>{}</title>\n    \
+         <link>{}</link>\n    \
+         <atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\" />\n    \
+         <description>{}</description>\n",
+        match content {
+            FeedContent::Full => " xmlns:content=\"http://purl.org/rss/1.0/modules/content/\"",
+            FeedContent::Summary => "",
+        },
+        escape_xml(channel.title),
+        escape_xml(&config.absolute_url(channel.link)),
+        escape_xml(&config.absolute_url(&config.site_url(channel.self_path))),
+        escape_xml(channel.description)
+    );
+    for (date, post) in posts {
+        let link = escape_xml(&config.absolute_url(&post.url));
+        feed.push_str(&format!(
+            "    <item>\n      \
+             <title>{}</title>\n      \
+             <link>{}</link>\n      \
+             <guid>{}</guid>\n      \
+             <pubDate>{}</pubDate>\n      \
+             <description>{}</description>\n",
+            escape_xml(&post.title),
+            link,
+            link,
+            date.format("%a, %d %b %Y %H:%M:%S +0000"),
+            escape_xml(&post.description)
+        ));
+        if content == FeedContent::Full {
+            // `]]>` would end the CDATA section early
+            feed.push_str(&format!(
+                "      <content:encoded><![CDATA[{}]]></content:encoded>\n",
+                post.content.replace("]]>", "]]]]><![CDATA[>")
+            ));
+        }
+        if let Some(category) = &post.category {
+            feed.push_str(&format!(
+                "      <category>{}</category>\n",
+                escape_xml(category)
+            ));
+        }
+        feed.push_str("    </item>\n");
+    }
+    feed.push_str("  </channel>\n</rss>\n");
+    feed
+}
+
+// Time of day from a `time` key or a `date` written in one of the
+// `date_formats` with a time, midnight when the post has neither
+fn post_time(site: &Site, post: &PostMetadata) -> NaiveTime {
+    if let Some(time) = extract_metadata(&post.frontmatter, "time") {
+        let time = time.trim();
+        if let Some(time) = ["%H:%M:%S", "%H:%M"]
+            .iter()
+            .find_map(|format| NaiveTime::parse_from_str(time, format).ok())
+        {
+            return time;
+        }
+    }
+    extract_metadata(&post.frontmatter, "date")
+        .and_then(|date| {
+            site.config.date_formats.iter().find_map(|format| {
+                NaiveDateTime::parse_from_str(date.trim(), format)
+                    .ok()
+                    .map(|date| date.time())
+            })
+        })
+        .unwrap_or(NaiveTime::MIN)
+}
+
+// Lower weights come first, posts without one count as 0
+fn post_weight(post: &PostMetadata) -> i64 {
+    extract_metadata(&post.frontmatter, "weight")
+        .and_then(|weight| weight.trim().parse().ok())
+        .unwrap_or(0)
+}
@@ -0,0 +1,65 @@
+use crate::{is_html, Site};
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{fs, time::SystemTime};
+use walkdir::WalkDir;
+
+// Add a `<meta name="content-hash">` to the head of every HTML file under
+// dist, so deploy tooling can upload only the pages that changed. The build
+// time and commit are left out of the hash, otherwise every build would
+// change every page that shows them.
+//
+// Pages an earlier build left behind keep the hash they were written with:
+// the time they show is no longer the build's, so hashing them again would
+// change it.
+pub(crate) fn add_content_hashes(site: &Site, started: Option<SystemTime>) -> Result<()> {
+    // Pages kept from an earlier `--only` build already have one
+    let existing = Regex::new(r#"<meta name="content-hash" content="[0-9a-f]*" />\n?"#)?;
+    let volatile = [&site.build_info.time, &site.build_info.commit];
+    for entry in WalkDir::new(&site.dist_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_html(path) {
+            continue;
+        }
+        let modified = entry.metadata().ok().and_then(|meta| meta.modified().ok());
+        let left_behind = modified
+            .zip(started)
+            .is_some_and(|(modified, started)| modified < started);
+        let html = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        if left_behind && existing.is_match(&html) {
+            continue;
+        }
+        let page = existing.replace_all(&html, "");
+        let Some(head_end) = page.find("</head>") else {
+            continue;
+        };
+        let stable = volatile
+            .iter()
+            .filter(|value| !value.is_empty())
+            .fold(page.to_string(), |page, value| {
+                page.replace(value.as_str(), "")
+            });
+        let hashed = format!(
+            "{}<meta name=\"content-hash\" content=\"{:016x}\" />\n{}",
+            &page[..head_end],
+            fnv1a(stable.as_bytes()),
+            &page[head_end..]
+        );
+        if hashed != html {
+            fs::write(path, hashed)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+// 64-bit FNV-1a, which unlike std's hasher is the same on every Rust version
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
@@ -0,0 +1,54 @@
+use crate::{
+    config::{HookConfig, HookStage},
+    Site,
+};
+use anyhow::{Context, Result};
+use std::{path, process::Command};
+
+// Run the hooks configured for `stage` one after another through the shell,
+// from the project root, the working directory site.toml is read from. The
+// first one that fails stops the build.
+pub(crate) fn run_hooks(site: &Site, stage: HookStage) -> Result<()> {
+    let config = &site.config;
+    let hooks: Vec<&HookConfig> = config
+        .hooks
+        .iter()
+        .filter(|hook| hook.stage == stage)
+        .collect();
+    if hooks.is_empty() {
+        return Ok(());
+    }
+
+    // Absolute, so hooks that change directory can still use them
+    let source_dir =
+        path::absolute(&site.source_dir).context("Failed to resolve the source dir")?;
+    let dist_dir = path::absolute(&site.dist_dir).context("Failed to resolve the dist dir")?;
+    for hook in hooks {
+        let status = shell(&hook.command)
+            .env("SITE_SOURCE_DIR", &source_dir)
+            .env("SITE_DIST_DIR", &dist_dir)
+            .env("SITE_BASE_URL", &config.base_url)
+            .env("SITE_BASE_PATH", &config.base_path)
+            .env("SITE_ENV", &config.env)
+            .status()
+            .with_context(|| format!("Failed to run hook `{}`", hook.command))?;
+        if !status.success() {
+            anyhow::bail!("Hook `{}` failed with {}", hook.command, status);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("sh");
+    shell.args(["-c", command]);
+    shell
+}
+
+#[cfg(not(unix))]
+fn shell(command: &str) -> Command {
+    let mut shell = Command::new("cmd");
+    shell.args(["/C", command]);
+    shell
+}
@@ -0,0 +1,34 @@
+use crate::Site;
+use anyhow::{Context, Result};
+use std::fs;
+
+pub(crate) fn generate_humans_txt(site: &Site) -> Result<()> {
+    let output_path = site.dist_dir.join("humans.txt");
+    let override_path = site.source_dir.join("static/humans.txt");
+    if override_path.exists() {
+        fs::copy(override_path, output_path).context("Failed to copy humans.txt")?;
+        return Ok(());
+    }
+
+    let mut humans = String::from("/* TEAM */\n");
+    let author = &site.config.author;
+    for (label, value) in [
+        ("Author", &author.name),
+        ("Contact", &author.contact),
+        ("Location", &author.location),
+    ] {
+        if let Some(value) = value {
+            humans.push_str(&format!("  {}: {}\n", label, value));
+        }
+    }
+
+    humans.push_str("\n/* SITE */\n");
+    humans.push_str(&format!(
+        "  Last update: {}\n",
+        site.build_date().format("%Y/%m/%d")
+    ));
+    humans.push_str("  Tools: Rust, Tera, pulldown-cmark\n");
+
+    fs::write(output_path, humans).context("Failed to write humans.txt")?;
+    Ok(())
+}
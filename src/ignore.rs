@@ -0,0 +1,39 @@
+use anyhow::{Context, Result};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::Path;
+
+// Decides which source files are left out of the output. Patterns are matched
+// against both the file name and the path relative to the walked directory.
+pub(crate) struct IgnoreMatcher {
+    globs: GlobSet,
+    include_hidden: bool,
+}
+
+impl IgnoreMatcher {
+    pub fn new(patterns: &[String], include_hidden: bool) -> Result<Self> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in patterns {
+            let glob = Glob::new(pattern)
+                .with_context(|| format!("Invalid ignore pattern `{}`", pattern))?;
+            builder.add(glob);
+        }
+        Ok(Self {
+            globs: builder.build().context("Failed to build ignore patterns")?,
+            include_hidden,
+        })
+    }
+
+    pub fn is_ignored(&self, relative_path: &Path) -> bool {
+        let hidden = relative_path
+            .components()
+            .any(|component| component.as_os_str().to_string_lossy().starts_with('.'));
+        if hidden && !self.include_hidden {
+            return true;
+        }
+
+        self.globs.is_match(relative_path)
+            || relative_path
+                .file_name()
+                .is_some_and(|name| self.globs.is_match(name))
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{check::resolve_dots, content::extract_metadata, urls, PostMetadata, Site};
+use anyhow::Result;
+use regex::Regex;
+
+// Local images posts show that the build didn't write: a frontmatter `image`
+// or an `<img src>` in the body, markdown or raw HTML. External images and
+// `data:` URIs aren't checked.
+pub(crate) fn check_images(site: &Site, posts: &[PostMetadata]) -> Result<Vec<String>> {
+    let img = Regex::new(r#"(?i)<img\b[^>]*?\ssrc\s*=\s*["']([^"']+)["']"#)?;
+    let mut problems = vec![];
+    for post in posts {
+        let frontmatter = extract_metadata(&post.frontmatter, "image");
+        let body = img
+            .captures_iter(&post.content)
+            .map(|captures| captures[1].to_string());
+        let mut seen = vec![];
+        for src in frontmatter.into_iter().chain(body) {
+            if seen.contains(&src) {
+                continue;
+            }
+            let Some(path) = local_path(site, &post.url, &src) else {
+                continue;
+            };
+            if !site.dist_dir.join(path.trim_start_matches('/')).is_file() {
+                problems.push(format!(
+                    "{} shows the image `{}`, which doesn't exist",
+                    post.url, src
+                ));
+            }
+            seen.push(src);
+        }
+    }
+    Ok(problems)
+}
+
+// The path under dist a page's image is served from, `None` for external ones
+fn local_path(site: &Site, page_url: &str, src: &str) -> Option<String> {
+    let config = &site.config;
+    let mut path = src.split(['?', '#']).next().unwrap_or_default();
+    for origin in [config.image_cdn.as_deref(), Some(config.base_url.as_str())]
+        .into_iter()
+        .flatten()
+        .map(|origin| origin.trim_end_matches('/'))
+        .filter(|origin| !origin.is_empty())
+    {
+        if let Some(rest) = path
+            .strip_prefix(origin)
+            .filter(|rest| rest.starts_with('/'))
+        {
+            path = rest;
+        }
+    }
+    if path.is_empty() || urls::is_external(path) {
+        return None;
+    }
+    // Page URLs, and so relative links, include the base path
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        let dir = page_url.rsplit_once('/').map_or("", |(dir, _)| dir);
+        resolve_dots(&format!("{}/{}", dir, path))
+    };
+    let base_path = format!("/{}", config.base_path.trim_matches('/'));
+    match path.strip_prefix(base_path.as_str()) {
+        Some(rest) if base_path != "/" && rest.starts_with('/') => Some(rest.to_string()),
+        _ => Some(path),
+    }
+}
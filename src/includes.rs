@@ -0,0 +1,95 @@
+use crate::{encoding::read_text, Site};
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::path::{Path, PathBuf};
+
+// Deep enough for any sane nesting, shallow enough to catch runaway includes
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+// Inline `{{< include "path.md" >}}` directives before the markdown is parsed.
+// Paths are resolved relative to the including file first, then to the
+// configured includes directory.
+pub(crate) fn expand_includes(site: &Site, src_path: &Path, markdown: &str) -> Result<String> {
+    let directive = Regex::new(r#"\{\{<\s*include\s+"([^"]+)"\s*>\}\}"#)?;
+    expand(
+        site,
+        &directive,
+        &mut vec![src_path.to_path_buf()],
+        markdown,
+    )
+}
+
+fn expand(
+    site: &Site,
+    directive: &Regex,
+    stack: &mut Vec<PathBuf>,
+    markdown: &str,
+) -> Result<String> {
+    let mut error = None;
+    let expanded = directive.replace_all(markdown, |captures: &Captures| {
+        match include(site, directive, stack, &captures[1]) {
+            Ok(content) => content,
+            Err(err) => {
+                error.get_or_insert(err);
+                String::new()
+            }
+        }
+    });
+    match error {
+        Some(err) => Err(err),
+        None => Ok(expanded.into_owned()),
+    }
+}
+
+fn include(site: &Site, directive: &Regex, stack: &mut Vec<PathBuf>, name: &str) -> Result<String> {
+    let including = stack.last().unwrap();
+    let path = resolve(site, including, name).with_context(|| {
+        format!(
+            "Included file `{}` not found from {}",
+            name,
+            including.display()
+        )
+    })?;
+
+    let canonical = path.canonicalize().context("Failed to resolve include")?;
+    let chain = || {
+        stack
+            .iter()
+            .chain([&path])
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    };
+    if stack
+        .iter()
+        .any(|seen| seen.canonicalize().ok().as_ref() == Some(&canonical))
+    {
+        anyhow::bail!("Include cycle: {}", chain());
+    }
+    if stack.len() > MAX_INCLUDE_DEPTH {
+        anyhow::bail!(
+            "Includes nested more than {} deep: {}",
+            MAX_INCLUDE_DEPTH,
+            chain()
+        );
+    }
+
+    let (content, _) = read_text(&path, site.config.markdown.fallback_encoding.as_deref())
+        .with_context(|| format!("Failed to read included file {}", path.display()))?;
+    stack.push(path);
+    let expanded = expand(site, directive, stack, content.trim_end());
+    stack.pop();
+    expanded
+}
+
+fn resolve(site: &Site, including: &Path, name: &str) -> Option<PathBuf> {
+    let relative = including.parent().map(|dir| dir.join(name));
+    let shared = site
+        .source_dir
+        .join(&site.config.markdown.includes_dir)
+        .join(name);
+    relative
+        .into_iter()
+        .chain([shared])
+        .find(|path| path.is_file())
+}
@@ -0,0 +1,104 @@
+use crate::Config;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+use tera::{Result as TeraResult, Value};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Images smaller than `inline_images_below` bytes written into the page as
+// `data:` URIs, counted for the build report
+pub(crate) struct ImageInliner {
+    threshold: u64,
+    source_dir: PathBuf,
+    inlined: AtomicUsize,
+}
+
+impl ImageInliner {
+    pub(crate) fn new(threshold: u64, source_dir: &Path) -> Arc<Self> {
+        Arc::new(Self {
+            threshold,
+            source_dir: source_dir.to_path_buf(),
+            inlined: AtomicUsize::new(0),
+        })
+    }
+
+    // The `data:` URI of a local image under the threshold, `None` for
+    // anything else. `/assets/...` is read from the assets, other site paths
+    // from co-located images under `src/content`.
+    pub(crate) fn data_uri(&self, url: &str) -> Option<String> {
+        if self.threshold == 0 || !url.starts_with('/') || url.starts_with("//") {
+            return None;
+        }
+        let path = url.split(['?', '#']).next()?.trim_start_matches('/');
+        let mime = match Path::new(path)
+            .extension()?
+            .to_str()?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "avif" => "image/avif",
+            "gif" => "image/gif",
+            "jpeg" | "jpg" => "image/jpeg",
+            "png" => "image/png",
+            "svg" => "image/svg+xml",
+            "webp" => "image/webp",
+            _ => return None,
+        };
+        let file = if path.starts_with("assets/") {
+            self.source_dir.join(path)
+        } else {
+            self.source_dir.join("content").join(path)
+        };
+        if fs::metadata(&file).ok()?.len() >= self.threshold {
+            return None;
+        }
+        let bytes = fs::read(&file).ok()?;
+        self.inlined.fetch_add(1, Ordering::Relaxed);
+        Some(format!("data:{};base64,{}", mime, base64(&bytes)))
+    }
+
+    // How many images were inlined since the last call
+    pub(crate) fn take_count(&self) -> usize {
+        self.inlined.swap(0, Ordering::Relaxed)
+    }
+}
+
+pub(crate) fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = (u32::from(chunk[0]) << 16)
+            | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8)
+            | u32::from(*chunk.get(2).unwrap_or(&0));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64[(group >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// `{{ "/assets/icons/rss.svg" | inline_image }}` in templates, the image as a
+// `data:` URI when it's small enough and its URL otherwise
+pub(crate) fn inline_image_filter(
+    inliner: Arc<ImageInliner>,
+    urls: Config,
+) -> impl Fn(&Value, &HashMap<String, Value>) -> TeraResult<Value> {
+    move |value, _| {
+        let url = tera::try_get_value!("inline_image", "value", String, value);
+        Ok(Value::String(
+            inliner
+                .data_uri(&url)
+                .unwrap_or_else(|| urls.image_url(&url)),
+        ))
+    }
+}
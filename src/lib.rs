@@ -0,0 +1,836 @@
+pub mod check;
+pub mod config;
+pub mod migrate;
+pub mod orphans;
+pub mod serve;
+pub mod watch;
+
+mod amp;
+mod anchors;
+mod assets;
+mod citations;
+mod comments;
+mod compress;
+mod content;
+mod cta;
+mod diff;
+mod encoding;
+mod error;
+mod favicon;
+mod feeds;
+mod hashes;
+mod hooks;
+mod humans;
+mod ignore;
+mod images;
+mod includes;
+mod inline;
+mod integrity;
+mod locales;
+mod lock;
+mod maintenance;
+mod manifest;
+mod markdown;
+mod pages;
+mod print;
+mod redirects;
+mod related;
+mod remote;
+mod schema;
+mod sitemap;
+mod slugs;
+mod stale;
+mod stats;
+mod strict;
+mod tags;
+mod tidy;
+mod urls;
+mod validate;
+
+pub use compress::Compressed;
+pub use config::Config;
+pub use content::PostMetadata;
+pub use diff::BuildDiff;
+pub use error::SiteError;
+pub use stale::StalePost;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, FixedOffset, Local};
+use config::HookStage;
+use serde::{Serialize, Serializer};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    process::Command,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Instant, SystemTime},
+};
+use tera::Tera;
+use walkdir::WalkDir;
+
+// Everything a build needs: the configuration, where to read the site sources
+// (`content`, `template`, `assets`, ...) from and where to write the output
+pub struct Site {
+    pub config: Config,
+    pub source_dir: PathBuf,
+    pub dist_dir: PathBuf,
+    template_dir: PathBuf,
+    tera: Tera,
+    build_info: BuildInfo,
+    // Contents of the `inline_css` stylesheets, read once per build
+    inline_styles: String,
+    // The AMP stylesheet, read once when AMP pages are enabled
+    amp_styles: String,
+    // Posts of the current build for the `post()` template function
+    posts: content::PostIndex,
+    // The newest posts of every section, `latest_across` in templates
+    latest_across: RwLock<Vec<tera::Value>>,
+    // Slugs given to content files by `on_slug_collision`, by source path
+    slugs: RwLock<HashMap<PathBuf, String>>,
+    images: Arc<inline::ImageInliner>,
+    // The `[cta]` snippet, rendered once per build
+    cta_html: RwLock<Option<String>>,
+}
+
+// Exposed to templates as `build`, gathered once when the site is set up
+#[derive(Debug, Serialize)]
+struct BuildInfo {
+    // Short SHA of the checked out commit, `unknown` outside a git repository
+    commit: String,
+    // RFC 3339 timestamp, usable with Tera's `date` filter
+    time: String,
+    #[serde(skip)]
+    timestamp: DateTime<FixedOffset>,
+}
+
+impl BuildInfo {
+    fn gather(config: &Config, source_dir: &Path) -> Self {
+        let commit = Command::new("git")
+            .args(["rev-parse", "--short", "HEAD"])
+            .current_dir(source_dir)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .and_then(|output| String::from_utf8(output.stdout).ok())
+            .map(|commit| commit.trim().to_string())
+            .unwrap_or_else(|| "unknown".to_string());
+
+        let timestamp = match config.source_date_epoch {
+            Some(seconds) => DateTime::from_timestamp(seconds, 0).map(|time| time.fixed_offset()),
+            None if config.reproducible => Some(DateTime::UNIX_EPOCH.fixed_offset()),
+            None => Some(Local::now().fixed_offset()),
+        }
+        .unwrap_or(DateTime::UNIX_EPOCH.fixed_offset());
+        Self {
+            commit,
+            time: timestamp.to_rfc3339(),
+            timestamp,
+        }
+    }
+}
+
+impl Site {
+    pub fn new(
+        mut config: Config,
+        source_dir: impl Into<PathBuf>,
+        dist_dir: impl Into<PathBuf>,
+    ) -> Result<Self> {
+        let source_dir = source_dir.into();
+
+        // Absolute URLs use the canonical host however the base URL was written
+        if let Some(host) = &config.canonical_host {
+            config.base_url = urls::canonicalize_host(&config.base_url, host);
+        }
+
+        // A typo in the encoding is better caught before any file needs it
+        if let Some(label) = &config.markdown.fallback_encoding {
+            encoding::fallback_encoding(label)?;
+        }
+
+        // Initialize template engine
+        let template_dir = config
+            .template_dir
+            .clone()
+            .unwrap_or_else(|| source_dir.join("template"));
+        // Relative to wherever the site is built from, so say where that is
+        let template_dir = std::path::absolute(&template_dir).unwrap_or(template_dir);
+        if !template_dir.is_dir() {
+            return Err(SiteError::NoTemplates { dir: template_dir }.into());
+        }
+        let templates = template_dir.join("**/*.html");
+        let mut tera =
+            Tera::new(&templates.to_string_lossy()).map_err(|source| SiteError::TemplateLoad {
+                dir: template_dir.clone(),
+                source,
+            })?;
+        if tera.get_template_names().next().is_none() {
+            return Err(SiteError::NoTemplates { dir: template_dir }.into());
+        }
+        tera.register_filter(
+            "url",
+            urls::url_filter(
+                config.trailing_slash,
+                config.base_url.clone(),
+                config.base_path.clone(),
+            ),
+        );
+        tera.register_filter(
+            "normalize_url",
+            urls::normalize_url_filter(config.trailing_slash, config.base_url.clone()),
+        );
+        tera.register_filter("integrity", integrity::integrity_filter(&source_dir));
+        let posts = content::PostIndex::default();
+        tera.register_function("post", content::post_function(posts.clone()));
+        let images = inline::ImageInliner::new(config.assets.inline_images_below, &source_dir);
+        // Just what `image_url` needs for images that aren't inlined
+        let image_urls = Config {
+            base_url: config.base_url.clone(),
+            base_path: config.base_path.clone(),
+            trailing_slash: config.trailing_slash,
+            image_cdn: config.image_cdn.clone(),
+            ..Config::default()
+        };
+        tera.register_filter(
+            "inline_image",
+            inline::inline_image_filter(images.clone(), image_urls),
+        );
+
+        // Partials only make sense inside other templates
+        let section_templates = config
+            .sections
+            .values()
+            .flat_map(|section| [&section.template, &section.list_template]);
+        if let Some(partial) = section_templates.into_iter().find(|name| is_partial(name)) {
+            anyhow::bail!(
+                "Template `{}` is a partial and can't be rendered as a page",
+                partial
+            );
+        }
+
+        // Sections are found by their directory under `src/content`
+        for (name, section) in &config.sections {
+            let dir = section.dir.as_deref().unwrap_or(name);
+            let mut components = Path::new(dir).components();
+            if dir.is_empty() || !components.all(|part| matches!(part, Component::Normal(_))) {
+                anyhow::bail!(
+                    "Section `{}` reads `{}`, which isn't a directory under src/content; \
+                     set `dir` relative to it, like `dir = \"books/series\"`",
+                    name,
+                    dir
+                );
+            }
+        }
+        if let Some(default) = &config.default_section {
+            if !config.sections.contains_key(default) {
+                anyhow::bail!("`default_section = \"{}\"` isn't a section", default);
+            }
+        }
+
+        compress::check_levels(&config.compress)?;
+
+        if let Some(listing) = &config.homepage.listing {
+            if !config.sections.contains_key(listing) {
+                anyhow::bail!(
+                    "`listing = \"{}\"` under `[homepage]` isn't a section",
+                    listing
+                );
+            }
+        }
+
+        let mut inline_styles = String::new();
+        for stylesheet in &config.inline_css {
+            let path = source_dir.join("assets").join(stylesheet);
+            let css = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read inline stylesheet {}", path.display()))?;
+            inline_styles.push_str(css.trim_end());
+            inline_styles.push('\n');
+        }
+
+        let mut amp_styles = String::new();
+        if config.amp.enabled {
+            if tera.get_template(&config.amp.template).is_err() {
+                anyhow::bail!("AMP pages need a `{}` template", config.amp.template);
+            }
+            let path = source_dir.join("assets").join(&config.amp.stylesheet);
+            let css = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read AMP stylesheet {}", path.display()))?;
+            amp_styles = amp::amp_css(&css);
+        }
+
+        Ok(Self {
+            build_info: BuildInfo::gather(&config, &source_dir),
+            inline_styles,
+            amp_styles,
+            posts,
+            latest_across: RwLock::default(),
+            slugs: RwLock::default(),
+            images,
+            cta_html: RwLock::default(),
+            config,
+            source_dir,
+            dist_dir: dist_dir.into(),
+            template_dir,
+            tera,
+        })
+    }
+
+    // The slug of a content file, its name unless `on_slug_collision` gave it another
+    fn slug(&self, src_path: &Path) -> Option<String> {
+        let slugs = self.slugs.read().unwrap_or_else(PoisonError::into_inner);
+        match slugs.get(src_path) {
+            Some(slug) => Some(slug.clone()),
+            None => src_path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .map(str::to_string),
+        }
+    }
+
+    fn content_dir(&self) -> PathBuf {
+        self.source_dir.join("content")
+    }
+
+    // Optional pages like `404.html` are only rendered when the site has a template for them
+    fn has_template(&self, name: &str) -> bool {
+        self.tera
+            .get_template_names()
+            .any(|template| template == name)
+    }
+
+    // Render a template, checking its conditions first with `strict_templates`
+    fn render(&self, name: &str, context: &tera::Context) -> tera::Result<String> {
+        if self.config.strict_templates {
+            let undefined = strict::undefined_variables(&self.tera, name, context);
+            if !undefined.is_empty() {
+                return Err(tera::Error::msg(format!(
+                    "'{}' tests variables that aren't in the context: `{}`. Check them with `is defined` if they're optional.",
+                    name,
+                    undefined.join("`, `")
+                )));
+            }
+        }
+        self.tera.render(name, context)
+    }
+
+    // A section's template from its own template directory, e.g.
+    // `books/book_detail.html`, falling back to the shared one
+    fn section_template(&self, section: &str, name: &str) -> String {
+        let dir = self
+            .config
+            .sections
+            .get(section)
+            .and_then(|section| section.template_dir.as_deref())
+            .unwrap_or(section);
+        let scoped = format!("{}/{}", dir.trim_matches('/'), name);
+        if self.has_template(&scoped) {
+            scoped
+        } else {
+            name.to_string()
+        }
+    }
+
+    // Day of the build, fixed in reproducible builds
+    fn build_date(&self) -> chrono::NaiveDate {
+        self.build_info.timestamp.date_naive()
+    }
+}
+
+// Summary of what a build did, for the caller to report
+#[derive(Debug, Default, Serialize)]
+pub struct BuildReport {
+    pub dist_dir: PathBuf,
+    // How long the build took, hooks included
+    pub duration_ms: u64,
+    pub warnings: Vec<String>,
+    pub assets_copied: usize,
+    pub assets_skipped: usize,
+    pub images_optimized: usize,
+    pub image_bytes_saved: u64,
+    pub image_variants: usize,
+    // Images written into pages as `data:` URIs
+    pub images_inlined: usize,
+    // Totals per format of the precompressed copies, with `[compress]`
+    pub compressed: Vec<Compressed>,
+    // Pages written per section name, standalone pages under `None`
+    #[serde(serialize_with = "serialize_published")]
+    pub published: BTreeMap<Option<String>, usize>,
+    // Content files that were left out or kept out of search engines
+    pub excluded: Vec<Exclusion>,
+    // Posts due for a review under `stale_after_days`, oldest first
+    pub stale: Vec<StalePost>,
+    // Pages added, removed and changed since the previous build, with
+    // `diff_report`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<BuildDiff>,
+    // Word counts for `stats.json`, only gathered when it's written
+    #[serde(skip)]
+    stats: stats::ContentStats,
+    // The files written for each source file, for `.manifest.json`
+    #[serde(skip)]
+    outputs: BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+    // When writing began, files older than that are left from earlier builds
+    #[serde(skip)]
+    started: Option<SystemTime>,
+}
+
+// JSON keys have to be strings, so the counts become a list of
+// `{"section": ..., "pages": ...}` with a `null` section for standalone pages
+fn serialize_published<S: Serializer>(
+    published: &BTreeMap<Option<String>, usize>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Published<'a> {
+        section: &'a Option<String>,
+        pages: usize,
+    }
+    serializer.collect_seq(published.iter().map(|(section, pages)| Published {
+        section,
+        pages: *pages,
+    }))
+}
+
+#[derive(Debug, Serialize)]
+pub struct Exclusion {
+    pub path: PathBuf,
+    pub reason: ExclusionReason,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExclusionReason {
+    Draft,
+    // A `status` that isn't built, e.g. `review`
+    Unpublished,
+    NoSlug,
+    // Not changed since `--since`, only listed
+    Unchanged,
+    // Published, but not in the sitemap or feeds
+    Noindex,
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self {
+            ExclusionReason::Draft => "draft",
+            ExclusionReason::Unpublished => "unpublished status",
+            ExclusionReason::NoSlug => "no usable slug",
+            ExclusionReason::Unchanged => "unchanged",
+            ExclusionReason::Noindex => "noindex",
+        };
+        f.write_str(reason)
+    }
+}
+
+impl BuildReport {
+    fn exclude(&mut self, path: &Path, reason: ExclusionReason) {
+        self.excluded.push(Exclusion {
+            path: path.to_path_buf(),
+            reason,
+        });
+    }
+
+    fn add_assets(&mut self, stats: assets::AssetStats) {
+        self.assets_copied += stats.copied;
+        self.assets_skipped += stats.skipped;
+        self.images_optimized += stats.images_optimized;
+        self.image_bytes_saved += stats.bytes_saved;
+        self.image_variants += stats.variants;
+        for (source, outputs) in stats.outputs {
+            self.outputs.entry(source).or_default().extend(outputs);
+        }
+    }
+
+    fn record_output(&mut self, source: &Path, output: &Path) {
+        self.outputs
+            .entry(source.to_path_buf())
+            .or_default()
+            .insert(output.to_path_buf());
+    }
+}
+
+pub fn build(site: &Site) -> Result<BuildReport> {
+    let start = Instant::now();
+    let _lock = lock::acquire(site)?;
+    let before = if site.config.diff_report {
+        Some(diff::snapshot(&site.dist_dir)?)
+    } else {
+        None
+    };
+    // A pool of its own, so every build of a long running process can use a
+    // different size
+    let mut report = match site.config.threads {
+        Some(threads) => rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .context("Failed to start the build threads")?
+            .install(|| build_site(site)),
+        None => build_site(site),
+    }?;
+    report.dist_dir = site.dist_dir.clone();
+    if let Some(before) = before {
+        report.diff = Some(diff::diff(&before, &diff::snapshot(&site.dist_dir)?));
+    }
+    report.images_inlined = site.images.take_count();
+    report.duration_ms = start.elapsed().as_millis() as u64;
+    Ok(report)
+}
+
+// The homepage, generic page and section templates the site doesn't have
+fn missing_templates(site: &Site) -> Vec<String> {
+    let sections = site.config.sections.iter().flat_map(|(name, section)| {
+        [&section.template, &section.list_template]
+            .map(|template| site.section_template(name, template))
+    });
+    let mut missing: Vec<String> = vec![];
+    // A listing on the homepage takes the place of its template
+    let homepage = site
+        .config
+        .homepage
+        .listing
+        .is_none()
+        .then(|| "homepage.html".to_string());
+    for name in homepage
+        .into_iter()
+        .chain(["page.html".to_string()])
+        .chain(sections)
+    {
+        if !site.has_template(&name) && !missing.contains(&name) {
+            missing.push(name);
+        }
+    }
+    missing
+}
+
+fn build_site(site: &Site) -> Result<BuildReport> {
+    let mut report = BuildReport {
+        started: Some(SystemTime::now()),
+        ..BuildReport::default()
+    };
+    let content_dir = site.content_dir();
+    let dist_dir = &site.dist_dir;
+    hooks::run_hooks(site, HookStage::PreBuild)?;
+
+    // Create the dist directory
+    fs::create_dir_all(dist_dir).context("Failed to create dist directory")?;
+
+    // A placeholder for every URL while the site is down for an overhaul
+    if site.config.maintenance.enabled {
+        maintenance::generate_maintenance(site)?;
+        hooks::run_hooks(site, HookStage::PostBuild)?;
+        return Ok(report);
+    }
+
+    // Better than failing on the first page that needs one
+    let missing = missing_templates(site);
+    if !missing.is_empty() {
+        return Err(SiteError::MissingTemplates {
+            dir: site.template_dir.clone(),
+            names: missing,
+        }
+        .into());
+    }
+
+    // `--only` renders a single file, and its listings with `--with-listings`
+    let only = match &site.config.only {
+        Some(path) => Some(only_file(site, path)?),
+        None => None,
+    };
+    let is_only = |path: &Path| {
+        only.as_ref()
+            .is_none_or(|only| fs::canonicalize(path).is_ok_and(|path| path == *only))
+    };
+
+    *site
+        .cta_html
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = cta::render_cta(site)?;
+
+    pages::check_section_intros(site)?;
+    let files = content_files(site);
+    *site.slugs.write().unwrap_or_else(PoisonError::into_inner) =
+        slugs::resolve_slugs(site, &files)?;
+
+    // Process markdown files
+    let mut posts = vec![];
+    let mut pages = vec![];
+    // The index in `posts` of each page's post
+    let mut page_posts = vec![];
+    for path in files {
+        if !site.config.with_listings && !is_only(&path) {
+            continue;
+        }
+        // Categorize the post based on its directory
+        let relative = path.strip_prefix(&content_dir)?;
+        let section = site.config.section_for(relative);
+        if section.is_none() && !site.config.allows_page(relative) {
+            return Err(SiteError::Validation {
+                message: format!(
+                    "{} isn't in any section's directory, and `require_section` is set",
+                    path.display()
+                ),
+                path,
+            }
+            .into());
+        }
+        if let Some((post, post_pages)) =
+            content::process_markdown(site, &path, section, &mut report)?
+        {
+            if !post_pages.is_empty() {
+                *report.published.entry(post.category.clone()).or_default() += 1;
+            }
+            page_posts.extend(std::iter::repeat_n(posts.len(), post_pages.len()));
+            posts.push(post);
+            pages.extend(post_pages);
+        }
+    }
+
+    report.stale = stale::stale_posts(site, &posts);
+
+    // Pages are written once all posts are known, so any of them can `post()` another
+    let mut index = BTreeMap::new();
+    for post in &posts {
+        if !index.contains_key(&post.slug) {
+            let value = tera::to_value(post).context("Failed to serialize post")?;
+            index.insert(post.slug.clone(), value);
+        }
+    }
+    *site.posts.write().unwrap_or_else(PoisonError::into_inner) = index;
+    let latest = pages::latest_across(&posts, site.config.latest_across)
+        .into_iter()
+        .map(tera::to_value)
+        .collect::<Result<_, _>>()
+        .context("Failed to serialize post")?;
+    *site
+        .latest_across
+        .write()
+        .unwrap_or_else(PoisonError::into_inner) = latest;
+    if site.config.related_posts > 0 {
+        for (page, post) in pages.iter_mut().zip(&page_posts) {
+            // Standalone pages aren't related to anything
+            let Some(section) = posts[*post]
+                .category
+                .as_ref()
+                .and_then(|category| site.config.sections.get(category))
+            else {
+                continue;
+            };
+            if !section.features.related_posts {
+                continue;
+            }
+            let related = related::related_posts(&posts[*post], &posts, site.config.related_posts);
+            page.context_mut().insert("related", &related);
+        }
+    }
+    for (page, post) in pages.iter_mut().zip(&page_posts) {
+        if let Some((position, total)) = related::series_position(&posts[*post], &posts) {
+            page.context_mut().insert("series_position", &position);
+            page.context_mut().insert("series_total", &total);
+        }
+    }
+    if site.config.i18n_fallback {
+        let fallbacks = locales::fallback_pages(site, &pages)?;
+        pages.extend(fallbacks);
+    }
+    for page in pages.iter().filter(|page| is_only(page.source())) {
+        content::write_page(site, page, &mut report)?;
+    }
+    if only.is_some() && !site.config.with_listings {
+        return finish_build(site, report);
+    }
+
+    // Generate the homepage and a listing page per section
+    pages::generate_homepage(site, &posts)?;
+    for name in site.config.sections.keys() {
+        let section_posts: Vec<&PostMetadata> = posts
+            .iter()
+            .filter(|post| post.category.as_ref() == Some(name))
+            .collect();
+        pages::generate_listing(site, name, &section_posts)?;
+        print::generate_print_books(site, name, &section_posts)?;
+    }
+
+    tags::generate_tag_pages(site, &posts)?;
+    pages::generate_updates(site, &posts)?;
+    pages::generate_featured(site, &posts)?;
+
+    // The 404 page can point lost visitors at recent posts, so it comes after them
+    pages::generate_404(site, &posts)?;
+
+    // Write the machine-readable index of all content, the sitemap and feeds
+    manifest::write_content_manifest(site, &posts)?;
+    if site.config.csv {
+        manifest::write_posts_csv(site, &posts)?;
+    }
+    if site.config.stats {
+        stats::write_stats(site, &report.stats)?;
+    }
+    sitemap::generate_sitemap(site, &posts)?;
+    feeds::generate_feeds(site, &posts)?;
+
+    // Keep old URLs of renamed posts working and write the host's headers file
+    redirects::generate_redirects(site, &posts)?;
+    redirects::generate_headers(site)?;
+    if only.is_some() {
+        return finish_build(site, report);
+    }
+
+    // Copy assets
+    let asset_stats = assets::copy_assets(
+        &site.source_dir.join("assets"),
+        &dist_dir.join("assets"),
+        &site.config.assets,
+    )?;
+    report.add_assets(asset_stats);
+
+    // Images kept next to the posts that use them
+    for dir in assets::colocated_dirs(&content_dir, &site.config.assets.colocated_dir) {
+        let asset_stats = assets::copy_assets(
+            &content_dir.join(&dir),
+            &dist_dir.join(&dir),
+            &site.config.assets,
+        )?;
+        report.add_assets(asset_stats);
+    }
+
+    favicon::generate_favicons(site)?;
+
+    // Copy CNAME file if it exists
+    let cname = site.source_dir.join("CNAME");
+    if cname.exists() {
+        fs::copy(&cname, dist_dir.join("CNAME")).context("Failed to copy CNAME file")?;
+        report.record_output(&cname, &dist_dir.join("CNAME"));
+    }
+
+    // Write humans.txt, preferring a hand-written one if present
+    humans::generate_humans_txt(site)?;
+
+    // Fragment links are checked once every page they could point at is written
+    if site.config.check_anchors {
+        report.warnings.extend(anchors::check_anchors(site)?);
+    }
+    if site.config.check_images {
+        report.warnings.extend(images::check_images(site, &posts)?);
+    }
+    if site.config.build_manifest {
+        manifest::write_build_manifest(site, &report.outputs)?;
+    }
+    finish_build(site, report)
+}
+
+// Checks and hooks that end every build, whole or partial
+fn finish_build(site: &Site, mut report: BuildReport) -> Result<BuildReport> {
+    if site.config.tidy {
+        tidy::tidy_dist(&site.dist_dir)?;
+    }
+    if site.config.content_hash {
+        hashes::add_content_hashes(site, report.started)?;
+    }
+    // Last, so the copies have what the pages ended up with
+    if site.config.compress.enabled {
+        report.compressed = compress::compress_dist(site)?;
+    }
+    if let Some(mode) = site.config.file_mode {
+        set_file_modes(&site.dist_dir, mode)?;
+    }
+
+    if site.config.strict && !report.warnings.is_empty() {
+        anyhow::bail!(
+            "Build has warnings in strict mode:\n  {}",
+            report.warnings.join("\n  ")
+        );
+    }
+
+    hooks::run_hooks(site, HookStage::PostBuild)?;
+    Ok(report)
+}
+
+// The canonical path of the `--only` file, which has to be a content file
+fn only_file(site: &Site, path: &Path) -> Result<PathBuf> {
+    let file =
+        fs::canonicalize(path).with_context(|| format!("Failed to find {}", path.display()))?;
+    let content_dir = fs::canonicalize(site.content_dir()).context("Failed to find content")?;
+    if !file.starts_with(&content_dir) || !(is_markdown(&file) || is_html(&file)) {
+        anyhow::bail!(
+            "{} isn't a content file under {}",
+            path.display(),
+            site.content_dir().display()
+        );
+    }
+    Ok(file)
+}
+
+// Markdown and HTML files under `src/content`, in a stable order so generated
+// indexes don't churn
+fn content_files(site: &Site) -> Vec<PathBuf> {
+    WalkDir::new(site.content_dir())
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .map(|entry| entry.into_path())
+        .filter(|path| is_markdown(path) || is_html(path))
+        .filter(|path| !pages::is_section_intro(site, path))
+        .collect()
+}
+
+#[cfg(unix)]
+fn set_file_modes(dist_dir: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    for entry in WalkDir::new(dist_dir).into_iter().filter_map(|e| e.ok()) {
+        if entry.file_type().is_file() {
+            fs::set_permissions(entry.path(), fs::Permissions::from_mode(mode))
+                .with_context(|| format!("Failed to set mode of {}", entry.path().display()))?;
+        }
+    }
+    Ok(())
+}
+
+// Other platforms don't have Unix modes to normalize
+#[cfg(not(unix))]
+fn set_file_modes(_dist_dir: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+// `.md` in any case, with `.markdown` as an alias
+fn is_markdown(path: &Path) -> bool {
+    has_extension(path, &["md", "markdown"])
+}
+
+// Hand-written pages that skip markdown conversion but are otherwise content
+fn is_html(path: &Path) -> bool {
+    has_extension(path, &["html", "htm"])
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|ext| {
+        let ext = ext.to_string_lossy().to_lowercase();
+        extensions.contains(&ext.as_str())
+    })
+}
+
+// Templates whose file name starts with `_` (e.g. `_head.html` or
+// `partials/_footer.html`) are only meant to be included or extended
+fn is_partial(template_name: &str) -> bool {
+    Path::new(template_name)
+        .file_name()
+        .is_some_and(|name| name.to_string_lossy().starts_with('_'))
+}
+
+// Site-absolute URL of a file written under the dist directory
+fn page_url(site: &Site, output_path: &Path) -> String {
+    let relative_path = output_path
+        .strip_prefix(&site.dist_dir)
+        .unwrap_or(output_path);
+    format!("/{}", relative_path.to_string_lossy().replace('\\', "/"))
+}
+
+fn escape_xml(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,49 @@
+use crate::{content::Page, pages::text_direction, Site};
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+// Copies of the section posts' pages for each locale without a translation of
+// them, at the same path under the locale's directory. They keep the default
+// language's canonical URL, and as they aren't posts of their own no listing,
+// feed or sitemap has them.
+pub(crate) fn fallback_pages(site: &Site, pages: &[Page]) -> Result<Vec<Page>> {
+    let content_dir = site.content_dir();
+    let mut fallbacks = vec![];
+    for page in pages {
+        let Ok(relative) = page.source().strip_prefix(&content_dir) else {
+            continue;
+        };
+        let Ok(output) = page.path().strip_prefix(&site.dist_dir) else {
+            continue;
+        };
+        if site.config.section_for(relative).is_none() {
+            continue;
+        }
+        for locale in &site.config.locales {
+            if is_translated(&content_dir.join(locale), relative) {
+                continue;
+            }
+            let path = site.dist_dir.join(locale).join(output);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).context("Failed to create locale directories")?;
+            }
+            let mut fallback = page.with_path(path);
+            let context = fallback.context_mut();
+            context.insert("translation_fallback", &true);
+            context.insert("lang", locale);
+            context.insert("dir", text_direction(locale));
+            fallbacks.push(fallback);
+        }
+    }
+    Ok(fallbacks)
+}
+
+// A translation can be markdown or HTML, whichever the original is
+fn is_translated(locale_dir: &Path, relative: &Path) -> bool {
+    ["md", "markdown", "html"].iter().any(|extension| {
+        locale_dir
+            .join(relative)
+            .with_extension(extension)
+            .is_file()
+    })
+}
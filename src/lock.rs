@@ -0,0 +1,97 @@
+use crate::{config::LockedBuild, Site, SiteError};
+use anyhow::{Context, Result};
+use std::{
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+
+// Held while a build writes to dist, removed again when it's dropped
+pub(crate) struct BuildLock {
+    path: PathBuf,
+}
+
+impl Drop for BuildLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Next to dist rather than in it, so it's never deployed: `dist.lock`
+fn lock_path(dist_dir: &Path) -> PathBuf {
+    let name = dist_dir
+        .file_name()
+        .map_or("dist".into(), |name| name.to_string_lossy());
+    dist_dir.with_file_name(format!("{}.lock", name))
+}
+
+// Take the lock of the site's dist, holding the id of this process. When
+// another build has it, fail or wait for it as `on_locked_build` says. A lock
+// left by a process that's gone fails either way, unless `--force-unlock`.
+pub(crate) fn acquire(site: &Site) -> Result<BuildLock> {
+    let path = lock_path(&site.dist_dir);
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::create_dir_all(parent).context("Failed to create the build lock directory")?;
+    }
+    if site.config.force_unlock {
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != ErrorKind::NotFound => {
+                return Err(err).context("Failed to remove the build lock");
+            }
+            _ => {}
+        }
+    }
+
+    loop {
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                write!(file, "{}", process::id()).context("Failed to write the build lock")?;
+                return Ok(BuildLock { path });
+            }
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err).context("Failed to create the build lock"),
+        }
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            // Released in the meantime
+            Err(err) if err.kind() == ErrorKind::NotFound => continue,
+            Err(err) => return Err(err).context("Failed to read the build lock"),
+        };
+        // A lock that was just created may not have its id yet
+        let pid = contents.trim().parse::<u32>().ok();
+        let running = pid.is_none_or(is_running);
+        if !running || site.config.on_locked_build == LockedBuild::Fail {
+            return Err(SiteError::Locked { path, pid, running }.into());
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+}
+
+// Only Linux can tell through `/proc`, elsewhere the holder is assumed to be
+// still at it
+fn is_running(pid: u32) -> bool {
+    let proc = Path::new("/proc");
+    !proc.is_dir() || proc.join(pid.to_string()).exists()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::lock_path;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn puts_the_lock_next_to_dist() {
+        assert_eq!(lock_path(Path::new("dist")), PathBuf::from("dist.lock"));
+        assert_eq!(
+            lock_path(Path::new("/srv/site/public")),
+            PathBuf::from("/srv/site/public.lock")
+        );
+        assert_eq!(lock_path(Path::new("out/")), PathBuf::from("out.lock"));
+    }
+}
@@ -1,214 +1,565 @@
 use anyhow::{Context, Result};
-use pulldown_cmark::{html, Parser};
-use serde::Serialize;
-use std::{fs, path::Path};
-use tera::Tera;
-use walkdir::WalkDir;
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
+use md_static_site_generator::{
+    build,
+    check::{check, check_config, Severity},
+    migrate::migrate,
+    orphans::find_orphaned_assets,
+    serve::serve,
+    watch::watch,
+    BuildReport, Config, ExclusionReason, Site,
+};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+#[derive(Parser)]
+#[command(about = "Generate the static site from markdown content")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Directory with the content, templates and assets
+    #[arg(long, global = true, default_value = "src")]
+    source: PathBuf,
+
+    /// Directory the site is written to, `dist_dir` in site.toml or `dist`
+    /// by default
+    #[arg(long, global = true, env = "DIST_DIR")]
+    dist: Option<PathBuf>,
+
+    /// Only build the `[[sites]]` entry with this name, all of them by default
+    #[arg(long, global = true)]
+    site: Option<String>,
+
+    /// Base URL for absolute links, overrides `base_url` in site.toml
+    #[arg(long, global = true, env = "SITE_BASE_URL")]
+    base_url: Option<String>,
+
+    /// Build environment, e.g. `staging` for preview deploys
+    #[arg(long, global = true)]
+    env: Option<String>,
+
+    /// Apply the `[profile.<name>]` overrides from site.toml
+    #[arg(long, global = true)]
+    profile: Option<String>,
+
+    /// Include posts marked `draft: true`
+    #[arg(long, global = true)]
+    drafts: bool,
+
+    /// Also build posts with this `status` and the ones after it, e.g. `review`
+    #[arg(long, global = true)]
+    status: Option<String>,
+
+    /// Threads for parallel work, one per logical CPU by default. `1` builds
+    /// serially, e.g. for debugging.
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Treat warnings as errors
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Warn about unclosed tags and duplicate ids in rendered posts
+    #[arg(long, global = true)]
+    validate_html: bool,
+
+    /// Use a fixed build time, `SOURCE_DATE_EPOCH` or else the Unix epoch,
+    /// so the same sources always give the same output
+    #[arg(long, global = true)]
+    reproducible: bool,
+
+    #[arg(long, global = true, env = "SOURCE_DATE_EPOCH", hide = true)]
+    source_date_epoch: Option<i64>,
+
+    /// Only write pages of posts dated, updated or edited on or after this
+    /// ISO date, e.g. `2024-05-01`. Listings still include every post.
+    #[arg(long, global = true)]
+    since: Option<NaiveDate>,
+
+    /// Write `stats.json` with word counts and the most frequent words
+    #[arg(long, global = true)]
+    stats: bool,
+
+    /// Write `.manifest.json` with the files each source file produced
+    #[arg(long, global = true)]
+    build_manifest: bool,
+
+    /// List the pages added, removed and changed since the previous build
+    #[arg(long, global = true)]
+    diff_report: bool,
+
+    /// Write `posts.csv` with every post's metadata, word count and draft state
+    #[arg(long, global = true)]
+    csv: bool,
+
+    /// Finish the build, then exit with an error if there were any warnings
+    #[arg(long, global = true)]
+    fail_on_warning: bool,
+
+    /// Remove the lock a crashed build left next to the output before building
+    #[arg(long, global = true)]
+    force_unlock: bool,
+
+    /// Build only the `maintenance.html` placeholder page
+    #[arg(long, global = true)]
+    maintenance: bool,
+
+    /// List every content file that was left out of the build, and why
+    #[arg(long, short, global = true)]
+    verbose: bool,
+
+    /// How the build summary is printed, `json` prints each site's report as
+    /// one JSON line on stdout instead
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Human)]
+    output_format: OutputFormat,
+
+    /// Rebuild whenever the sources or site.toml change
+    #[arg(long)]
+    watch: bool,
+
+    /// Validate site.toml and the templates it names, then exit without building
+    #[arg(long)]
+    config_check: bool,
+
+    /// List assets that no generated page or stylesheet refers to
+    #[arg(long)]
+    report_orphans: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Human,
+    Json,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build the site, what runs when no command is given
+    Build {
+        /// Only render this content file, e.g. `src/content/blog/foo.md`
+        #[arg(long)]
+        only: Option<PathBuf>,
+
+        /// Rewrite the listings, feeds and indexes too when using `--only`
+        #[arg(long, requires = "only")]
+        with_listings: bool,
+    },
+
+    /// Validate the content without writing any output
+    Check,
+
+    /// Rewrite frontmatter to the current key names, ISO dates and a fixed
+    /// key order. Only shows the changes unless `--write` is given.
+    Migrate {
+        /// Rewrite the content files in place
+        #[arg(long)]
+        write: bool,
+    },
+
+    /// Build, serve `dist` locally and rebuild on changes
+    Serve {
+        /// Port to listen on
+        #[arg(long, default_value_t = 8000)]
+        port: u16,
+
+        /// Open the site in the default browser once the first build is done
+        #[arg(long)]
+        open: bool,
+    },
+}
 
 fn main() -> Result<()> {
-    let src_dir = "src/content";
-    let dist_dir = "dist";
-
-    // Create the dist directory
-    fs::create_dir_all(dist_dir).context("Failed to create dist directory")?;
-
-    // Initialize template engine
-    let tera = Tera::new("src/template/**/*.html").context("Failed to load templates")?;
-
-    // Prepare listings for blog and book
-    let mut blog_posts = vec![];
-    let mut book_posts = vec![];
-
-    // Process markdown files
-    for entry in WalkDir::new(src_dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.extension().map_or(false, |ext| ext == "md") {
-            let metadata = process_markdown(path, dist_dir, &tera)?;
-
-            // Categorize the post based on its directory
-            if path.starts_with("src/content/blog") {
-                blog_posts.push(metadata);
-            } else if path.starts_with("src/content/books") {
-                book_posts.push(metadata);
+    let cli = Cli::parse();
+    let targets = targets(&cli)?;
+
+    // With `--output-format json` stdout only gets the reports, everything
+    // else goes to stderr
+    let json = cli.output_format == OutputFormat::Json;
+    // Config and templates are reloaded on every build so watch mode picks up their changes
+    // Returns the content warnings and how many orphaned assets were listed
+    let rebuild = || -> Result<(usize, usize)> {
+        let (mut warnings, mut orphans) = (0, 0);
+        for target in &targets {
+            // CI logs show where the output went
+            if !json {
+                match &target.name {
+                    Some(name) => {
+                        println!("Building site `{}` into `{}`", name, target.dist.display())
+                    }
+                    None => println!("Building into `{}`", target.dist.display()),
+                }
+            }
+            let site = target.site(&cli)?;
+            let report = build(&site)?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::to_string(&report).context("Failed to serialize build report")?
+                );
+            } else {
+                print_report(&report, cli.verbose);
+            }
+            warnings += report.warnings.len();
+            if cli.report_orphans {
+                orphans += print_orphans(&site, json)?;
             }
         }
-    }
+        Ok((warnings, orphans))
+    };
 
-    // Generate the homepage, blog listing, and book listing pages
-    generate_homepage(dist_dir, &tera)?;
-    generate_listing("blog", &blog_posts, dist_dir, &tera)?;
-    generate_listing("books", &book_posts, dist_dir, &tera)?;
+    if let Some(Command::Check) = cli.command {
+        return targets
+            .iter()
+            .try_for_each(|target| check_content(&cli, target));
+    }
+    if let Some(Command::Migrate { write }) = cli.command {
+        return targets
+            .iter()
+            .try_for_each(|target| migrate_content(&cli, target, write));
+    }
+    if cli.config_check {
+        return targets
+            .iter()
+            .try_for_each(|target| check_site_config(&cli, target));
+    }
 
-    // Copy assets
-    copy_assets("src/assets", "dist/assets")?;
+    let mut paths: Vec<&Path> = vec![Path::new("site.toml")];
+    paths.extend(targets.iter().map(|target| target.source.as_path()));
+    if let Some(Command::Serve { port, open }) = cli.command {
+        let [target] = targets.as_slice() else {
+            anyhow::bail!("Pick the site to serve with `--site`");
+        };
+        if let Err(err) = rebuild() {
+            eprintln!("Build failed: {:#}", err);
+        }
+        let base_path = load_config(&cli, target.name.as_deref())?.base_path;
+        let url = serve(&target.dist, &format!("127.0.0.1:{}", port), &base_path)?;
+        println!("Serving `{}` at {}", target.dist.display(), url);
+
+        // A missing browser, e.g. on a headless machine, shouldn't stop the server
+        if open {
+            if let Err(err) = open::that(&url) {
+                eprintln!("Failed to open browser: {}", err);
+            }
+        }
+        return watch(&paths, || rebuild().map(|_| ()));
+    }
 
-    // Copy CNAME file if it exists
-    if Path::new("src/CNAME").exists() {
-        fs::copy("src/CNAME", "dist/CNAME").context("Failed to copy CNAME file")?;
+    if cli.watch {
+        if let Err(err) = rebuild() {
+            eprintln!("Build failed: {:#}", err);
+        }
+        return watch(&paths, || rebuild().map(|_| ()));
     }
 
-    println!("Static site generated successfully in `{}`", dist_dir);
+    let (content_warnings, orphans) = rebuild()?;
+
+    // The output is complete either way, CI just shouldn't pass
+    let warnings = content_warnings + orphans;
+    if cli.fail_on_warning && warnings > 0 {
+        anyhow::bail!(
+            "Build finished with {} warnings ({} in content, {} orphaned assets)",
+            warnings,
+            content_warnings,
+            orphans
+        );
+    }
+    if !json {
+        for target in &targets {
+            println!(
+                "Static site generated successfully in `{}`",
+                target.dist.display()
+            );
+        }
+    }
     Ok(())
 }
 
-#[derive(Serialize)]
-struct PostMetadata {
-    title: String,
-    slug: String,
-    image: String,
-    description: String,
-    date: String,
+// A site to build, one of the `[[sites]]` in site.toml when it has any
+struct Target {
+    name: Option<String>,
+    source: PathBuf,
+    dist: PathBuf,
 }
 
-fn process_markdown(src_path: &Path, dist_dir: &str, tera: &Tera) -> Result<PostMetadata> {
-    // Read markdown file
-    let content = fs::read_to_string(src_path).context("Failed to read markdown file")?;
-
-    // Extract metadata and content, skipping frontmatter
-    let (frontmatter, markdown_content) = split_frontmatter(&content);
-
-    // Extract metadata from frontmatter
-    let title = extract_metadata(&frontmatter, "title").unwrap_or_else(|| "Untitled".to_string());
-    let slug = src_path.file_stem().unwrap().to_str().unwrap().to_string();
-    let image = extract_metadata(&frontmatter, "image")
-        .unwrap_or_else(|| "/assets/images/rubber-duck.jpg".to_string());
-    let description = extract_metadata(&frontmatter, "description")
-        .unwrap_or_else(|| "No description".to_string());
-    let date = extract_metadata(&frontmatter, "date").unwrap_or_else(|| "No date".to_string());
-
-    // Parse markdown to HTML (using only the content part)
-    let parser = Parser::new(&markdown_content);
-    let mut html_output = String::new();
-    html::push_html(&mut html_output, parser);
-
-    // Determine output path
-    let relative_path = src_path.strip_prefix("src/content")?;
-    let output_path = Path::new(dist_dir)
-        .join(relative_path)
-        .with_extension("html");
-
-    // Create parent directories if necessary
-    if let Some(parent) = output_path.parent() {
-        fs::create_dir_all(parent).context("Failed to create output directories")?;
-    }
-
-    // Determine template based on content type
-    let template_name = if src_path.starts_with("src/content/blog") {
-        "blog_detail.html"
-    } else if src_path.starts_with("src/content/books") {
-        "book_detail.html"
-    } else {
-        "base.html" // fallback template
-    };
-
-    // Render HTML using Tera template
-    let mut context = tera::Context::new();
-    context.insert("content", &html_output);
-    context.insert("title", &title);
-    context.insert("image", &image);
-    context.insert("description", &description);
-    context.insert("date", &date);
-    let rendered = tera
-        .render(template_name, &context)
-        .context("Failed to render template")?;
-
-    // Write to the output HTML file
-    fs::write(output_path, rendered).context("Failed to write HTML file")?;
-    Ok(PostMetadata {
-        title,
-        slug,
-        image,
-        description,
-        date,
-    })
+impl Target {
+    fn site(&self, cli: &Cli) -> Result<Site> {
+        Site::new(
+            load_config(cli, self.name.as_deref())?,
+            &self.source,
+            &self.dist,
+        )
+    }
 }
 
-fn extract_metadata(content: &str, key: &str) -> Option<String> {
-    let key = format!("{}:", key);
-    content
-        .lines()
-        .find(|line| line.starts_with(&key))
-        .map(|line| line[key.len()..].trim().to_string())
+// Every configured site, or only the one named by `--site`. Without `[[sites]]`
+// the site comes from `--source` and `--dist`, which also redirects the output
+// of a single site picked from `[[sites]]`.
+fn targets(cli: &Cli) -> Result<Vec<Target>> {
+    let config = Config::load("site.toml", cli.profile.as_deref())?;
+    if config.sites.is_empty() {
+        if let Some(name) = &cli.site {
+            anyhow::bail!("Unknown site `{}`, site.toml has no [[sites]]", name);
+        }
+        let dist = cli
+            .dist
+            .clone()
+            .or(config.dist_dir)
+            .unwrap_or_else(|| PathBuf::from("dist"));
+        return Ok(vec![Target {
+            name: None,
+            source: cli.source.clone(),
+            dist,
+        }]);
+    }
+
+    let mut targets: Vec<Target> = config
+        .sites
+        .into_iter()
+        .filter(|entry| cli.site.as_ref().is_none_or(|name| *name == entry.name))
+        .map(|entry| Target {
+            name: Some(entry.name),
+            source: entry.source,
+            dist: entry.dist,
+        })
+        .collect();
+    if targets.is_empty() {
+        anyhow::bail!(
+            "Unknown site `{}` in site.toml",
+            cli.site.as_deref().unwrap_or_default()
+        );
+    }
+    if let Some(dist) = &cli.dist {
+        let [target] = targets.as_mut_slice() else {
+            anyhow::bail!(
+                "Pick the site to write to `{}` with `--site`",
+                dist.display()
+            );
+        };
+        target.dist = dist.clone();
+    }
+    Ok(targets)
 }
 
-fn generate_homepage(dist_dir: &str, tera: &Tera) -> Result<()> {
-    let mut context = tera::Context::new();
-    context.insert("title", "Homepage");
+fn print_report(report: &BuildReport, verbose: bool) {
+    for warning in &report.warnings {
+        eprintln!("Warning: {}", warning);
+    }
 
-    let rendered = tera
-        .render("homepage.html", &context)
-        .context("Failed to render homepage template")?;
+    let published: usize = report.published.values().sum();
+    let sections: Vec<String> = report
+        .published
+        .iter()
+        .map(|(section, count)| match section {
+            Some(section) => format!("{} in {}", count, section),
+            None => format!("{} standalone", count),
+        })
+        .collect();
+    println!("Published {} pages: {}", published, sections.join(", "));
+    if !report.excluded.is_empty() {
+        let mut reasons: BTreeMap<ExclusionReason, usize> = BTreeMap::new();
+        for exclusion in &report.excluded {
+            *reasons.entry(exclusion.reason).or_default() += 1;
+        }
+        let reasons: Vec<String> = reasons
+            .iter()
+            .map(|(reason, count)| format!("{} {}", count, reason))
+            .collect();
+        println!("Excluded {}", reasons.join(", "));
+        if verbose {
+            for exclusion in &report.excluded {
+                println!("  {}: {}", exclusion.path.display(), exclusion.reason);
+            }
+        }
+    }
+    println!(
+        "Copied {} assets, skipped {} unchanged",
+        report.assets_copied, report.assets_skipped
+    );
+    if report.images_optimized > 0 {
+        println!(
+            "Optimized {} images, saved {} KB",
+            report.images_optimized,
+            report.image_bytes_saved / 1024
+        );
+    }
+    if report.images_inlined > 0 {
+        println!("Inlined {} small images", report.images_inlined);
+    }
+    if report.image_variants > 0 {
+        println!(
+            "Generated {} responsive image variants",
+            report.image_variants
+        );
+    }
+    for compressed in &report.compressed {
+        println!(
+            "Compressed {} files with {}, {} KB to {} KB",
+            compressed.files,
+            compressed.format,
+            compressed.bytes / 1024,
+            compressed.compressed_bytes / 1024
+        );
+    }
+    if let Some(diff) = &report.diff {
+        println!(
+            "Since the previous build: {} pages added, {} removed, {} changed",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.changed.len()
+        );
+        for (mark, pages) in [
+            ("+", &diff.added),
+            ("-", &diff.removed),
+            ("~", &diff.changed),
+        ] {
+            for page in pages {
+                println!("  {} {}", mark, page.display());
+            }
+        }
+    }
+    if !report.stale.is_empty() {
+        println!("{} posts are due for a review", report.stale.len());
+        if verbose {
+            for post in &report.stale {
+                println!(
+                    "  {}: last touched {}, {} days ago",
+                    post.url, post.last_touched, post.age_days
+                );
+            }
+        }
+    }
+}
 
-    let output_path = Path::new(dist_dir).join("index.html");
-    fs::write(output_path, rendered).context("Failed to write homepage")?;
+fn check_content(cli: &Cli, target: &Target) -> Result<()> {
+    let site = target.site(cli)?;
+    let issues = check(&site)?;
+    for issue in &issues {
+        println!("{}", issue);
+    }
+
+    // Under `--strict` or `--fail-on-warning` warnings fail the check too
+    let strict = site.config.strict || cli.fail_on_warning;
+    let failures = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error || strict)
+        .count();
+    if failures > 0 {
+        anyhow::bail!("Found {} problems in the content", failures);
+    }
+    println!("Checked content, {} warnings", issues.len());
     Ok(())
 }
 
-fn generate_listing(
-    category: &str,
-    posts: &[PostMetadata],
-    dist_dir: &str,
-    tera: &Tera,
-) -> Result<()> {
-    let mut context = tera::Context::new();
-    context.insert("posts", posts);
-    context.insert("title", &format!("{} Listing", category));
-
-    let template_name = match category {
-        "blog" => "blog_list.html",
-        "books" => "book_list.html",
-        _ => return Err(anyhow::anyhow!("Unknown category: {}", category)),
-    };
+fn migrate_content(cli: &Cli, target: &Target, write: bool) -> Result<()> {
+    let site = target.site(cli)?;
+    let migrations = migrate(&site, write)?;
+    if write {
+        for migration in &migrations {
+            println!("Rewrote {}", migration.path.display());
+        }
+        println!("Migrated {} content files", migrations.len());
+    } else {
+        for migration in &migrations {
+            println!("{}", migration.diff());
+        }
+        println!(
+            "{} content files to migrate, run again with `--write` to rewrite them",
+            migrations.len()
+        );
+    }
+    Ok(())
+}
 
-    let rendered = tera
-        .render(template_name, &context)
-        .context("Failed to render listing template")?;
+fn check_site_config(cli: &Cli, target: &Target) -> Result<()> {
+    // Loading the site parses the config and compiles every template
+    let site = target.site(cli)?;
+    let issues = check_config(&site, Path::new("site.toml"));
+    for issue in &issues {
+        println!("{}", issue);
+    }
 
-    let output_path = Path::new(dist_dir).join(format!("{}.html", category));
-    fs::write(output_path, rendered).context("Failed to write listing")?;
+    let strict = site.config.strict || cli.fail_on_warning;
+    let failures = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error || strict)
+        .count();
+    if failures > 0 {
+        anyhow::bail!("Found {} problems in the configuration", failures);
+    }
+    println!("Checked configuration, {} warnings", issues.len());
     Ok(())
 }
 
-fn copy_assets(src: &str, dest: &str) -> Result<()> {
-    for entry in WalkDir::new(src).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path();
-        if path.is_file() {
-            let relative_path = path.strip_prefix(src)?;
-            let dest_path = Path::new(dest).join(relative_path);
-            if let Some(parent) = dest_path.parent() {
-                fs::create_dir_all(parent).context("Failed to create asset directories")?;
-            }
-            fs::copy(path, dest_path).context("Failed to copy asset")?;
+fn print_orphans(site: &Site, to_stderr: bool) -> Result<usize> {
+    let print = |line: String| {
+        if to_stderr {
+            eprintln!("{}", line);
+        } else {
+            println!("{}", line);
         }
+    };
+    let orphans = find_orphaned_assets(site)?;
+    if orphans.is_empty() {
+        print("No orphaned assets found".to_string());
+        return Ok(0);
     }
-    Ok(())
+
+    print("Assets not referenced by any page:".to_string());
+    for orphan in &orphans {
+        print(format!("  {}", orphan.display()));
+    }
+    Ok(orphans.len())
 }
 
-fn split_frontmatter(content: &str) -> (String, String) {
-    let mut lines = content.lines();
-    let mut frontmatter = String::new();
-    let mut markdown_content = String::new();
-
-    // Check if the file starts with frontmatter delimiter
-    if let Some("---") = lines.next() {
-        // Collect frontmatter until the closing delimiter
-        for line in lines.by_ref() {
-            if line == "---" {
-                break;
-            }
-            frontmatter.push_str(line);
-            frontmatter.push('\n');
+// Load site configuration, command line flags take precedence over the
+// profile and the `[[sites]]` entry of the site being built
+fn load_config(cli: &Cli, site: Option<&str>) -> Result<Config> {
+    let mut config = Config::load("site.toml", cli.profile.as_deref())?;
+    let entry = site.and_then(|name| config.sites.iter().find(|entry| entry.name == name));
+    if let Some(entry) = entry {
+        if let Some(base_url) = &entry.base_url {
+            config.base_url = base_url.clone();
         }
-
-        // The rest is markdown content
-        for line in lines {
-            markdown_content.push_str(line);
-            markdown_content.push('\n');
+        if let Some(template_dir) = &entry.template_dir {
+            config.template_dir = Some(template_dir.clone());
         }
-    } else {
-        // No frontmatter found, treat everything as content
-        markdown_content = content.to_string();
     }
-
-    (frontmatter, markdown_content)
+    if let Some(base_url) = &cli.base_url {
+        config.base_url = base_url.clone();
+    }
+    if let Some(env) = &cli.env {
+        config.env = env.clone();
+    }
+    config.drafts |= cli.drafts;
+    if let Some(status) = &cli.status {
+        config.status = Some(status.clone());
+    }
+    config.strict |= cli.strict;
+    if let Some(threads) = cli.threads {
+        config.threads = Some(threads);
+    }
+    config.validate_html |= cli.validate_html;
+    config.maintenance.enabled |= cli.maintenance;
+    config.force_unlock = cli.force_unlock;
+    config.stats |= cli.stats;
+    config.build_manifest |= cli.build_manifest;
+    config.diff_report |= cli.diff_report;
+    config.csv |= cli.csv;
+    config.reproducible |= cli.reproducible;
+    config.source_date_epoch = cli.source_date_epoch;
+    config.since = cli.since;
+    if let Some(Command::Build {
+        only,
+        with_listings,
+    }) = &cli.command
+    {
+        config.only = only.clone();
+        config.with_listings = *with_listings;
+    }
+    Ok(config)
 }
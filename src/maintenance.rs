@@ -0,0 +1,39 @@
+use crate::{pages::base_context, Site};
+use anyhow::{Context, Result};
+use std::fs;
+
+const MAINTENANCE_TEMPLATE: &str = "maintenance.html";
+
+// Write the `maintenance.html` placeholder as the homepage and the 404 page, so
+// every URL ends up on it, and copy only the assets it needs
+pub(crate) fn generate_maintenance(site: &Site) -> Result<()> {
+    if !site.has_template(MAINTENANCE_TEMPLATE) {
+        anyhow::bail!(
+            "Maintenance mode needs a `{}` template",
+            MAINTENANCE_TEMPLATE
+        );
+    }
+
+    let mut context = base_context(site, "/index.html", None);
+    context.insert("title", "Maintenance");
+    // The placeholder shouldn't replace the real pages in search results
+    context.insert("noindex", &true);
+    let rendered = site
+        .render(MAINTENANCE_TEMPLATE, &context)
+        .context("Failed to render maintenance template")?;
+    for page in ["index.html", "404.html"] {
+        fs::write(site.dist_dir.join(page), &rendered)
+            .with_context(|| format!("Failed to write {}", page))?;
+    }
+
+    let assets_dir = site.source_dir.join("assets");
+    for asset in &site.config.maintenance.assets {
+        let src = assets_dir.join(asset);
+        let dest = site.dist_dir.join("assets").join(asset);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).context("Failed to create asset directories")?;
+        }
+        fs::copy(&src, &dest).with_context(|| format!("Failed to copy asset {}", src.display()))?;
+    }
+    Ok(())
+}
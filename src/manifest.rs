@@ -0,0 +1,112 @@
+use crate::{
+    content::{is_draft, strip_tags},
+    PostMetadata, Site,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+// Metadata-only view of a post, the body is left to the pages themselves
+#[derive(Serialize)]
+struct ManifestEntry<'a> {
+    title: &'a str,
+    slug: &'a str,
+    url: &'a str,
+    date: &'a str,
+    tags: &'a [String],
+    keywords: &'a [String],
+    description: &'a str,
+    category: Option<&'a str>,
+    reading_time: usize,
+}
+
+pub(crate) fn write_content_manifest(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let entries: Vec<ManifestEntry> = posts
+        .iter()
+        .map(|post| ManifestEntry {
+            title: &post.title,
+            slug: &post.slug,
+            url: &post.url,
+            date: &post.date,
+            tags: &post.tags,
+            keywords: &post.keywords,
+            description: &post.description,
+            category: post.category.as_deref(),
+            reading_time: post.reading_time,
+        })
+        .collect();
+
+    let json =
+        serde_json::to_string_pretty(&entries).context("Failed to serialize content index")?;
+    fs::write(site.dist_dir.join("index.json"), json).context("Failed to write content index")?;
+    Ok(())
+}
+
+// Write `posts.csv`, a row per post for looking over the content in a
+// spreadsheet
+pub(crate) fn write_posts_csv(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let mut csv =
+        String::from("category,slug,title,date,updated,tags,word_count,reading_time,draft\n");
+    for post in posts {
+        let word_count = strip_tags(&post.content).split_whitespace().count();
+        let row = [
+            post.category.as_deref().unwrap_or_default(),
+            &post.slug,
+            &post.title,
+            &post.date,
+            post.updated.as_deref().unwrap_or_default(),
+            &post.tags.join(", "),
+            &word_count.to_string(),
+            &post.reading_time.to_string(),
+            &is_draft(&post.frontmatter).to_string(),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    fs::write(site.dist_dir.join("posts.csv"), csv).context("Failed to write posts.csv")?;
+    Ok(())
+}
+
+// Fields with commas, quotes or line breaks are quoted, with quotes doubled
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// Write `.manifest.json`, mapping each source file to what the build wrote
+// for it under dist, for tooling that needs to know which outputs to remove
+// with a source. Listings, feeds and other pages drawn from many sources
+// aren't in it.
+pub(crate) fn write_build_manifest(
+    site: &Site,
+    outputs: &BTreeMap<PathBuf, BTreeSet<PathBuf>>,
+) -> Result<()> {
+    let relative = |path: &Path| {
+        path.strip_prefix(&site.dist_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    };
+    let manifest: BTreeMap<String, Vec<String>> = outputs
+        .iter()
+        .map(|(source, outputs)| {
+            (
+                source.to_string_lossy().replace('\\', "/"),
+                outputs.iter().map(|output| relative(output)).collect(),
+            )
+        })
+        .collect();
+    let json =
+        serde_json::to_string_pretty(&manifest).context("Failed to serialize build manifest")?;
+    fs::write(site.dist_dir.join(".manifest.json"), json)
+        .context("Failed to write .manifest.json")?;
+    Ok(())
+}
@@ -0,0 +1,1113 @@
+use crate::{
+    assets::{variant_name, variant_widths},
+    config::MarkdownConfig,
+    content::strip_tags,
+    escape_xml,
+    hashes::fnv1a,
+    urls::{self, slugify},
+    Site,
+};
+use anyhow::{Context, Result};
+use pulldown_cmark::{html, CowStr, Event, HeadingLevel, LinkType, Options, Parser, Tag};
+use regex::{Captures, Regex};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::Path,
+    sync::LazyLock,
+};
+
+static PARAGRAPH: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)<p((?: [^>]*)?)>(.*?)</p>").unwrap());
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?:#([\w-]+)|\.([\w-]+)|([\w-]+)=(?:"([^"]*)"|([^\s"]+)))"#).unwrap()
+});
+static BARE_URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"\bhttps?://[^\s<>"]+"#).unwrap());
+
+pub(crate) struct RenderedMarkdown {
+    pub html: String,
+    pub has_code: bool,
+    pub toc: Vec<TocEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct TocEntry {
+    pub level: u8,
+    pub id: String,
+    // Page the heading is on when that's not the current one, empty otherwise
+    pub url: String,
+    pub title: String,
+    // `1.2` style number of the heading, with `number_headings`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub number: Option<String>,
+    pub children: Vec<TocEntry>,
+}
+
+// `toc` controls whether headings are anchored and collected at all,
+// `heading_shift` moves every heading that many levels down and
+// `number_headings` numbers the anchored ones
+pub(crate) fn render_markdown(
+    site: &Site,
+    markdown: &str,
+    toc: bool,
+    heading_shift: u8,
+    number_headings: bool,
+    page_dir: &Path,
+) -> Result<RenderedMarkdown> {
+    let config = &site.config.markdown;
+    let (markdown, abbreviations) = if config.abbreviations {
+        extract_abbreviations(markdown)
+    } else {
+        (markdown.to_string(), vec![])
+    };
+
+    let mut options = Options::empty();
+    if config.attributes {
+        options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
+    }
+    let events: Vec<Event> = Parser::new_ext(&markdown, options)
+        .map(|event| match event {
+            Event::Start(Tag::Heading(level, id, classes)) if heading_shift > 0 => Event::Start(
+                Tag::Heading(shift_heading(level, heading_shift), id, classes),
+            ),
+            Event::End(Tag::Heading(level, id, classes)) if heading_shift > 0 => Event::End(
+                Tag::Heading(shift_heading(level, heading_shift), id, classes),
+            ),
+            _ => event,
+        })
+        .collect();
+    let events = if config.admonitions {
+        admonitions(config, events)
+    } else {
+        events
+    };
+    let events = if config.autolink {
+        autolink(events)
+    } else {
+        events
+    };
+    let events = if site.config.assets.responsive_images {
+        responsive_images(site, events)
+    } else {
+        events
+    };
+    let events: Vec<Event> = events
+        .into_iter()
+        .map(|event| match event {
+            Event::Start(Tag::Link(link_type, url, title)) => {
+                let url = CowStr::from(site.config.site_url(&colocated_url(site, page_dir, &url)));
+                Event::Start(Tag::Link(link_type, url, title))
+            }
+            Event::Start(Tag::Image(link_type, url, title)) => {
+                let url = colocated_url(site, page_dir, &url);
+                let url = site
+                    .images
+                    .data_uri(&url)
+                    .unwrap_or_else(|| site.config.image_url(&url));
+                Event::Start(Tag::Image(link_type, CowStr::from(url), title))
+            }
+            _ => event,
+        })
+        .collect();
+
+    let (events, has_code) = wrap_code_blocks(site, events)?;
+    let (events, toc) = if toc {
+        anchor_headings(config, events, number_headings)
+    } else {
+        (events, vec![])
+    };
+    let events = if config.definition_lists {
+        definition_lists(events)
+    } else {
+        events
+    };
+    let events = if config.attributes {
+        attribute_lists(events)
+    } else {
+        events
+    };
+    let events = expand_abbreviations(events, &abbreviations);
+
+    let mut html_output = String::new();
+    html::push_html(&mut html_output, events.into_iter());
+    if config.paragraph_ids {
+        html_output = paragraph_ids(&html_output);
+    }
+    Ok(RenderedMarkdown {
+        html: html_output,
+        has_code,
+        toc,
+    })
+}
+
+// Add `class` to the paragraph a post opens with. Posts that start with
+// something else, like a heading or an image on its own, have no lead.
+pub(crate) fn mark_lead_paragraph(html: &str, class: &str) -> String {
+    let Some(rest) = html.strip_prefix("<p") else {
+        return html.to_string();
+    };
+    let Some(tag_end) = rest.find('>') else {
+        return html.to_string();
+    };
+    // `<pre>` and `<picture>` start the same way
+    let (attributes, body) = (&rest[..tag_end], &rest[tag_end + 1..]);
+    if !(attributes.is_empty() || attributes.starts_with(' ')) {
+        return html.to_string();
+    }
+    let first = body.trim_start();
+    if first.starts_with("<img") || first.starts_with("<picture") {
+        return html.to_string();
+    }
+    let attributes = match attributes.split_once(" class=\"") {
+        Some((before, classes)) => format!("{} class=\"{} {}", before, class, classes),
+        None => format!(" class=\"{}\"{}", class, attributes),
+    };
+    format!("<p{}>{}", attributes, body)
+}
+
+// Give every paragraph without an id one from a hash of its text, like
+// `p-3f2a9c01`, for links to it that keep working across builds and edits
+// elsewhere in the post. Paragraphs with the same text are told apart by
+// their order.
+fn paragraph_ids(html: &str) -> String {
+    let mut used_ids = HashSet::new();
+    PARAGRAPH
+        .replace_all(html, |captures: &Captures| {
+            let (attributes, body) = (&captures[1], &captures[2]);
+            if attributes.contains(" id=") {
+                return captures[0].to_string();
+            }
+            let text = strip_tags(body);
+            let hash = fnv1a(
+                text.split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .as_bytes(),
+            );
+            let id = unique_id(&mut used_ids, &format!("p-{:08x}", hash >> 32));
+            format!("<p id=\"{}\"{}>{}</p>", id, attributes, body)
+        })
+        .into_owned()
+}
+
+// `images/cat.png` in `blog/trip/index.md` becomes `/blog/trip/images/cat.png`,
+// so it still works at `/blog/trip` without the trailing slash. Only links into
+// an existing file of a co-located directory are touched.
+fn colocated_url(site: &Site, page_dir: &Path, link: &str) -> String {
+    let name = &site.config.assets.colocated_dir;
+    let relative = link.strip_prefix("./").unwrap_or(link);
+    let path = relative.split(['?', '#']).next().unwrap_or_default();
+    let in_colocated_dir = path
+        .split_once('/')
+        .is_some_and(|(dir, file)| dir == name && !file.is_empty());
+    if name.is_empty()
+        || !in_colocated_dir
+        || urls::is_external(link)
+        || !site.content_dir().join(page_dir).join(path).is_file()
+    {
+        return link.to_string();
+    }
+    let page_dir = page_dir.to_string_lossy().replace('\\', "/");
+    if page_dir.is_empty() {
+        format!("/{}", relative)
+    } else {
+        format!("/{}/{}", page_dir, relative)
+    }
+}
+
+// Give local images with resized variants a `srcset`. The `<img>` is written
+// out directly since pulldown-cmark has no way to add attributes.
+fn responsive_images<'a>(site: &Site, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut image: Option<(CowStr, CowStr, Vec<u32>, Vec<Event>)> = None;
+    for event in events {
+        match event {
+            Event::Start(Tag::Image(link_type, url, title)) if image.is_none() => {
+                let widths = local_asset(&url)
+                    .map(|path| variant_widths(&site.source_dir.join(path), &site.config.assets))
+                    .unwrap_or_default();
+                if widths.is_empty() {
+                    output.push(Event::Start(Tag::Image(link_type, url, title)));
+                } else {
+                    image = Some((url, title, widths, vec![]));
+                }
+            }
+            Event::End(Tag::Image(..)) if image.is_some() => {
+                let (url, title, widths, inner) = image.take().unwrap();
+                output.push(Event::Html(CowStr::from(srcset_img(
+                    site,
+                    &url,
+                    &title,
+                    &plain_text(&inner),
+                    &widths,
+                ))));
+            }
+            event => match &mut image {
+                Some((.., inner)) => inner.push(event),
+                None => output.push(event),
+            },
+        }
+    }
+    output
+}
+
+// Path under the source directory of an `/assets/...` URL
+pub(crate) fn local_asset(url: &str) -> Option<&str> {
+    if urls::is_external(url) {
+        return None;
+    }
+    let path = url.split(['?', '#']).next()?;
+    path.strip_prefix('/')
+        .filter(|path| path.starts_with("assets/"))
+}
+
+fn srcset_img(site: &Site, url: &str, title: &str, alt: &str, widths: &[u32]) -> String {
+    let src = site.config.image_url(url);
+    let srcset: Vec<String> = widths
+        .iter()
+        .map(|width| format!("{} {}w", variant_name(&src, *width), width))
+        .collect();
+    let title = if title.is_empty() {
+        String::new()
+    } else {
+        format!(" title=\"{}\"", escape_xml(title))
+    };
+    format!(
+        "<img src=\"{}\" alt=\"{}\"{} srcset=\"{}\" sizes=\"{}\" />",
+        escape_xml(&src),
+        escape_xml(alt),
+        title,
+        escape_xml(&srcset.join(", ")),
+        escape_xml(&site.config.assets.responsive_sizes)
+    )
+}
+
+fn wrap_code_blocks<'a>(site: &Site, events: Vec<Event<'a>>) -> Result<(Vec<Event<'a>>, bool)> {
+    // The copy button markup comes from a partial so it can be restyled freely
+    let copy_button = if site.config.markdown.code_copy {
+        let button = site
+            .render("_code_copy.html", &tera::Context::new())
+            .context("Failed to render code copy button")?;
+        Some(button)
+    } else {
+        None
+    };
+
+    let mut has_code = false;
+    let mut wrapped = Vec::with_capacity(events.len());
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(_)) => {
+                has_code = true;
+                if let Some(button) = &copy_button {
+                    let wrapper = format!("<div class=\"code-block\">{}", button);
+                    wrapped.push(Event::Html(CowStr::from(wrapper)));
+                }
+                wrapped.push(event);
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                wrapped.push(event);
+                if copy_button.is_some() {
+                    wrapped.push(Event::Html(CowStr::from("</div>\n")));
+                }
+            }
+            _ => wrapped.push(event),
+        }
+    }
+    Ok((wrapped, has_code))
+}
+
+// A heading being collected: its level, the id and classes of its attribute
+// list and what's inside it
+type PendingHeading<'a> = (HeadingLevel, Option<&'a str>, Vec<&'a str>, Vec<Event<'a>>);
+
+// Give headings within the configured TOC levels an id and collect them into
+// the table of contents. Other headings are rendered untouched.
+fn anchor_headings<'a>(
+    config: &MarkdownConfig,
+    events: Vec<Event<'a>>,
+    numbered: bool,
+) -> (Vec<Event<'a>>, Vec<TocEntry>) {
+    let levels = config.toc_min_level..=config.toc_max_level;
+    let mut used_ids = HashSet::new();
+    let mut counters: Vec<(HeadingLevel, usize)> = vec![];
+    let mut toc = vec![];
+    let mut anchored = Vec::with_capacity(events.len());
+    let mut heading: Option<PendingHeading> = None;
+
+    for event in events {
+        if let Some((level, custom_id, classes, inner)) = &mut heading {
+            if !matches!(event, Event::End(Tag::Heading(..))) {
+                inner.push(event);
+                continue;
+            }
+
+            let (level, custom_id, classes, inner) = (
+                *level,
+                *custom_id,
+                std::mem::take(classes),
+                std::mem::take(inner),
+            );
+            heading = None;
+            let title = plain_text(&inner);
+            let number = numbered.then(|| heading_number(&mut counters, level));
+            // An id from an attribute list is kept as written
+            let id = match custom_id {
+                Some(id) => {
+                    used_ids.insert(id.to_string());
+                    id.to_string()
+                }
+                None => {
+                    let slug = match &number {
+                        Some(number) => slugify(&format!("{} {}", number.replace('.', "-"), title)),
+                        None => slugify(&title),
+                    };
+                    unique_id(&mut used_ids, &slug)
+                }
+            };
+            let class = if classes.is_empty() {
+                String::new()
+            } else {
+                format!(" class=\"{}\"", escape_xml(&classes.join(" ")))
+            };
+
+            anchored.push(Event::Html(CowStr::from(format!(
+                "<{} id=\"{}\"{}>",
+                level,
+                escape_xml(&id),
+                class
+            ))));
+            if let Some(number) = &number {
+                anchored.push(Event::Html(CowStr::from(format!(
+                    "<span class=\"heading-number\">{}</span> ",
+                    number
+                ))));
+            }
+            anchored.extend(inner);
+            anchored.push(Event::Html(CowStr::from(format!("</{}>\n", level))));
+
+            insert_toc_entry(
+                &mut toc,
+                TocEntry {
+                    level: level as u8,
+                    id,
+                    url: String::new(),
+                    title,
+                    number,
+                    children: vec![],
+                },
+            );
+            continue;
+        }
+
+        match event {
+            Event::Start(Tag::Heading(level, id, classes)) if levels.contains(&(level as u8)) => {
+                heading = Some((level, id, classes, vec![]));
+            }
+            _ => anchored.push(event),
+        }
+    }
+    (anchored, toc)
+}
+
+// The next number at a heading's level, `1.2` for the second heading under
+// the first higher one. Skipped levels don't add a `0`: an `<h4>` right under
+// an `<h2>` is `1.1`, and an `<h3>` after it goes on as `1.2`.
+fn heading_number(counters: &mut Vec<(HeadingLevel, usize)>, level: HeadingLevel) -> String {
+    let mut deeper = 0;
+    while counters.last().is_some_and(|(last, _)| *last > level) {
+        deeper = counters.pop().map_or(0, |(_, count)| count);
+    }
+    match counters.last_mut() {
+        Some((last, count)) if *last == level => *count += 1,
+        _ => counters.push((level, deeper + 1)),
+    }
+    counters
+        .iter()
+        .map(|(_, count)| count.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// Headings pushed past `<h6>` stay at `<h6>`
+fn shift_heading(level: HeadingLevel, shift: u8) -> HeadingLevel {
+    let level = (level as usize + shift as usize).min(6);
+    HeadingLevel::try_from(level).unwrap_or(HeadingLevel::H6)
+}
+
+fn plain_text(events: &[Event]) -> String {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            Event::Text(text) | Event::Code(text) => Some(text.as_ref()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn unique_id(used_ids: &mut HashSet<String>, slug: &str) -> String {
+    let slug = if slug.is_empty() { "section" } else { slug };
+    let mut id = slug.to_string();
+    let mut counter = 1;
+    while !used_ids.insert(id.clone()) {
+        id = format!("{}-{}", slug, counter);
+        counter += 1;
+    }
+    id
+}
+
+// Nest an entry under the last heading of a higher level
+fn insert_toc_entry(entries: &mut Vec<TocEntry>, entry: TocEntry) {
+    match entries.last_mut() {
+        Some(last) if last.level < entry.level => insert_toc_entry(&mut last.children, entry),
+        _ => entries.push(entry),
+    }
+}
+
+// Attributes from a `{#id .class key="value"}` list
+#[derive(Default)]
+struct Attributes {
+    id: Option<String>,
+    classes: Vec<String>,
+    pairs: Vec<(String, String)>,
+}
+
+impl Attributes {
+    fn parse(list: &str) -> Option<Self> {
+        let mut attributes = Attributes::default();
+        let mut rest = list.trim();
+        if rest.is_empty() {
+            return None;
+        }
+        while !rest.is_empty() {
+            let captures = ATTRIBUTE.captures(rest)?;
+            if let Some(id) = captures.get(1) {
+                attributes.id = Some(id.as_str().to_string());
+            } else if let Some(class) = captures.get(2) {
+                attributes.classes.push(class.as_str().to_string());
+            } else {
+                let value = captures.get(4).or(captures.get(5))?;
+                attributes
+                    .pairs
+                    .push((captures[3].to_string(), value.as_str().to_string()));
+            }
+            let matched = captures.get(0)?.end();
+            let after = &rest[matched..];
+            // Attributes are separated by whitespace
+            if !after.is_empty() && !after.starts_with(char::is_whitespace) {
+                return None;
+            }
+            rest = after.trim_start();
+        }
+        Some(attributes)
+    }
+
+    fn render(&self) -> String {
+        let mut html = String::new();
+        if let Some(id) = &self.id {
+            html.push_str(&format!(" id=\"{}\"", escape_xml(id)));
+        }
+        if !self.classes.is_empty() {
+            html.push_str(&format!(
+                " class=\"{}\"",
+                escape_xml(&self.classes.join(" "))
+            ));
+        }
+        for (name, value) in &self.pairs {
+            html.push_str(&format!(" {}=\"{}\"", name, escape_xml(value)));
+        }
+        html
+    }
+}
+
+// Split `Text {.attrs}` into the text and its attribute list
+fn trailing_attributes(text: &str) -> Option<(&str, Attributes)> {
+    let text = text.trim_end();
+    let list = text.strip_suffix('}')?;
+    let open = list.rfind('{')?;
+    let attributes = Attributes::parse(&list[open + 1..])?;
+    Some((&text[..open], attributes))
+}
+
+fn is_block(tag: &Tag) -> bool {
+    matches!(
+        tag,
+        Tag::Paragraph
+            | Tag::Heading(..)
+            | Tag::BlockQuote
+            | Tag::CodeBlock(_)
+            | Tag::List(_)
+            | Tag::Item
+            | Tag::FootnoteDefinition(_)
+            | Tag::Table(_)
+    )
+}
+
+// Apply attribute lists to paragraphs, quotes and lists. A list at the end of
+// a paragraph belongs to it, a paragraph that is only a list to the quote or
+// list right before it. The tags are written out directly since
+// pulldown-cmark has no way to add attributes.
+fn attribute_lists(mut events: Vec<Event>) -> Vec<Event> {
+    let mut starts = vec![];
+    let mut blocks: HashMap<usize, usize> = HashMap::new();
+    let mut paragraphs = vec![];
+    for (index, event) in events.iter().enumerate() {
+        match event {
+            Event::Start(tag) if is_block(tag) => starts.push(index),
+            Event::End(tag) if is_block(tag) => {
+                let Some(start) = starts.pop() else {
+                    continue;
+                };
+                blocks.insert(index, start);
+                if matches!(tag, Tag::Paragraph) {
+                    paragraphs.push((start, index));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut attributes: HashMap<usize, Attributes> = HashMap::new();
+    let mut removed: HashSet<usize> = HashSet::new();
+    for (start, end) in paragraphs {
+        // The list can be split over several text events
+        let first_text = (start + 1..end)
+            .rev()
+            .take_while(|&index| matches!(events[index], Event::Text(_)))
+            .last()
+            .unwrap_or(end);
+        let text: String = events[first_text..end]
+            .iter()
+            .filter_map(|event| match event {
+                Event::Text(text) => Some(text.as_ref()),
+                _ => None,
+            })
+            .collect();
+        let Some((before, list)) = trailing_attributes(&text) else {
+            continue;
+        };
+
+        if before.trim().is_empty() && first_text == start + 1 {
+            let target = start
+                .checked_sub(1)
+                .filter(|&previous| {
+                    matches!(events[previous], Event::End(Tag::BlockQuote | Tag::List(_)))
+                })
+                .and_then(|previous| blocks.get(&previous));
+            if let Some(&target) = target {
+                attributes.insert(target, list);
+                removed.extend(start..=end);
+            }
+            continue;
+        }
+        let before = before.trim_end().to_string();
+        events[first_text] = Event::Text(CowStr::from(before));
+        removed.extend(first_text + 1..end);
+        attributes.insert(start, list);
+    }
+    if attributes.is_empty() {
+        return events;
+    }
+
+    let ends: HashMap<usize, usize> = blocks
+        .iter()
+        .filter(|(_, start)| attributes.contains_key(start))
+        .map(|(&end, &start)| (end, start))
+        .collect();
+    let mut output = Vec::with_capacity(events.len());
+    for (index, event) in events.into_iter().enumerate() {
+        if removed.contains(&index) {
+            continue;
+        }
+        let event = match (attributes.get(&index), ends.contains_key(&index), event) {
+            (Some(list), _, Event::Start(tag)) => {
+                let html = match tag {
+                    Tag::Paragraph => format!("<p{}>", list.render()),
+                    Tag::BlockQuote => format!("<blockquote{}>\n", list.render()),
+                    Tag::List(Some(1)) => format!("<ol{}>\n", list.render()),
+                    Tag::List(Some(start)) => {
+                        format!("<ol start=\"{}\"{}>\n", start, list.render())
+                    }
+                    _ => format!("<ul{}>\n", list.render()),
+                };
+                Event::Html(CowStr::from(html))
+            }
+            (_, true, Event::End(tag)) => Event::Html(CowStr::from(match tag {
+                Tag::Paragraph => "</p>\n",
+                Tag::BlockQuote => "</blockquote>\n",
+                Tag::List(Some(_)) => "</ol>\n",
+                _ => "</ul>\n",
+            })),
+            (_, _, event) => event,
+        };
+        output.push(event);
+    }
+    output
+}
+
+const ADMONITIONS: [(&str, &str); 4] = [
+    ("note", "Note"),
+    ("tip", "Tip"),
+    ("warning", "Warning"),
+    ("danger", "Danger"),
+];
+
+// Wrap quotes that open with a `[!NOTE]` line in a titled
+// `<div class="admonition note">`, without the marker. The rest of the quote
+// is markdown like any other, and quotes without a marker are left alone.
+fn admonitions<'a>(config: &MarkdownConfig, events: Vec<Event<'a>>) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    // Whether each open quote is a callout
+    let mut quotes = vec![];
+    let mut index = 0;
+    while index < events.len() {
+        let event = events[index].clone();
+        index += 1;
+        match event {
+            Event::Start(Tag::BlockQuote) => {
+                let Some((kind, label, marker)) = admonition_marker(&events[index..]) else {
+                    quotes.push(false);
+                    output.push(event);
+                    continue;
+                };
+                quotes.push(true);
+                let label = config
+                    .admonition_labels
+                    .get(kind)
+                    .map_or(label, String::as_str);
+                output.push(Event::Html(CowStr::from(format!(
+                    "<div class=\"admonition {}\">\n<p class=\"admonition-title\">{}</p>\n",
+                    kind,
+                    escape_xml(label)
+                ))));
+                output.push(event);
+                // Text after the marker keeps its paragraph, a marker on a
+                // paragraph of its own goes with it
+                if matches!(
+                    events.get(index + 1 + marker),
+                    Some(Event::SoftBreak | Event::HardBreak)
+                ) {
+                    output.push(Event::Start(Tag::Paragraph));
+                }
+                index += marker + 2;
+            }
+            Event::End(Tag::BlockQuote) => {
+                output.push(event);
+                if quotes.pop() == Some(true) {
+                    output.push(Event::Html(CowStr::from("</div>\n")));
+                }
+            }
+            event => output.push(event),
+        }
+    }
+    output
+}
+
+// The kind and default label of the `[!KIND]` marker a quote's events start
+// with, and how many text events it's made of
+fn admonition_marker(events: &[Event]) -> Option<(&'static str, &'static str, usize)> {
+    if !matches!(events.first(), Some(Event::Start(Tag::Paragraph))) {
+        return None;
+    }
+    let mut marker = String::new();
+    let mut count = 0;
+    for event in &events[1..] {
+        let Event::Text(text) = event else {
+            break;
+        };
+        marker.push_str(text);
+        count += 1;
+    }
+    let kind = marker.trim().strip_prefix("[!")?.strip_suffix(']')?;
+    ADMONITIONS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(kind))
+        .map(|(name, label)| (*name, *label, count))
+}
+
+// Turn paragraphs of the form `Term` followed by `: Definition` lines into a
+// `<dl>`. Consecutive definition paragraphs share one list.
+fn definition_lists(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut paragraph: Option<Vec<Event>> = None;
+
+    for event in events {
+        match (&mut paragraph, event) {
+            (None, Event::Start(Tag::Paragraph)) => paragraph = Some(vec![]),
+            (Some(inner), Event::End(Tag::Paragraph)) => {
+                let inner = std::mem::take(inner);
+                paragraph = None;
+                match definition_lines(&inner) {
+                    Some(lines) => push_definition_list(&mut output, lines),
+                    None => {
+                        output.push(Event::Start(Tag::Paragraph));
+                        output.extend(inner);
+                        output.push(Event::End(Tag::Paragraph));
+                    }
+                }
+            }
+            (Some(inner), event) => inner.push(event),
+            (None, event) => output.push(event),
+        }
+    }
+    output
+}
+
+// Split a paragraph into its lines when it's a definition list: one or more
+// terms first, every line after them a `: ` definition
+fn definition_lines<'a>(inner: &[Event<'a>]) -> Option<Vec<(bool, Vec<Event<'a>>)>> {
+    let mut lines = vec![];
+    let mut line = vec![];
+    for event in inner {
+        match event {
+            Event::SoftBreak | Event::HardBreak => lines.push(std::mem::take(&mut line)),
+            event => line.push(event.clone()),
+        }
+    }
+    lines.push(line);
+
+    let lines: Vec<(bool, Vec<Event>)> = lines
+        .into_iter()
+        .map(|mut line| match line.first() {
+            Some(Event::Text(text)) if text.starts_with(": ") => {
+                line[0] = Event::Text(CowStr::from(text[2..].to_string()));
+                (true, line)
+            }
+            _ => (false, line),
+        })
+        .collect();
+
+    let terms = lines
+        .iter()
+        .take_while(|(definition, _)| !definition)
+        .count();
+    let is_list = terms > 0
+        && terms < lines.len()
+        && lines.iter().skip(terms).all(|(definition, _)| *definition);
+    is_list.then_some(lines)
+}
+
+fn push_definition_list<'a>(output: &mut Vec<Event<'a>>, lines: Vec<(bool, Vec<Event<'a>>)>) {
+    let closing = "</dl>\n";
+    if matches!(output.last(), Some(Event::Html(html)) if html.as_ref() == closing) {
+        output.pop();
+    } else {
+        output.push(Event::Html(CowStr::from("<dl>\n")));
+    }
+
+    for (definition, line) in lines {
+        let tag = if definition { "dd" } else { "dt" };
+        output.push(Event::Html(CowStr::from(format!("<{}>", tag))));
+        output.extend(line);
+        output.push(Event::Html(CowStr::from(format!("</{}>\n", tag))));
+    }
+    output.push(Event::Html(CowStr::from(closing)));
+}
+
+// Pull `<!-- name: value -->` lines for the given directive names out of the
+// body. Other comments, and directives inside code, stay where they are.
+pub(crate) fn extract_directives(body: &str, names: &[String]) -> (String, Vec<(String, String)>) {
+    if names.is_empty() {
+        return (body.to_string(), vec![]);
+    }
+
+    let mut directives = vec![];
+    let mut remaining = String::with_capacity(body.len());
+    let mut in_fence = false;
+    for line in body.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let directive = Some(trimmed)
+            .filter(|_| !in_fence)
+            .and_then(|line| line.strip_prefix("<!--"))
+            .and_then(|rest| rest.strip_suffix("-->"))
+            .and_then(|comment| comment.split_once(':'))
+            .map(|(name, value)| (name.trim(), value.trim()))
+            .filter(|(name, _)| names.iter().any(|known| known == name));
+        match directive {
+            Some((name, value)) => directives.push((name.to_string(), value.to_string())),
+            None => {
+                remaining.push_str(line);
+                remaining.push('\n');
+            }
+        }
+    }
+    (remaining, directives)
+}
+
+// Replace `<Note>` and `</Note>` lines with the two halves of the component's
+// wrapper. Blank lines around them keep the markdown inside rendering as
+// markdown. Returns the names without a wrapper, which are left as written.
+pub(crate) fn expand_components(
+    markdown: &str,
+    components: &BTreeMap<String, String>,
+) -> (String, Vec<String>) {
+    let mut unknown: Vec<String> = vec![];
+    let mut output = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let tag = Some(trimmed)
+            .filter(|_| !in_fence)
+            .and_then(|line| line.strip_prefix('<'))
+            .and_then(|rest| rest.strip_suffix('>'))
+            .map(|tag| match tag.strip_prefix('/') {
+                Some(name) => (name, true),
+                None => (tag, false),
+            })
+            .filter(|(name, _)| {
+                name.starts_with(|c: char| c.is_ascii_uppercase())
+                    && name.chars().all(|c| c.is_ascii_alphanumeric())
+            });
+        let Some((name, closing)) = tag else {
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        };
+        let Some(wrapper) = components.get(name) else {
+            if !unknown.iter().any(|unknown| unknown == name) {
+                unknown.push(name.to_string());
+            }
+            output.push_str(line);
+            output.push('\n');
+            continue;
+        };
+        let (open, close) = wrapper.split_once("{}").unwrap_or((wrapper, ""));
+        if closing {
+            output.push_str(&format!("\n{}\n\n", close));
+        } else {
+            output.push_str(&format!("\n{}\n\n", open));
+        }
+    }
+    (output, unknown)
+}
+
+// Split markdown before each `#`-style heading of `level`, any text ahead of
+// the first one stays with it. Headings inside code blocks don't count.
+pub(crate) fn split_at_headings(markdown: &str, level: u8) -> Vec<String> {
+    let marker = "#".repeat(level as usize);
+    let mut parts = vec![String::new()];
+    let mut in_fence = false;
+    let mut has_heading = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let is_heading = !in_fence
+            && line.len() - trimmed.len() < 4
+            && trimmed
+                .strip_prefix(&marker)
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']));
+        if is_heading {
+            if has_heading {
+                parts.push(String::new());
+            }
+            has_heading = true;
+        }
+        let part = parts.last_mut().unwrap();
+        part.push_str(line);
+        part.push('\n');
+    }
+    parts
+}
+
+// Split the body at the earliest excerpt separator, dropping the separator
+pub(crate) fn split_excerpt<'a>(
+    body: &'a str,
+    separators: &[String],
+) -> Option<(&'a str, &'a str)> {
+    separators
+        .iter()
+        .filter(|separator| !separator.is_empty())
+        .filter_map(|separator| {
+            let position = body.find(separator.as_str())?;
+            Some((position, position + separator.len()))
+        })
+        .min()
+        .map(|(start, end)| (&body[..start], &body[end..]))
+}
+
+// Pull `*[HTML]: HyperText Markup Language` lines out of the markdown
+fn extract_abbreviations(markdown: &str) -> (String, Vec<(String, String)>) {
+    let mut abbreviations = vec![];
+    let mut remaining = String::with_capacity(markdown.len());
+    let mut in_fence = false;
+    for line in markdown.lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        }
+        let definition = Some(line)
+            .filter(|_| !in_fence)
+            .and_then(|line| line.strip_prefix("*["))
+            .and_then(|rest| rest.split_once("]:"))
+            .filter(|(abbreviation, _)| !abbreviation.is_empty());
+        match definition {
+            Some((abbreviation, title)) => {
+                abbreviations.push((abbreviation.to_string(), title.trim().to_string()))
+            }
+            None => {
+                remaining.push_str(line);
+                remaining.push('\n');
+            }
+        }
+    }
+    (remaining, abbreviations)
+}
+
+// Wrap the first use of each abbreviation in `<abbr>`, code and image alt
+// texts are left alone
+fn expand_abbreviations<'a>(
+    events: Vec<Event<'a>>,
+    abbreviations: &[(String, String)],
+) -> Vec<Event<'a>> {
+    if abbreviations.is_empty() {
+        return events;
+    }
+
+    let mut pending: Vec<&(String, String)> = abbreviations.iter().collect();
+    let mut skip_depth = 0;
+    let mut output = Vec::with_capacity(events.len());
+    for event in events {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Image(..)) => {
+                skip_depth += 1;
+                output.push(event);
+            }
+            Event::End(Tag::CodeBlock(_) | Tag::Image(..)) => {
+                skip_depth -= 1;
+                output.push(event);
+            }
+            Event::Text(text) if skip_depth == 0 && !pending.is_empty() => {
+                let mut rest: &str = &text;
+                loop {
+                    let first_use = pending
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(index, (abbreviation, _))| {
+                            find_word(rest, abbreviation).map(|position| (position, index))
+                        })
+                        .min();
+                    let Some((position, index)) = first_use else {
+                        break;
+                    };
+
+                    let (abbreviation, title) = pending.remove(index);
+                    if position > 0 {
+                        output.push(Event::Text(CowStr::from(rest[..position].to_string())));
+                    }
+                    output.push(Event::Html(CowStr::from(format!(
+                        "<abbr title=\"{}\">{}</abbr>",
+                        escape_xml(title),
+                        escape_xml(abbreviation)
+                    ))));
+                    rest = &rest[position + abbreviation.len()..];
+                }
+                if !rest.is_empty() {
+                    output.push(Event::Text(CowStr::from(rest.to_string())));
+                }
+            }
+            event => output.push(event),
+        }
+    }
+    output
+}
+
+// Turn bare `https://...` URLs in text into links, the way `<https://...>`
+// already is. Code, existing links and image descriptions are left alone.
+fn autolink(events: Vec<Event>) -> Vec<Event> {
+    let mut skip_depth = 0;
+    let mut output = Vec::with_capacity(events.len());
+    let mut text = String::new();
+    let mut events = events.into_iter().peekable();
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::CodeBlock(_) | Tag::Image(..) | Tag::Link(..)) => {
+                skip_depth += 1;
+                output.push(event);
+            }
+            Event::End(Tag::CodeBlock(_) | Tag::Image(..) | Tag::Link(..)) => {
+                skip_depth -= 1;
+                output.push(event);
+            }
+            // Nor inside links written as HTML
+            Event::Html(ref html) if html.starts_with("<a ") || html.starts_with("<a>") => {
+                skip_depth += 1;
+                output.push(event);
+            }
+            Event::Html(ref html) if html.starts_with("</a>") && skip_depth > 0 => {
+                skip_depth -= 1;
+                output.push(event);
+            }
+            // A URL can come in several text events, split at `_` or `&`
+            Event::Text(part) if skip_depth == 0 => {
+                text.push_str(&part);
+                if matches!(events.peek(), Some(Event::Text(_))) {
+                    continue;
+                }
+                let mut rest = 0;
+                for found in BARE_URL.find_iter(&text) {
+                    let link = trim_url(found.as_str());
+                    if found.start() > rest {
+                        output.push(Event::Text(CowStr::from(
+                            text[rest..found.start()].to_string(),
+                        )));
+                    }
+                    let tag = Tag::Link(
+                        LinkType::Autolink,
+                        CowStr::from(link.to_string()),
+                        CowStr::from(""),
+                    );
+                    output.push(Event::Start(tag.clone()));
+                    output.push(Event::Text(CowStr::from(link.to_string())));
+                    output.push(Event::End(tag));
+                    rest = found.start() + link.len();
+                }
+                if rest < text.len() {
+                    output.push(Event::Text(CowStr::from(text[rest..].to_string())));
+                }
+                text.clear();
+            }
+            event => output.push(event),
+        }
+    }
+    output
+}
+
+// Punctuation ending a sentence isn't part of the URL before it, and neither
+// is a closing parenthesis without an opening one in the URL
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ':', ';', '!', '?', '\'', '*']);
+        let unbalanced =
+            trimmed.ends_with(')') && trimmed.matches('(').count() < trimmed.matches(')').count();
+        let trimmed = if unbalanced {
+            &trimmed[..trimmed.len() - 1]
+        } else {
+            trimmed
+        };
+        if trimmed == url {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+// Position of `word` in `text` where it isn't part of a longer word
+fn find_word(text: &str, word: &str) -> Option<usize> {
+    let is_word_char = |c: char| c.is_alphanumeric() || c == '_';
+    text.match_indices(word)
+        .map(|(position, _)| position)
+        .find(|&position| {
+            let before = text[..position].chars().next_back();
+            let after = text[position + word.len()..].chars().next();
+            !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+        })
+}
@@ -0,0 +1,160 @@
+use crate::{
+    content::{is_fence_opener, parse_date, DATE_FORMAT},
+    content_files,
+    encoding::read_text,
+    Site,
+};
+use anyhow::{Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::{fs, path::PathBuf};
+
+// Keys in the order migrated frontmatter lists them, anything else follows in
+// the order the file had it
+const KEY_ORDER: &[&str] = &[
+    "title",
+    "slug",
+    "description",
+    "date",
+    "updated",
+    "author",
+    "draft",
+    "status",
+    "image",
+    "tags",
+    "keywords",
+];
+
+// Keys holding dates, rewritten as ISO 8601 when they're in one of `date_formats`
+const DATE_KEYS: &[&str] = &["date", "updated"];
+
+// A content file whose frontmatter isn't in the canonical form yet
+#[derive(Debug)]
+pub struct Migration {
+    pub path: PathBuf,
+    pub before: String,
+    pub after: String,
+}
+
+impl Migration {
+    // The frontmatter change as a unified style line diff
+    pub fn diff(&self) -> String {
+        let before: Vec<&str> = self.before.lines().collect();
+        let after: Vec<&str> = self.after.lines().collect();
+        // Longest common subsequence of the lines, from the end
+        let mut common = vec![vec![0usize; after.len() + 1]; before.len() + 1];
+        for i in (0..before.len()).rev() {
+            for j in (0..after.len()).rev() {
+                common[i][j] = if before[i] == after[j] {
+                    common[i + 1][j + 1] + 1
+                } else {
+                    common[i + 1][j].max(common[i][j + 1])
+                };
+            }
+        }
+
+        let mut diff = format!("--- {}\n+++ {}\n", self.path.display(), self.path.display());
+        let (mut i, mut j) = (0, 0);
+        while i < before.len() || j < after.len() {
+            if i < before.len() && j < after.len() && before[i] == after[j] {
+                diff.push_str(&format!(" {}\n", before[i]));
+                i += 1;
+                j += 1;
+            } else if i < before.len() && (j == after.len() || common[i + 1][j] >= common[i][j + 1])
+            {
+                diff.push_str(&format!("-{}\n", before[i]));
+                i += 1;
+            } else {
+                diff.push_str(&format!("+{}\n", after[j]));
+                j += 1;
+            }
+        }
+        diff
+    }
+}
+
+// Rewrite every content file's frontmatter with the current key names from
+// `frontmatter_aliases`, ISO dates and the keys in a canonical order. Only the
+// files that change are returned, and only written when `write` is set, so
+// running it again finds nothing to do.
+pub fn migrate(site: &Site, write: bool) -> Result<Vec<Migration>> {
+    let fallback = site.config.markdown.fallback_encoding.as_deref();
+    let mut migrations = vec![];
+    for path in content_files(site) {
+        let (content, _) = read_text(&path, fallback)?;
+        let Some((before, body)) = split_raw_frontmatter(&content) else {
+            continue;
+        };
+        if before.trim().is_empty() {
+            continue;
+        }
+        let frontmatter: Mapping = serde_yaml::from_str(before)
+            .with_context(|| format!("Invalid frontmatter in {}", path.display()))?;
+        let frontmatter = normalize(site, frontmatter);
+        let after = serde_yaml::to_string(&frontmatter).context("Failed to write frontmatter")?;
+        if after == before {
+            continue;
+        }
+
+        if write {
+            fs::write(&path, format!("---\n{}---\n{}", after, body))
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        migrations.push(Migration {
+            path,
+            before: before.to_string(),
+            after,
+        });
+    }
+    Ok(migrations)
+}
+
+fn normalize(site: &Site, mut frontmatter: Mapping) -> Mapping {
+    // The current key wins when a file has both, like when building
+    for (alias, key) in &site.config.frontmatter_aliases {
+        if let Some(value) = frontmatter.shift_remove(alias.as_str()) {
+            if !frontmatter.contains_key(key.as_str()) {
+                frontmatter.insert(Value::from(key.as_str()), value);
+            }
+        }
+    }
+    for key in DATE_KEYS {
+        let Some(Value::String(value)) = frontmatter.get(*key) else {
+            continue;
+        };
+        // Dates the build wouldn't accept either are left for `check` to report
+        if let Some(date) = parse_date(&site.config.date_formats, value) {
+            frontmatter.insert(
+                Value::from(*key),
+                Value::from(date.format(DATE_FORMAT).to_string()),
+            );
+        }
+    }
+
+    let mut ordered = Mapping::new();
+    for key in KEY_ORDER {
+        if let Some(value) = frontmatter.shift_remove(*key) {
+            ordered.insert(Value::from(*key), value);
+        }
+    }
+    ordered.extend(frontmatter);
+    ordered
+}
+
+// The frontmatter between the `---` lines as written and the rest of the file
+// untouched, `None` for files without frontmatter, like when building
+fn split_raw_frontmatter(content: &str) -> Option<(&str, &str)> {
+    let rest = content
+        .strip_prefix("---\n")
+        .or_else(|| content.strip_prefix("---\r\n"))?;
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end_matches(['\r', '\n']) == "---" {
+            return Some((&rest[..offset], &rest[offset + line.len()..]));
+        }
+        if is_fence_opener(line) {
+            return None;
+        }
+        offset += line.len();
+    }
+    None
+}
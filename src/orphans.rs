@@ -0,0 +1,94 @@
+use crate::Site;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+// List files under `dist/assets` that no generated page or stylesheet refers
+// to. Paths are relative to the dist directory. Nothing is deleted.
+pub fn find_orphaned_assets(site: &Site) -> Result<Vec<PathBuf>> {
+    let attribute = Regex::new(r#"(?:src|href|srcset|content)\s*=\s*["']([^"']+)["']"#)?;
+    let css_url = Regex::new(r#"url\(\s*["']?([^"')]+)["']?\s*\)"#)?;
+    let dist_dir = &site.dist_dir;
+
+    let mut referenced = HashSet::new();
+    for entry in WalkDir::new(dist_dir).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let pattern = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("html") => &attribute,
+            Some("css") => &css_url,
+            _ => continue,
+        };
+
+        let content = fs::read_to_string(path).context("Failed to read generated file")?;
+        let base_dir = path.parent().unwrap_or(dist_dir);
+        for capture in pattern.captures_iter(&content) {
+            // `srcset` holds several comma separated candidates
+            for candidate in capture[1].split(',') {
+                let reference = candidate.split_whitespace().next().unwrap_or_default();
+                if let Some(target) = resolve_reference(site, base_dir, reference) {
+                    referenced.insert(target);
+                }
+            }
+        }
+    }
+
+    let mut orphans: Vec<PathBuf> = WalkDir::new(dist_dir.join("assets"))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| !referenced.contains(entry.path()))
+        .filter_map(|entry| {
+            entry
+                .path()
+                .strip_prefix(dist_dir)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .collect();
+    orphans.sort();
+    Ok(orphans)
+}
+
+// Map a link found in `base_dir` to the file it points at under dist
+fn resolve_reference(site: &Site, base_dir: &Path, reference: &str) -> Option<PathBuf> {
+    // Templates escape slashes in attribute values
+    let reference = reference.replace("&#x2F;", "/").replace("&amp;", "&");
+    let reference = reference.split(['?', '#']).next().unwrap_or_default();
+
+    let base_url = site.config.base_url.trim_end_matches('/');
+    let reference = match reference.strip_prefix(base_url) {
+        Some(path) if !base_url.is_empty() => path,
+        _ => reference,
+    };
+    if reference.is_empty() || reference.starts_with("//") || reference.contains(':') {
+        return None;
+    }
+    let base_path = format!("/{}", site.config.base_path.trim_matches('/'));
+    let reference = match reference.strip_prefix(&base_path) {
+        Some(path) if base_path != "/" && path.starts_with('/') => path,
+        _ => reference,
+    };
+
+    let joined = match reference.strip_prefix('/') {
+        Some(path) => site.dist_dir.join(path),
+        None => base_dir.join(reference),
+    };
+
+    // Resolve `..` without touching the filesystem
+    let mut resolved = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            component => resolved.push(component),
+        }
+    }
+    Some(resolved)
+}
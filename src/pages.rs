@@ -0,0 +1,713 @@
+use crate::{
+    config::{FirstListingPage, ListingGroup, SectionConfig, SortOrder},
+    content::{extract_metadata, read_source, DATE_FORMAT},
+    escape_xml,
+    favicon::favicon_links,
+    feeds::page_feeds,
+    markdown::render_markdown,
+    redirects, tags, Config, PostMetadata, Site, SiteError,
+};
+use anyhow::{Context, Result};
+use chrono::{Duration, NaiveDate};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::PoisonError,
+};
+
+#[derive(Serialize)]
+struct Pagination {
+    number: usize,
+    count: usize,
+}
+
+#[derive(Serialize)]
+struct ListingGroupContext<'a> {
+    label: String,
+    posts: Vec<&'a PostMetadata>,
+}
+
+#[derive(Serialize)]
+struct NavLink<'a> {
+    label: &'a str,
+    url: String,
+    active: bool,
+}
+
+// Build the context shared by every page, `page_url` is the page's path under
+// dist, without the base path
+pub(crate) fn base_context(site: &Site, page_url: &str, section: Option<&str>) -> tera::Context {
+    let config = &site.config;
+    let nav: Vec<NavLink> = config
+        .nav
+        .iter()
+        .map(|item| NavLink {
+            label: &item.label,
+            url: config.site_url(&listing_link(site, &item.url)),
+            active: is_active_link(&listing_link(site, &item.url), page_url),
+        })
+        .collect();
+
+    let mut context = tera::Context::new();
+    // Pages with a kind of their own set a more specific class
+    context.insert("body_class", "page");
+    // Posts are articles, listings and everything else describe the site
+    context.insert("og_type", "website");
+    context.insert("nav", &nav);
+    // Site paths for the `url` filter, `{{ listings.blog | url }}`
+    let listings: BTreeMap<&str, String> = config
+        .sections
+        .iter()
+        .map(|(name, section)| (name.as_str(), listing_path(&site.config, name, section, 1)))
+        .collect();
+    context.insert("listings", &listings);
+    context.insert("env", &config.env);
+    context.insert("lang", &config.lang);
+    context.insert(
+        "dir",
+        config
+            .dir
+            .as_deref()
+            .unwrap_or_else(|| text_direction(&config.lang)),
+    );
+    context.insert("build", &site.build_info);
+    context.insert("noindex", &!config.is_production());
+    insert_latest_across(site, &mut context);
+    if !site.inline_styles.is_empty() {
+        context.insert("inline_styles", &site.inline_styles);
+    }
+    if let Some(host) = &config.canonical_host {
+        context.insert("canonical_host", host);
+    }
+    if let Some(links) = favicon_links(site) {
+        context.insert("favicon_links", &links);
+    }
+    let feeds = page_feeds(site, page_url, section);
+    if !feeds.is_empty() {
+        context.insert("feeds", &feeds);
+    }
+    if !config.base_url.is_empty() {
+        // An `index.html` is served at its directory, which is the URL to
+        // point search engines at, e.g. the bare base URL for the homepage
+        let page_url = match page_url.strip_suffix("/index.html") {
+            Some(dir) => format!("{}/", dir),
+            None => page_url.to_string(),
+        };
+        let canonical_url = config.absolute_url(&config.site_url(&page_url));
+        context.insert("canonical_url", &canonical_url);
+    }
+    context
+}
+
+// Pages of posts get their context before every post is known, so this is
+// set again when they're written
+pub(crate) fn insert_latest_across(site: &Site, context: &mut tera::Context) {
+    let latest = site
+        .latest_across
+        .read()
+        .unwrap_or_else(PoisonError::into_inner);
+    if site.config.latest_across > 0 {
+        context.insert("latest_across", &*latest);
+    }
+}
+
+// Scripts of these languages are written right to left
+pub(crate) fn text_direction(lang: &str) -> &'static str {
+    let primary = lang.split(['-', '_']).next().unwrap_or_default();
+    let rtl = ["ar", "dv", "fa", "he", "ks", "ps", "sd", "ug", "ur", "yi"];
+    if rtl.contains(&primary.to_ascii_lowercase().as_str()) {
+        "rtl"
+    } else {
+        "ltr"
+    }
+}
+
+fn is_active_link(link: &str, page_url: &str) -> bool {
+    // Compare without extensions, index files or trailing slashes so `/blog`,
+    // `/blog/` and `/blog.html` all point at the same page
+    fn normalize(url: &str) -> &str {
+        let url = url.strip_suffix(".html").unwrap_or(url);
+        let url = url.strip_suffix("index").unwrap_or(url);
+        url.trim_end_matches('/')
+    }
+
+    let (link, page) = (normalize(link), normalize(page_url));
+    // Section links stay active on the pages nested below them
+    link == page || (!link.is_empty() && page.starts_with(&format!("{}/", link)))
+}
+
+pub(crate) fn generate_homepage(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    // The listing of `[homepage] listing` is written at `/` instead
+    if site.config.homepage.listing.is_some() {
+        return Ok(());
+    }
+
+    // Posts past the listing cutoff are left to the section listings' archive
+    let posts: Vec<&PostMetadata> = posts
+        .iter()
+        .filter(|post| !is_past_cutoff(site, post, site.config.listing_cutoff_days))
+        .collect();
+    // The latest post is featured, the next few are listed below it
+    let mut latest = latest_posts(&posts, 1 + site.config.homepage.recent_posts).into_iter();
+    let featured = latest.next();
+    let recent: Vec<&PostMetadata> = latest.collect();
+
+    let mut context = base_context(site, "/index.html", None);
+    context.insert("body_class", "home");
+    context.insert("title", "Homepage");
+    context.insert("featured", &featured);
+    context.insert("recent", &recent);
+    // A plain list of the latest posts, ending in a link to the whole blog
+    context.insert(
+        "posts",
+        &latest_posts(&posts, site.config.homepage_post_count),
+    );
+    if let Some(blog) = site.config.sections.get("blog") {
+        let url = site
+            .config
+            .site_url(&listing_path(&site.config, "blog", blog, 1));
+        context.insert("view_all_url", &url);
+    }
+    context.insert("sections_preview", &sections_preview(site, &posts));
+    context.insert("featured_posts", &featured_posts(&posts));
+
+    let rendered = site
+        .render("homepage.html", &context)
+        .context("Failed to render homepage template")?;
+
+    let output_path = site.dist_dir.join("index.html");
+    fs::write(output_path, rendered).context("Failed to write homepage")?;
+    Ok(())
+}
+
+pub(crate) fn generate_listing(site: &Site, category: &str, posts: &[&PostMetadata]) -> Result<()> {
+    let section = &site.config.sections[category];
+    let (mut posts, mut archived) = split_at_cutoff(site, posts);
+    sort_posts(&mut posts, section);
+    sort_posts(&mut archived, section);
+
+    let count = listing_page_count(section, posts.len());
+    let per_page = section
+        .paginate
+        .filter(|per_page| *per_page > 0)
+        .unwrap_or(posts.len())
+        .max(1);
+    let intro = section_intro(site, category, section)?;
+    for number in 1..=count {
+        let page_posts: Vec<&PostMetadata> = posts
+            .iter()
+            .skip((number - 1) * per_page)
+            .take(per_page)
+            .copied()
+            .collect();
+        let path = listing_path(&site.config, category, section, number);
+        let mut context = base_context(site, &tags::file_path(&path), Some(category));
+        context.insert("body_class", &format!("{} listing", category));
+        context.insert("posts", &page_posts);
+        context.insert("title", &format!("{} Listing", category));
+        if number == 1 {
+            if let Some(intro) = &intro {
+                context.insert("intro", &intro.html);
+                if let Some(title) = &intro.title {
+                    context.insert("title", title);
+                }
+            }
+        }
+        if section.group_listing_by == ListingGroup::Month {
+            context.insert("groups", &group_by_month(&page_posts));
+        }
+        if count > 1 {
+            context.insert("pagination", &Pagination { number, count });
+        }
+        // Posts past `listing_cutoff_days` still get a mention after the rest
+        if number == count {
+            context.insert("archived", &archived);
+        }
+        if number > 1 && site.config.noindex_pagination {
+            context.insert("robots", "noindex, follow");
+        }
+        if number > 1 {
+            let prev_url =
+                site.config
+                    .site_url(&listing_path(&site.config, category, section, number - 1));
+            context.insert("prev_url", &prev_url);
+        }
+        if number < count {
+            let next_url =
+                site.config
+                    .site_url(&listing_path(&site.config, category, section, number + 1));
+            context.insert("next_url", &next_url);
+        }
+
+        let rendered = site
+            .render(
+                &site.section_template(category, &section.list_template),
+                &context,
+            )
+            .context("Failed to render listing template")?;
+
+        let output_path = site
+            .dist_dir
+            .join(tags::file_path(&path).trim_start_matches('/'));
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent).context("Failed to create listing directory")?;
+        }
+        fs::write(output_path, rendered).context("Failed to write listing")?;
+    }
+
+    // The first page only ever lives at the section's own URL, `blog-1.html`
+    // at most redirects there
+    if section.paginate.is_some_and(|per_page| per_page > 0)
+        && site.config.first_listing_page == FirstListingPage::Redirect
+    {
+        let first = listing_path(&site.config, category, section, 1);
+        let target = site.config.absolute_url(&site.config.site_url(&first));
+        redirects::write_stub(
+            site,
+            &numbered_listing_path(&first, 1),
+            &redirects::redirect_stub(&target),
+        )?;
+    }
+    // Links to where the listing used to be lead to the homepage
+    if site.config.homepage.listing.as_deref() == Some(category) {
+        let target = site.config.absolute_url(&site.config.site_url("/"));
+        redirects::write_stub(
+            site,
+            &own_listing_path(category, section),
+            &redirects::redirect_stub(&target),
+        )?;
+    }
+    Ok(())
+}
+
+// Whether a post is dated more than `days` before the build, undated posts
+// never are
+pub(crate) fn is_past_cutoff(site: &Site, post: &PostMetadata, days: Option<i64>) -> bool {
+    let Some(days) = days else {
+        return false;
+    };
+    NaiveDate::parse_from_str(&post.date, DATE_FORMAT)
+        .is_ok_and(|date| date < site.build_date() - Duration::days(days))
+}
+
+// A section's posts split into the ones its listing shows and the ones past
+// `listing_cutoff_days`
+pub(crate) fn split_at_cutoff<'a>(
+    site: &Site,
+    posts: &[&'a PostMetadata],
+) -> (Vec<&'a PostMetadata>, Vec<&'a PostMetadata>) {
+    posts
+        .iter()
+        .partition(|post| !is_past_cutoff(site, post, site.config.listing_cutoff_days))
+}
+
+// How many pages a section's listing takes, always at least one
+pub(crate) fn listing_page_count(section: &SectionConfig, posts: usize) -> usize {
+    match section.paginate {
+        Some(per_page) if per_page > 0 => posts.div_ceil(per_page).max(1),
+        _ => 1,
+    }
+}
+
+// Site path of a listing page, `/blog.html` unless the section sets
+// `list_path` or is the homepage's `listing`, which starts at `/`. Later pages
+// go next to the first, see `numbered_listing_path`.
+pub(crate) fn listing_path(
+    config: &Config,
+    category: &str,
+    section: &SectionConfig,
+    number: usize,
+) -> String {
+    let first = if config.homepage.listing.as_deref() == Some(category) {
+        "/".to_string()
+    } else {
+        own_listing_path(category, section)
+    };
+    match number {
+        1 => first,
+        _ => numbered_listing_path(&first, number),
+    }
+}
+
+// Where a section's listing starts when it isn't on the homepage
+pub(crate) fn own_listing_path(category: &str, section: &SectionConfig) -> String {
+    match &section.list_path {
+        Some(path) => format!("/{}", path.trim_start_matches('/')),
+        None => format!("/{}.html", category),
+    }
+}
+
+// `/blog.html` numbers its pages `/blog-2.html`, a directory like `/blog/`
+// numbers them `/blog/2/`
+pub(crate) fn numbered_listing_path(first: &str, number: usize) -> String {
+    if first.ends_with('/') {
+        format!("{}{}/", first, number)
+    } else {
+        match first.strip_suffix(".html") {
+            Some(stem) => format!("{}-{}.html", stem, number),
+            None => format!("{}-{}", first, number),
+        }
+    }
+}
+
+// The listing a link to `/blog.html` means, for nav items written before the
+// section moved its listing with `list_path`
+pub(crate) fn listing_link(site: &Site, link: &str) -> String {
+    let section = link
+        .strip_prefix('/')
+        .and_then(|link| link.strip_suffix(".html"))
+        .and_then(|name| site.config.sections.get_key_value(name));
+    match section {
+        Some((name, section)) => listing_path(&site.config, name, section, 1),
+        None => link.to_string(),
+    }
+}
+
+#[derive(PartialEq, PartialOrd)]
+enum SortKey {
+    Date(NaiveDate),
+    Number(f64),
+    Text(String),
+}
+
+// Posts newest first under `April 2024` style labels, undated ones last
+fn group_by_month<'a>(posts: &[&'a PostMetadata]) -> Vec<ListingGroupContext<'a>> {
+    let mut posts: Vec<(Option<NaiveDate>, &PostMetadata)> = posts
+        .iter()
+        .map(|post| {
+            (
+                NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok(),
+                *post,
+            )
+        })
+        .collect();
+    posts.sort_by_key(|(date, _)| std::cmp::Reverse(*date));
+
+    let mut groups: Vec<ListingGroupContext> = vec![];
+    for (date, post) in posts {
+        let label = date.map_or_else(
+            || "Undated".to_string(),
+            |date| date.format("%B %Y").to_string(),
+        );
+        match groups.last_mut() {
+            Some(group) if group.label == label => group.posts.push(post),
+            _ => groups.push(ListingGroupContext {
+                label,
+                posts: vec![post],
+            }),
+        }
+    }
+    groups
+}
+
+// Sort a listing by the section's `sort_by` key, posts missing it sink to the end
+pub(crate) fn sort_posts(posts: &mut [&PostMetadata], section: &SectionConfig) {
+    let Some(key) = &section.sort_by else {
+        return;
+    };
+    // A manual order, with the newest first among posts of the same weight
+    if key == "weight" {
+        posts.sort_by(|a, b| {
+            let ordering = weight(a, section).cmp(&weight(b, section));
+            let ordering = match section.sort_order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            };
+            ordering.then_with(|| post_date(b).cmp(&post_date(a)))
+        });
+        return;
+    }
+
+    posts.sort_by(|a, b| match (sort_key(a, key), sort_key(b, key)) {
+        (Some(a), Some(b)) => {
+            let ordering = a.partial_cmp(&b).unwrap_or(Ordering::Equal);
+            match section.sort_order {
+                SortOrder::Asc => ordering,
+                SortOrder::Desc => ordering.reverse(),
+            }
+        }
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    });
+}
+
+// A post's `weight`, `default_weight` when it has none or it isn't a whole number
+fn weight(post: &PostMetadata, section: &SectionConfig) -> i64 {
+    match post.frontmatter.get("weight") {
+        Some(Value::Number(number)) => number.as_i64(),
+        Some(Value::String(text)) => text.trim().parse().ok(),
+        _ => None,
+    }
+    .unwrap_or(section.default_weight)
+}
+
+fn post_date(post: &PostMetadata) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok()
+}
+
+fn sort_key(post: &PostMetadata, key: &str) -> Option<SortKey> {
+    match key {
+        "title" => Some(SortKey::Text(post.title.to_lowercase())),
+        "date" => NaiveDate::parse_from_str(&post.date, DATE_FORMAT)
+            .ok()
+            .map(SortKey::Date),
+        _ => match post.frontmatter.get(key)? {
+            Value::Number(number) => number.as_f64().map(SortKey::Number),
+            Value::String(text) => Some(
+                text.parse()
+                    .map(SortKey::Number)
+                    .unwrap_or_else(|_| SortKey::Text(text.to_lowercase())),
+            ),
+            _ => None,
+        },
+    }
+}
+
+// Write `updates.html` from its template with the posts revised within the
+// configured window, latest revision first. Skipped when nothing was updated.
+pub(crate) fn generate_updates(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    if !site.has_template("updates.html") {
+        return Ok(());
+    }
+
+    let since = site.build_date() - Duration::days(site.config.updates.days);
+    let mut updated_posts: Vec<(NaiveDate, &PostMetadata)> = posts
+        .iter()
+        .filter(|post| !post.noindex)
+        .filter_map(|post| {
+            let updated = NaiveDate::parse_from_str(post.updated.as_ref()?, DATE_FORMAT).ok()?;
+            Some((updated, post))
+        })
+        .filter(|(updated, _)| *updated >= since)
+        .collect();
+    if updated_posts.is_empty() {
+        return Ok(());
+    }
+    updated_posts.sort_by_key(|(updated, _)| std::cmp::Reverse(*updated));
+    let updated_posts: Vec<&PostMetadata> =
+        updated_posts.into_iter().map(|(_, post)| post).collect();
+
+    let mut context = base_context(site, "/updates.html", None);
+    context.insert("title", "Updates");
+    context.insert("posts", &updated_posts);
+    let rendered = site
+        .render("updates.html", &context)
+        .context("Failed to render updates template")?;
+    fs::write(site.dist_dir.join("updates.html"), rendered)
+        .context("Failed to write updates page")?;
+    Ok(())
+}
+
+// Write `featured.html` from its template with the posts marked `featured`
+// in any section, latest first. Skipped when none are.
+pub(crate) fn generate_featured(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    if !site.has_template("featured.html") {
+        return Ok(());
+    }
+
+    let posts: Vec<&PostMetadata> = posts.iter().collect();
+    let featured = featured_posts(&posts);
+    if featured.is_empty() {
+        return Ok(());
+    }
+
+    let mut context = base_context(site, "/featured.html", None);
+    context.insert("title", "Featured");
+    context.insert("posts", &featured);
+    let rendered = site
+        .render("featured.html", &context)
+        .context("Failed to render featured template")?;
+    fs::write(site.dist_dir.join("featured.html"), rendered)
+        .context("Failed to write featured page")?;
+    Ok(())
+}
+
+// The dated section posts marked `featured`, newest first
+fn featured_posts<'a>(posts: &[&'a PostMetadata]) -> Vec<&'a PostMetadata> {
+    let featured: Vec<&PostMetadata> = posts.iter().filter(|post| post.featured).copied().collect();
+    latest_posts(&featured, featured.len())
+}
+
+// The `count` latest dated section posts that are meant to be found, newest first
+fn latest_posts<'a>(posts: &[&'a PostMetadata], count: usize) -> Vec<&'a PostMetadata> {
+    let mut latest: Vec<(NaiveDate, &PostMetadata)> = posts
+        .iter()
+        .copied()
+        .filter(|post| post.category.is_some() && !post.noindex)
+        .filter_map(|post| {
+            let date = NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok()?;
+            Some((date, post))
+        })
+        .collect();
+    latest.sort_by_key(|(date, _)| std::cmp::Reverse(*date));
+    latest
+        .into_iter()
+        .take(count)
+        .map(|(_, post)| post)
+        .collect()
+}
+
+// The latest `homepage_posts` of each section by name, leaving out sections
+// without dated posts
+fn sections_preview<'a>(
+    site: &Site,
+    posts: &[&'a PostMetadata],
+) -> BTreeMap<String, Vec<&'a PostMetadata>> {
+    site.config
+        .sections
+        .iter()
+        .filter_map(|(name, section)| {
+            let section_posts: Vec<&PostMetadata> = posts
+                .iter()
+                .copied()
+                .filter(|post| post.category.as_deref() == Some(name.as_str()))
+                .collect();
+            let latest = latest_posts(&section_posts, section.homepage_posts);
+            (!latest.is_empty()).then(|| (name.clone(), latest))
+        })
+        .collect()
+}
+
+// The newest posts of all sections together. Undated posts go after the
+// dated ones, in file name order.
+pub(crate) fn latest_across(posts: &[PostMetadata], count: usize) -> Vec<&PostMetadata> {
+    let mut latest: Vec<&PostMetadata> = posts
+        .iter()
+        .filter(|post| post.category.is_some() && !post.noindex)
+        .collect();
+    latest.sort_by_key(|post| {
+        std::cmp::Reverse(NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok())
+    });
+    latest.truncate(count);
+    latest
+}
+
+// Write `404.html` from the `404.html` template, or a bare page when the site
+// doesn't have one
+pub(crate) fn generate_404(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let config = &site.config.not_found;
+
+    let posts: Vec<&PostMetadata> = posts.iter().collect();
+    let recent_posts = latest_posts(&posts, config.recent_posts);
+    let search_url = config
+        .search_url
+        .as_ref()
+        .map(|url| site.config.site_url(url));
+
+    let rendered = if site.has_template("404.html") {
+        let mut context = base_context(site, "/404.html", None);
+        context.insert("title", "Page not found");
+        context.insert("recent_posts", &recent_posts);
+        if let Some(search_url) = &search_url {
+            context.insert("search_url", search_url);
+        }
+        site.render("404.html", &context)
+            .context("Failed to render 404 template")?
+    } else {
+        fallback_404(site, &recent_posts, search_url.as_deref())
+    };
+
+    fs::write(site.dist_dir.join("404.html"), rendered).context("Failed to write 404 page")?;
+    Ok(())
+}
+
+fn fallback_404(site: &Site, recent_posts: &[&PostMetadata], search_url: Option<&str>) -> String {
+    let mut body = format!(
+        "<h1>Page not found</h1>\n<p><a href=\"{}\">Back to the homepage</a></p>\n",
+        escape_xml(&site.config.site_url("/"))
+    );
+    if let Some(search_url) = search_url {
+        body.push_str(&format!(
+            "<p><a href=\"{}\">Search the site</a></p>\n",
+            escape_xml(search_url)
+        ));
+    }
+    if !recent_posts.is_empty() {
+        body.push_str("<h2>Recent posts</h2>\n<ul>\n");
+        for post in recent_posts {
+            body.push_str(&format!(
+                "<li><a href=\"{}\">{}</a></li>\n",
+                escape_xml(&post.url),
+                escape_xml(&post.title)
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\" />\n\
+         <meta name=\"robots\" content=\"noindex\" />\n<title>Page not found</title>\n\
+         </head>\n<body>\n{}</body>\n</html>\n",
+        body
+    )
+}
+
+struct SectionIntro {
+    title: Option<String>,
+    html: String,
+}
+
+// `_index.md` (or `index.md`) at the root of a section's directory, the intro
+// of its listing rather than a page of its own. With both, one of them would
+// quietly become a post, so that fails.
+fn section_intro_path(site: &Site, name: &str, section: &SectionConfig) -> Result<Option<PathBuf>> {
+    let dir = site
+        .content_dir()
+        .join(section.dir.as_deref().unwrap_or(name));
+    let found: Vec<PathBuf> = ["_index.md", "index.md"]
+        .iter()
+        .map(|file| dir.join(file))
+        .filter(|path| path.is_file())
+        .collect();
+    if let [intro, other] = found.as_slice() {
+        return Err(SiteError::Validation {
+            path: other.clone(),
+            message: format!(
+                "Section `{}` has both {} and {}, keep one of them as its intro",
+                name,
+                intro.display(),
+                other.display()
+            ),
+        }
+        .into());
+    }
+    Ok(found.into_iter().next())
+}
+
+pub(crate) fn check_section_intros(site: &Site) -> Result<()> {
+    for (name, section) in &site.config.sections {
+        section_intro_path(site, name, section)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn is_section_intro(site: &Site, path: &Path) -> bool {
+    site.config.sections.iter().any(|(name, section)| {
+        section_intro_path(site, name, section)
+            .ok()
+            .flatten()
+            .is_some_and(|intro| intro == path)
+    })
+}
+
+fn section_intro(site: &Site, name: &str, section: &SectionConfig) -> Result<Option<SectionIntro>> {
+    let Some(path) = section_intro_path(site, name, section)? else {
+        return Ok(None);
+    };
+    let source = read_source(site, &path)?;
+    let page_dir = path
+        .parent()
+        .and_then(|dir| dir.strip_prefix(site.content_dir()).ok())
+        .unwrap_or(Path::new(""));
+    let rendered = render_markdown(site, &source.markdown, false, 0, false, page_dir)
+        .with_context(|| format!("Failed to render {}", path.display()))?;
+    Ok(Some(SectionIntro {
+        title: extract_metadata(&source.frontmatter, "title"),
+        html: rendered.html,
+    }))
+}
@@ -0,0 +1,68 @@
+use crate::{
+    pages::{base_context, sort_posts},
+    urls::join_base_path,
+    PostMetadata, Site,
+};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{collections::BTreeMap, fs};
+
+pub(crate) const PRINT_TEMPLATE: &str = "print.html";
+
+#[derive(Serialize)]
+struct Chapter<'a> {
+    title: &'a str,
+    url: &'a str,
+    content: &'a str,
+}
+
+// Write `<book>/print.html` with every chapter of the books a section lists
+// in `print_books`, in listing order, so readers can read or print it whole.
+// A book is a directory right under the section's one.
+pub(crate) fn generate_print_books(
+    site: &Site,
+    category: &str,
+    posts: &[&PostMetadata],
+) -> Result<()> {
+    let section = &site.config.sections[category];
+    if section.print_books.is_empty() {
+        return Ok(());
+    }
+    let dir = section.dir.as_deref().unwrap_or(category);
+    let prefix = join_base_path(&site.config.base_path, &format!("/{}/", dir));
+
+    let mut posts = posts.to_vec();
+    sort_posts(&mut posts, section);
+    let mut books: BTreeMap<&str, Vec<Chapter>> = BTreeMap::new();
+    for post in posts {
+        let Some((book, _)) = post
+            .url
+            .strip_prefix(&prefix)
+            .and_then(|path| path.split_once('/'))
+        else {
+            continue;
+        };
+        books.entry(book).or_default().push(Chapter {
+            title: &post.title,
+            url: &post.url,
+            content: &post.content,
+        });
+    }
+
+    for book in &section.print_books {
+        let Some(chapters) = books.get(book.as_str()) else {
+            continue;
+        };
+        let path = format!("/{}/{}/print.html", dir, book);
+        let mut context = base_context(site, &path, Some(category));
+        context.insert("title", book);
+        context.insert("book", book);
+        context.insert("chapters", chapters);
+        let rendered = site
+            .render(&site.section_template(category, PRINT_TEMPLATE), &context)
+            .context("Failed to render print template")?;
+        fs::write(site.dist_dir.join(path.trim_start_matches('/')), rendered)
+            .with_context(|| format!("Failed to write the print version of {}", book))?;
+    }
+    Ok(())
+}
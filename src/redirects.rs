@@ -0,0 +1,149 @@
+use crate::{escape_xml, urls, PostMetadata, Site};
+use anyhow::{Context, Result};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+// File under `src/content` listing removed pages, each mapped to the URL that
+// replaces it or to `"gone"`
+const GONE_FILE: &str = "_gone.toml";
+const GONE: &str = "gone";
+
+// Write a stub page for every `redirect_from` entry that sends visitors on to
+// the post's current URL, stubs for removed pages, and a Netlify `_redirects`
+// file when there are rules
+pub(crate) fn generate_redirects(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let config = &site.config;
+    let mut rules = vec![];
+    for post in posts {
+        let target = config.absolute_url(&post.url);
+        for from in &post.redirect_from {
+            write_stub(site, from, &redirect_stub(&target))?;
+            let from = urls::join_base_path(&config.base_path, from);
+            rules.push(format!("{} {} 301", from, post.url));
+        }
+    }
+
+    if !config.redirects_file {
+        rules.clear();
+    }
+
+    // Removed pages either point at their replacement or answer 410. Hosts don't
+    // apply rules over existing files unless forced, hence the `!`.
+    for (from, target) in load_gone(site)? {
+        if posts.iter().any(|post| post.url == config.site_url(&from)) {
+            anyhow::bail!("`{}` is listed in {} but still exists", from, GONE_FILE);
+        }
+        let rule_from = urls::join_base_path(&config.base_path, &from);
+        if target == GONE {
+            write_stub(site, &from, GONE_STUB)?;
+            rules.push(format!("{} {} 410!", rule_from, rule_from));
+        } else {
+            let target = config.site_url(&target);
+            write_stub(site, &from, &redirect_stub(&config.absolute_url(&target)))?;
+            rules.push(format!("{} {} 301", rule_from, target));
+        }
+    }
+
+    // Site-wide rules go last so catch-all patterns don't shadow the post redirects
+    rules.extend(
+        config
+            .redirects
+            .iter()
+            .map(|rule| format!("{} {} {}", rule.from, rule.to, rule.status)),
+    );
+    if !rules.is_empty() {
+        let mut redirects = rules.join("\n");
+        redirects.push('\n');
+        fs::write(site.dist_dir.join("_redirects"), redirects)
+            .context("Failed to write _redirects file")?;
+    }
+    Ok(())
+}
+
+fn load_gone(site: &Site) -> Result<BTreeMap<String, String>> {
+    let path = site.content_dir().join(GONE_FILE);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let content = fs::read_to_string(&path).context("Failed to read removed pages")?;
+    toml::from_str(&content).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+pub(crate) fn write_stub(site: &Site, from: &str, html: &str) -> Result<()> {
+    let stub_path = site.dist_dir.join(stub_path(from)?);
+    if let Some(parent) = stub_path.parent() {
+        fs::create_dir_all(parent).context("Failed to create redirect directories")?;
+    }
+    fs::write(&stub_path, html).with_context(|| format!("Failed to write stub for {}", from))
+}
+
+// Write the configured headers in Netlify's `_headers` format
+pub(crate) fn generate_headers(site: &Site) -> Result<()> {
+    let rules = &site.config.headers;
+    if rules.is_empty() {
+        return Ok(());
+    }
+
+    let mut headers = String::new();
+    for rule in rules {
+        headers.push_str(&rule.path);
+        headers.push('\n');
+        for (name, value) in &rule.values {
+            headers.push_str(&format!("  {}: {}\n", name, value));
+        }
+    }
+    fs::write(site.dist_dir.join("_headers"), headers).context("Failed to write _headers file")?;
+    Ok(())
+}
+
+// `/old.html` is written as is, anything else is treated as a directory
+fn stub_path(from: &str) -> Result<PathBuf> {
+    let path = Path::new(from.trim_start_matches('/'));
+    if !path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        anyhow::bail!("Invalid redirect path `{}`", from);
+    }
+
+    if path.extension().is_some_and(|ext| ext == "html") {
+        Ok(path.to_path_buf())
+    } else {
+        Ok(path.join("index.html"))
+    }
+}
+
+pub(crate) fn redirect_stub(target: &str) -> String {
+    let target = escape_xml(target);
+    format!(
+        "<!DOCTYPE html>\n\
+         <html>\n  \
+         <head>\n    \
+         <meta charset=\"utf-8\" />\n    \
+         <title>Redirecting…</title>\n    \
+         <link rel=\"canonical\" href=\"{0}\" />\n    \
+         <meta name=\"robots\" content=\"noindex\" />\n    \
+         <meta http-equiv=\"refresh\" content=\"0; url={0}\" />\n  \
+         </head>\n  \
+         <body>\n    \
+         <a href=\"{0}\">{0}</a>\n  \
+         </body>\n\
+         </html>\n",
+        target
+    )
+}
+
+const GONE_STUB: &str = "<!DOCTYPE html>\n\
+<html>\n  \
+<head>\n    \
+<meta charset=\"utf-8\" />\n    \
+<title>Gone</title>\n    \
+<meta name=\"robots\" content=\"noindex\" />\n  \
+</head>\n  \
+<body>\n    \
+<p>This page has been removed.</p>\n  \
+</body>\n\
+</html>\n";
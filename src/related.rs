@@ -0,0 +1,84 @@
+use crate::{
+    content::{extract_metadata, DATE_FORMAT},
+    PostMetadata,
+};
+use chrono::NaiveDate;
+use std::cmp::Reverse;
+
+// Other posts of the section in the same `series` first, in `series_index`
+// order, then the ones sharing the most tags with it, newest first on ties.
+// At most `count`.
+pub(crate) fn related_posts<'a>(
+    post: &PostMetadata,
+    posts: &'a [PostMetadata],
+    count: usize,
+) -> Vec<&'a PostMetadata> {
+    let others = posts
+        .iter()
+        .filter(|other| other.url != post.url && other.category == post.category);
+    let series = extract_metadata(&post.frontmatter, "series");
+
+    let mut related: Vec<&PostMetadata> = match &series {
+        Some(series) => others
+            .clone()
+            .filter(|other| extract_metadata(&other.frontmatter, "series").as_ref() == Some(series))
+            .collect(),
+        None => vec![],
+    };
+    // Entries without an index go last, in file order
+    related.sort_by(|a, b| match (series_index(a), series_index(b)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+
+    let tags: Vec<String> = post.tags.iter().map(|tag| tag.to_lowercase()).collect();
+    let mut by_tags: Vec<(usize, &PostMetadata)> = others
+        .filter(|other| !related.iter().any(|related| related.url == other.url))
+        .map(|other| {
+            let shared = other
+                .tags
+                .iter()
+                .filter(|tag| tags.contains(&tag.to_lowercase()))
+                .count();
+            (shared, other)
+        })
+        .filter(|(shared, _)| *shared > 0)
+        .collect();
+    by_tags.sort_by_key(|(shared, other)| {
+        (
+            Reverse(*shared),
+            Reverse(NaiveDate::parse_from_str(&other.date, DATE_FORMAT).ok()),
+        )
+    });
+
+    related.extend(by_tags.into_iter().map(|(_, other)| other));
+    related.truncate(count);
+    related
+}
+
+fn series_index(post: &PostMetadata) -> Option<f64> {
+    extract_metadata(&post.frontmatter, "series_index").and_then(|index| index.parse().ok())
+}
+
+// Where a post is in its section's `series`, counting from 1, and how many
+// entries the series has. Entries go by `series_index`, the ones without
+// one after them by date.
+pub(crate) fn series_position(
+    post: &PostMetadata,
+    posts: &[PostMetadata],
+) -> Option<(usize, usize)> {
+    let series = extract_metadata(&post.frontmatter, "series")?;
+    let mut entries: Vec<&PostMetadata> = posts
+        .iter()
+        .filter(|other| other.category == post.category)
+        .filter(|other| extract_metadata(&other.frontmatter, "series").as_ref() == Some(&series))
+        .collect();
+    let date = |post: &PostMetadata| NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok();
+    entries.sort_by(|a, b| match (series_index(a), series_index(b)) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (None, None) => date(a).cmp(&date(b)),
+        (a, b) => b.is_some().cmp(&a.is_some()),
+    });
+    let position = entries.iter().position(|entry| entry.url == post.url)?;
+    Some((position + 1, entries.len()))
+}
@@ -0,0 +1,128 @@
+use crate::{hashes::fnv1a, Site, SiteError};
+use anyhow::{Context, Result};
+use regex::{Captures, Regex};
+use std::{
+    fs, io,
+    path::PathBuf,
+    process::Command,
+    sync::LazyLock,
+    time::{Duration, SystemTime},
+};
+
+static DIRECTIVE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"\{\{<\s*remote\s+"([^"]+)"\s*>\}\}"#).unwrap());
+
+// Inline `{{< remote "https://..." >}}` directives with what the URL serves,
+// markdown or HTML. Only with `[remote] enabled = true`. Copies are cached by
+// URL for `cache_hours`, and a stale copy stands in when fetching fails, with
+// a warning. Without any copy the build fails.
+pub(crate) fn expand_remote(site: &Site, markdown: &str) -> Result<(String, Vec<String>)> {
+    let mut warnings = vec![];
+    let mut error = None;
+    let expanded = DIRECTIVE.replace_all(markdown, |captures: &Captures| {
+        match remote_content(site, &captures[1], &mut warnings) {
+            Ok(content) => content,
+            Err(err) => {
+                error.get_or_insert(err);
+                String::new()
+            }
+        }
+    });
+    match error {
+        Some(err) => Err(err),
+        None => Ok((expanded.into_owned(), warnings)),
+    }
+}
+
+fn remote_content(site: &Site, url: &str, warnings: &mut Vec<String>) -> Result<String> {
+    let config = &site.config.remote;
+    if !config.enabled {
+        anyhow::bail!(
+            "`{{{{< remote \"{}\" >}}}}` needs `enabled = true` under `[remote]` in site.toml",
+            url
+        );
+    }
+    // Anything else reads local files or passes the "URL" to curl as an option
+    let scheme = url
+        .split_once("://")
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    if !matches!(scheme.as_deref(), Some("http" | "https")) {
+        anyhow::bail!(
+            "`{{{{< remote \"{}\" >}}}}` isn't an http:// or https:// URL",
+            url
+        );
+    }
+
+    let cached = cache_path(site, url);
+    let age = fs::metadata(&cached)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    if age.is_some_and(|age| age < Duration::from_secs(config.cache_hours * 3600)) {
+        return fs::read_to_string(&cached)
+            .with_context(|| format!("Failed to read cached {}", cached.display()));
+    }
+
+    match fetch(url, config.timeout_secs) {
+        Ok(content) => {
+            if let Some(parent) = cached.parent() {
+                fs::create_dir_all(parent).context("Failed to create the remote cache")?;
+            }
+            fs::write(&cached, &content).with_context(|| format!("Failed to cache {}", url))?;
+            Ok(content)
+        }
+        Err(err) if age.is_some() => {
+            warnings.push(format!(
+                "couldn't fetch {} ({:#}), used the copy cached in {}",
+                url,
+                err,
+                cached.display()
+            ));
+            fs::read_to_string(&cached)
+                .with_context(|| format!("Failed to read cached {}", cached.display()))
+        }
+        Err(err) => Err(err.context(format!(
+            "Failed to fetch {} and there's no cached copy",
+            url
+        ))),
+    }
+}
+
+fn cache_path(site: &Site, url: &str) -> PathBuf {
+    site.config
+        .remote
+        .cache_dir
+        .join(format!("{:016x}", fnv1a(url.as_bytes())))
+}
+
+// curl is on every CI image and handles redirects, TLS and proxies
+fn fetch(url: &str, timeout_secs: u64) -> Result<String> {
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--silent",
+            "--show-error",
+            "--location",
+            "--proto",
+            "=http,https",
+            "--proto-redir",
+            "=http,https",
+            "--max-time",
+        ])
+        .arg(timeout_secs.to_string())
+        .arg("--")
+        .arg(url)
+        .output()
+        .map_err(|source| match source.kind() {
+            io::ErrorKind::NotFound => anyhow::Error::new(SiteError::MissingProgram {
+                program: "curl".to_string(),
+                needed_for: "`{{< remote >}}`".to_string(),
+                source,
+            }),
+            _ => anyhow::Error::new(source).context("Failed to run curl"),
+        })?;
+    if !output.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    String::from_utf8(output.stdout).context("Remote content isn't UTF-8")
+}
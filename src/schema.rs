@@ -0,0 +1,80 @@
+use crate::{config::FrontmatterType, Site, SiteError};
+use anyhow::Result;
+use serde_yaml::{Mapping, Value};
+use std::path::Path;
+
+// Fill in the configured defaults for keys a file leaves out, then convert
+// values to their configured types. Runs after aliases are resolved, so
+// defaults and types use the current key names.
+pub(crate) fn apply_schema(site: &Site, src_path: &Path, frontmatter: &mut Mapping) -> Result<()> {
+    let config = &site.config;
+    for (key, default) in &config.frontmatter_defaults {
+        if !frontmatter.contains_key(key.as_str()) {
+            frontmatter.insert(Value::from(key.as_str()), default.clone());
+        }
+    }
+
+    for (key, kind) in &config.frontmatter_types {
+        let Some(value) = frontmatter.get_mut(key.as_str()) else {
+            continue;
+        };
+        let Some(coerced) = coerce(value, *kind) else {
+            return Err(SiteError::Validation {
+                path: src_path.to_path_buf(),
+                message: format!(
+                    "Frontmatter `{}` in {} should be {}, found {}",
+                    key,
+                    src_path.display(),
+                    describe(*kind),
+                    serde_yaml::to_string(value)?.trim_end()
+                ),
+            }
+            .into());
+        };
+        *value = coerced;
+    }
+    Ok(())
+}
+
+fn coerce(value: &Value, kind: FrontmatterType) -> Option<Value> {
+    match (kind, value) {
+        (FrontmatterType::String, Value::String(_)) => Some(value.clone()),
+        (FrontmatterType::String, Value::Number(number)) => Some(Value::from(number.to_string())),
+        (FrontmatterType::String, Value::Bool(flag)) => Some(Value::from(flag.to_string())),
+        (FrontmatterType::Integer, Value::Number(number)) => number.as_i64().map(Value::from),
+        (FrontmatterType::Integer, Value::String(text)) => {
+            text.trim().parse::<i64>().ok().map(Value::from)
+        }
+        (FrontmatterType::Float, Value::Number(number)) => number.as_f64().map(Value::from),
+        (FrontmatterType::Float, Value::String(text)) => {
+            text.trim().parse::<f64>().ok().map(Value::from)
+        }
+        (FrontmatterType::Boolean, Value::Bool(_)) => Some(value.clone()),
+        (FrontmatterType::Boolean, Value::String(text)) => match text.trim() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        (FrontmatterType::List, Value::Sequence(_)) => Some(value.clone()),
+        // `tags: rust, web` like the built-in list keys accept
+        (FrontmatterType::List, Value::String(text)) => Some(Value::Sequence(
+            text.split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(Value::from)
+                .collect(),
+        )),
+        (FrontmatterType::List, Value::Null) => Some(Value::Sequence(vec![])),
+        _ => None,
+    }
+}
+
+fn describe(kind: FrontmatterType) -> &'static str {
+    match kind {
+        FrontmatterType::String => "a string",
+        FrontmatterType::Integer => "an integer",
+        FrontmatterType::Float => "a number",
+        FrontmatterType::Boolean => "true or false",
+        FrontmatterType::List => "a list",
+    }
+}
@@ -0,0 +1,126 @@
+use crate::escape_xml;
+use anyhow::{anyhow, Result};
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+    thread,
+};
+use tiny_http::{Header, Response, Server};
+use walkdir::WalkDir;
+
+// Index of every file in `dist`, generated on each request and never written
+const PAGE_INDEX_PATH: &str = "/__sitemap";
+
+// Serve `dist_dir` over HTTP from a background thread under `base_path` and
+// return the URL the site is reachable at. Use port 0 in `address` to pick any
+// free port.
+pub fn serve(dist_dir: impl Into<PathBuf>, address: &str, base_path: &str) -> Result<String> {
+    let dist_dir = dist_dir.into();
+    let server =
+        Server::http(address).map_err(|err| anyhow!("Failed to listen on {}: {}", address, err))?;
+    let base_path = match base_path.trim_matches('/') {
+        "" => String::new(),
+        path => format!("/{}", path),
+    };
+    let url = format!("http://{}{}/", server.server_addr(), base_path);
+
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let path = request.url().strip_prefix(&base_path);
+            if path.is_some_and(|path| path.split(['?', '#']).next() == Some(PAGE_INDEX_PATH)) {
+                let index = page_index(&dist_dir, &base_path);
+                let response =
+                    Response::from_string(index).with_header(content_type(Path::new("index.html")));
+                if let Err(err) = request.respond(response) {
+                    eprintln!("Failed to send response: {}", err);
+                }
+                continue;
+            }
+            let response = match path.and_then(|path| resolve_path(&dist_dir, path)) {
+                Some(path) => match fs::read(&path) {
+                    Ok(body) => Response::from_data(body).with_header(content_type(&path)),
+                    Err(_) => not_found(),
+                },
+                None => not_found(),
+            };
+            if let Err(err) = request.respond(response) {
+                eprintln!("Failed to send response: {}", err);
+            }
+        }
+    });
+    Ok(url)
+}
+
+// Map a request path onto a file the way static hosts do: directories serve
+// their `index.html` and extensionless paths fall back to `.html`
+fn resolve_path(dist_dir: &Path, url: &str) -> Option<PathBuf> {
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    let relative = Path::new(path.trim_start_matches('/'));
+    if !relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        return None;
+    }
+
+    let path = dist_dir.join(relative);
+    if path.is_dir() {
+        return Some(path.join("index.html")).filter(|index| index.is_file());
+    }
+    if path.is_file() {
+        return Some(path);
+    }
+    Some(path.with_extension("html")).filter(|page| page.is_file())
+}
+
+// A page linking every file under `dist_dir`, sorted by path
+fn page_index(dist_dir: &Path, base_path: &str) -> String {
+    let mut files: Vec<String> = WalkDir::new(dist_dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative = entry.path().strip_prefix(dist_dir).ok()?;
+            Some(relative.to_string_lossy().replace('\\', "/"))
+        })
+        .collect();
+    files.sort();
+
+    let mut index = String::from(
+        "<!DOCTYPE html>\n<html>\n<head><title>Generated files</title></head>\n<body>\n\
+         <h1>Generated files</h1>\n<ul>\n",
+    );
+    for file in &files {
+        index.push_str(&format!(
+            "<li><a href=\"{}/{}\">{}</a></li>\n",
+            base_path,
+            escape_xml(file),
+            escape_xml(file)
+        ));
+    }
+    index.push_str("</ul>\n</body>\n</html>\n");
+    index
+}
+
+fn content_type(path: &Path) -> Header {
+    let mime = match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") => "text/html; charset=utf-8",
+        Some("css") => "text/css; charset=utf-8",
+        Some("js") => "text/javascript; charset=utf-8",
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("ico") => "image/x-icon",
+        _ => "application/octet-stream",
+    };
+    Header::from_bytes("Content-Type", mime).unwrap()
+}
+
+fn not_found() -> Response<std::io::Cursor<Vec<u8>>> {
+    Response::from_string("Not found").with_status_code(404)
+}
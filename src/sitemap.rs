@@ -0,0 +1,58 @@
+use crate::{
+    escape_xml,
+    pages::{listing_page_count, listing_path, split_at_cutoff},
+    PostMetadata, Site,
+};
+use anyhow::{Context, Result};
+use std::fs;
+
+pub(crate) fn generate_sitemap(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let config = &site.config;
+
+    // Sitemaps need absolute URLs, there's nothing sensible to write without a base URL
+    if config.base_url.is_empty() {
+        return Ok(());
+    }
+
+    let homepage = config.site_url("/");
+    let mut urls = vec![homepage.clone()];
+    for (name, section) in &config.sections {
+        let section_posts: Vec<&PostMetadata> = posts
+            .iter()
+            .filter(|post| post.category.as_ref() == Some(name))
+            .collect();
+        let (listed, _) = split_at_cutoff(site, &section_posts);
+        // Pages after the first are left out when they're `noindex`
+        let count = if config.noindex_pagination {
+            1
+        } else {
+            listing_page_count(section, listed.len())
+        };
+        // A listing on the homepage is already there as `/`
+        urls.extend(
+            (1..=count)
+                .map(|number| config.site_url(&listing_path(config, name, section, number)))
+                .filter(|url| *url != homepage),
+        );
+    }
+    urls.extend(
+        posts
+            .iter()
+            // Cross-posts are indexed at their original URL
+            .filter(|post| !post.noindex && post.canonical_url.is_none())
+            .map(|post| post.url.clone()),
+    );
+
+    let mut sitemap = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for url in urls {
+        let loc = config.normalize_link(&config.absolute_url(&url));
+        sitemap.push_str(&format!("  <url><loc>{}</loc></url>\n", escape_xml(&loc)));
+    }
+    sitemap.push_str("</urlset>\n");
+
+    fs::write(site.dist_dir.join("sitemap.xml"), sitemap).context("Failed to write sitemap")?;
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::{config::SlugCollision, Site, SiteError};
+use anyhow::Result;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+// Slugs for content files that would otherwise be written to the same page as
+// another, like `post.md` next to `post.html`. The first in file name order
+// keeps its slug. Names are compared case insensitively, as some hosts do.
+pub(crate) fn resolve_slugs(site: &Site, files: &[PathBuf]) -> Result<HashMap<PathBuf, String>> {
+    let mut pages: HashMap<(PathBuf, String), Vec<&PathBuf>> = HashMap::new();
+    for path in files {
+        if let Some(stem) = stem(path) {
+            let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+            pages
+                .entry((dir, stem.to_lowercase()))
+                .or_default()
+                .push(path);
+        }
+    }
+    let taken: HashSet<(PathBuf, String)> = pages.keys().cloned().collect();
+
+    let mut slugs = HashMap::new();
+    let mut colliding: Vec<(&(PathBuf, String), &Vec<&PathBuf>)> =
+        pages.iter().filter(|(_, paths)| paths.len() > 1).collect();
+    // Suffixes are handed out in the same order every build
+    colliding.sort_by_key(|(key, _)| *key);
+    let mut assigned: HashSet<(PathBuf, String)> = HashSet::new();
+    for ((dir, _), paths) in colliding {
+        let first = paths[0];
+        for path in &paths[1..] {
+            let stem = stem(path).unwrap_or_default();
+            let slug = match site.config.on_slug_collision {
+                SlugCollision::Error => {
+                    return Err(SiteError::Validation {
+                        path: path.to_path_buf(),
+                        message: format!(
+                            "{} and {} would both be written to the same page, rename one or \
+                             set `on_slug_collision`",
+                            first.display(),
+                            path.display()
+                        ),
+                    }
+                    .into());
+                }
+                SlugCollision::Suffix => (2..)
+                    .map(|number| format!("{}-{}", stem, number))
+                    .find(|slug| {
+                        let key = (dir.clone(), slug.to_lowercase());
+                        !taken.contains(&key) && !assigned.contains(&key)
+                    })
+                    .unwrap_or_default(),
+                // The whole file name, `post.md` is `post-md`
+                SlugCollision::Path => {
+                    let name = path.file_name().unwrap_or_default().to_string_lossy();
+                    name.replace('.', "-")
+                }
+            };
+            assigned.insert((dir.clone(), slug.to_lowercase()));
+            slugs.insert(path.to_path_buf(), slug);
+        }
+    }
+    Ok(slugs)
+}
+
+fn stem(path: &Path) -> Option<&str> {
+    path.file_stem().and_then(|stem| stem.to_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::resolve_slugs;
+    use crate::{
+        config::{Config, SlugCollision},
+        Site,
+    };
+    use std::path::PathBuf;
+
+    fn resolve(on_slug_collision: SlugCollision, files: &[&str]) -> Vec<(String, String)> {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            on_slug_collision,
+            ..Config::default()
+        };
+        let site = Site::new(config, "tests/fixtures/site", dist.path()).unwrap();
+        let files: Vec<PathBuf> = files.iter().map(PathBuf::from).collect();
+        let mut slugs: Vec<_> = resolve_slugs(&site, &files)
+            .unwrap()
+            .into_iter()
+            .map(|(path, slug)| (path.to_string_lossy().into_owned(), slug))
+            .collect();
+        slugs.sort();
+        slugs
+    }
+
+    #[test]
+    fn suffixes_skip_slugs_already_taken() {
+        let slugs = resolve(
+            SlugCollision::Suffix,
+            &[
+                "blog/Post.md",
+                "blog/post-2.md",
+                "blog/post.html",
+                "blog/post.txt",
+                "notes/post.md",
+            ],
+        );
+        assert_eq!(
+            slugs,
+            [
+                ("blog/post.html".to_string(), "post-3".to_string()),
+                ("blog/post.txt".to_string(), "post-4".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn spells_out_the_file_name() {
+        let slugs = resolve(SlugCollision::Path, &["post.html", "post.md"]);
+        assert_eq!(slugs, [("post.md".to_string(), "post-md".to_string())]);
+    }
+
+    #[test]
+    fn fails_on_collisions_by_default() {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(Config::default(), "tests/fixtures/site", dist.path()).unwrap();
+        let files = [PathBuf::from("post.html"), PathBuf::from("POST.md")];
+        assert!(resolve_slugs(&site, &files).is_err());
+        assert!(resolve_slugs(&site, &files[..1]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{
+    content::{PostMetadata, DATE_FORMAT},
+    Site,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+
+// A published post that hasn't been touched in `stale_after_days`
+#[derive(Debug, Serialize)]
+pub struct StalePost {
+    pub url: String,
+    pub title: String,
+    // Its `updated` date, or `date` when it was never revised
+    pub last_touched: String,
+    pub age_days: i64,
+}
+
+// The `stale_posts_listed` section posts whose last `updated` or `date` is
+// the longest ago, oldest first, of those older than `stale_after_days`.
+// Standalone pages and posts without a date are left out.
+pub(crate) fn stale_posts(site: &Site, posts: &[PostMetadata]) -> Vec<StalePost> {
+    let Some(days) = site.config.stale_after_days else {
+        return vec![];
+    };
+    let mut stale: Vec<StalePost> = posts
+        .iter()
+        .filter(|post| post.category.is_some())
+        .filter_map(|post| {
+            let last_touched = post.updated.as_deref().unwrap_or(&post.date);
+            let date = NaiveDate::parse_from_str(last_touched, DATE_FORMAT).ok()?;
+            let age_days = (site.build_date() - date).num_days();
+            (age_days > days).then(|| StalePost {
+                url: post.url.clone(),
+                title: post.title.clone(),
+                last_touched: last_touched.to_string(),
+                age_days,
+            })
+        })
+        .collect();
+    stale.sort_by(|a, b| b.age_days.cmp(&a.age_days).then_with(|| a.url.cmp(&b.url)));
+    stale.truncate(site.config.stale_posts_listed);
+    stale
+}
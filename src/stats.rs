@@ -0,0 +1,113 @@
+use crate::Site;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+};
+
+// How many of the most frequent words `stats.json` lists
+const TOP_WORDS: usize = 50;
+
+// Common English and Turkish words that would otherwise top the list
+const STOPWORDS: &[&str] = &[
+    "about", "after", "again", "all", "also", "and", "any", "are", "because", "been", "before",
+    "being", "but", "can", "could", "did", "does", "doing", "down", "each", "for", "from", "had",
+    "has", "have", "her", "here", "him", "his", "how", "into", "its", "just", "more", "most",
+    "not", "now", "off", "once", "one", "only", "other", "our", "out", "over", "own", "same",
+    "she", "should", "some", "such", "than", "that", "the", "their", "them", "then", "there",
+    "these", "they", "this", "those", "through", "too", "under", "until", "very", "was", "were",
+    "what", "when", "where", "which", "while", "who", "why", "will", "with", "would", "you",
+    "your", "acaba", "ama", "ancak", "artık", "aslında", "bana", "bazı", "belki", "ben", "beni",
+    "benim", "bir", "biri", "birkaç", "biz", "bize", "bizim", "böyle", "buna", "bunu", "bunun",
+    "çok", "çünkü", "daha", "dahi", "değil", "diğer", "diye", "gibi", "göre", "hem", "hep", "her",
+    "hiç", "için", "ile", "ise", "kadar", "kendi", "kim", "nasıl", "neden", "nerede", "niçin",
+    "olan", "olarak", "oldu", "olduğu", "olmak", "ona", "onu", "onun", "sen", "sana", "seni",
+    "siz", "şey", "şimdi", "tüm", "veya", "yani", "yine", "zaten",
+];
+
+// Word counts gathered from the plain text of every built page
+#[derive(Debug, Default)]
+pub(crate) struct ContentStats {
+    posts: usize,
+    words: usize,
+    reading_minutes: usize,
+    category_words: BTreeMap<String, usize>,
+    frequencies: HashMap<String, usize>,
+}
+
+#[derive(Serialize)]
+struct StatsFile<'a> {
+    posts: usize,
+    total_words: usize,
+    category_words: &'a BTreeMap<String, usize>,
+    average_reading_time: f64,
+    top_words: Vec<WordCount<'a>>,
+}
+
+#[derive(Serialize)]
+struct WordCount<'a> {
+    word: &'a str,
+    count: usize,
+}
+
+impl ContentStats {
+    // Count a page's words, standalone pages are under `standalone`. Apostrophes
+    // split words, so Turkish suffixes like `Rust'ın` count towards `rust`.
+    pub(crate) fn add(&mut self, category: Option<&str>, text: &str, reading_minutes: usize) {
+        let words: Vec<String> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+            // `İ` would otherwise lowercase to `i` plus a combining dot
+            .map(|word| word.replace('İ', "i").to_lowercase())
+            .collect();
+        self.posts += 1;
+        self.words += words.len();
+        self.reading_minutes += reading_minutes;
+        *self
+            .category_words
+            .entry(category.unwrap_or("standalone").to_string())
+            .or_default() += words.len();
+        for word in words {
+            // Numbers and two letter words say little about the content
+            if word.chars().count() < 3
+                || word.chars().all(|c| c.is_numeric())
+                || STOPWORDS.contains(&word.as_str())
+            {
+                continue;
+            }
+            *self.frequencies.entry(word).or_default() += 1;
+        }
+    }
+}
+
+// Write `stats.json` with word counts, the average reading time and the most
+// frequent words, ties by word so the file is the same from build to build
+pub(crate) fn write_stats(site: &Site, stats: &ContentStats) -> Result<()> {
+    let mut top_words: Vec<WordCount> = stats
+        .frequencies
+        .iter()
+        .map(|(word, count)| WordCount {
+            word,
+            count: *count,
+        })
+        .collect();
+    top_words.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(b.word)));
+    top_words.truncate(TOP_WORDS);
+
+    let average_reading_time = if stats.posts == 0 {
+        0.0
+    } else {
+        stats.reading_minutes as f64 / stats.posts as f64
+    };
+    let file = StatsFile {
+        posts: stats.posts,
+        total_words: stats.words,
+        category_words: &stats.category_words,
+        average_reading_time,
+        top_words,
+    };
+    let json = serde_json::to_string_pretty(&file).context("Failed to serialize stats")?;
+    fs::write(site.dist_dir.join("stats.json"), json).context("Failed to write stats.json")?;
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use std::collections::HashSet;
+use tera::{
+    ast::{Expr, ExprVal, LogicOperator, Node},
+    Context, Tera,
+};
+
+// Variables a template tests with `{% if %}` that the context doesn't have.
+// Tera already fails on undefined variables it prints, but takes them as
+// false in conditions, so a page can quietly lose a block. Variables checked
+// with `is defined` or given a `default` are meant to be optional, and macros
+// only see their arguments, so they're left alone.
+pub(crate) fn undefined_variables(tera: &Tera, name: &str, context: &Context) -> Vec<String> {
+    let Ok(template) = tera.get_template(name) else {
+        return vec![];
+    };
+    // Rendering starts at the template everything else extends
+    let root = template
+        .parents
+        .last()
+        .and_then(|parent| tera.get_template(parent).ok())
+        .unwrap_or(template);
+    let mut walker = Walker {
+        tera,
+        template,
+        context,
+        undefined: vec![],
+    };
+    walker.nodes(&root.ast, &mut HashSet::new());
+    walker.undefined
+}
+
+struct Walker<'a> {
+    tera: &'a Tera,
+    // The template being rendered, whose blocks replace its parents'
+    template: &'a tera::Template,
+    context: &'a Context,
+    undefined: Vec<String>,
+}
+
+impl Walker<'_> {
+    fn nodes(&mut self, nodes: &[Node], locals: &mut HashSet<String>) {
+        for node in nodes {
+            match node {
+                Node::Set(_, set) => {
+                    locals.insert(set.key.clone());
+                }
+                Node::Block(_, block, _) => {
+                    let body = self
+                        .template
+                        .blocks_definitions
+                        .get(&block.name)
+                        .and_then(|definitions| definitions.first())
+                        .map_or(&block.body, |(_, block)| &block.body);
+                    self.nodes(body, &mut locals.clone());
+                }
+                Node::Forloop(_, forloop, _) => {
+                    let mut scope = locals.clone();
+                    scope.insert("loop".to_string());
+                    scope.insert(forloop.value.clone());
+                    scope.extend(forloop.key.clone());
+                    self.nodes(&forloop.body, &mut scope);
+                    if let Some(empty_body) = &forloop.empty_body {
+                        self.nodes(empty_body, &mut locals.clone());
+                    }
+                }
+                Node::If(condition, _) => {
+                    for (_, expr, body) in &condition.conditions {
+                        let mut scope = locals.clone();
+                        self.expr(expr, &mut scope);
+                        self.nodes(body, &mut scope);
+                    }
+                    if let Some((_, body)) = &condition.otherwise {
+                        self.nodes(body, &mut locals.clone());
+                    }
+                }
+                Node::FilterSection(_, section, _) => self.nodes(&section.body, locals),
+                Node::Include(_, names, _) => {
+                    let tera = self.tera;
+                    if let Some(included) =
+                        names.iter().find_map(|name| tera.get_template(name).ok())
+                    {
+                        self.nodes(&included.ast, locals);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Check the variables of a condition. Ones it tests with `is defined`
+    // count as defined for the rest of it and the body it guards.
+    fn expr(&mut self, expr: &Expr, locals: &mut HashSet<String>) {
+        if expr.filters.iter().any(|filter| filter.name == "default") {
+            return;
+        }
+        match &expr.val {
+            ExprVal::Ident(ident) => {
+                let name = ident.split(['.', '[']).next().unwrap_or(ident);
+                let known = locals.contains(name) || self.context.contains_key(name);
+                if !known && !self.undefined.iter().any(|undefined| undefined == name) {
+                    self.undefined.push(name.to_string());
+                }
+            }
+            ExprVal::Test(test) if test.name == "defined" || test.name == "undefined" => {
+                let name = test.ident.split(['.', '[']).next().unwrap_or(&test.ident);
+                locals.insert(name.to_string());
+            }
+            ExprVal::Logic(logic) => {
+                self.expr(&logic.lhs, locals);
+                // Only `and` guards what comes after it
+                if logic.operator == LogicOperator::And {
+                    self.expr(&logic.rhs, locals);
+                } else {
+                    self.expr(&logic.rhs, &mut locals.clone());
+                }
+            }
+            ExprVal::Math(math) => {
+                self.expr(&math.lhs, locals);
+                self.expr(&math.rhs, locals);
+            }
+            ExprVal::In(expr) => {
+                self.expr(&expr.lhs, locals);
+                self.expr(&expr.rhs, locals);
+            }
+            _ => {}
+        }
+    }
+}
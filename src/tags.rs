@@ -0,0 +1,195 @@
+use crate::{
+    content::DATE_FORMAT,
+    feeds::{generate_tag_feed, tag_feeds},
+    pages::base_context,
+    urls::slugify,
+    PostMetadata, Site,
+};
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::{cmp::Reverse, collections::BTreeMap, fs};
+
+pub(crate) const TAG_TEMPLATE: &str = "tag.html";
+pub(crate) const TAGS_TEMPLATE: &str = "tags.html";
+
+#[derive(Serialize)]
+struct Tag<'a> {
+    name: &'a str,
+    slug: String,
+    url: String,
+    posts: Vec<&'a PostMetadata>,
+}
+
+// Posts grouped by the slug of their tags, so `Rust` and `rust` share a page.
+// Tags are ordered by slug and their posts newest first, keeping the output
+// the same from one build to the next.
+fn collect_tags<'a>(site: &Site, posts: &'a [PostMetadata]) -> Vec<Tag<'a>> {
+    let mut tags: BTreeMap<String, Tag> = BTreeMap::new();
+    for post in posts {
+        for name in &post.tags {
+            let slug = slugify(name);
+            if slug.is_empty() {
+                continue;
+            }
+            tags.entry(slug.clone())
+                .or_insert_with(|| Tag {
+                    name,
+                    url: site.config.site_url(&tag_path(site, &slug)),
+                    slug,
+                    posts: vec![],
+                })
+                .posts
+                .push(post);
+        }
+    }
+
+    let mut tags: Vec<Tag> = tags.into_values().collect();
+    for tag in &mut tags {
+        // Undated posts go last, ties keep the file name order
+        tag.posts
+            .sort_by_key(|post| Reverse(NaiveDate::parse_from_str(&post.date, DATE_FORMAT).ok()));
+    }
+    tags
+}
+
+// Site path of a tag's page, without the base path. With `clean_urls` it's
+// the directory, written as its `index.html`.
+pub(crate) fn tag_path(site: &Site, slug: &str) -> String {
+    if site.config.clean_urls {
+        format!("/tags/{}/", slug)
+    } else {
+        format!("/tags/{}.html", slug)
+    }
+}
+
+// Site path of the page listing every tag, like `tag_path`
+pub(crate) fn tags_index_path(site: &Site) -> String {
+    if site.config.clean_urls {
+        "/tags/".to_string()
+    } else {
+        "/tags.html".to_string()
+    }
+}
+
+// The file under `dist` a page's site path is served from
+pub(crate) fn file_path(path: &str) -> String {
+    if path.ends_with('/') {
+        format!("{}index.html", path)
+    } else {
+        path.to_string()
+    }
+}
+
+fn write_tag_page(site: &Site, path: &str, rendered: String) -> Result<()> {
+    let output = site.dist_dir.join(file_path(path).trim_start_matches('/'));
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).context("Failed to create tags directory")?;
+    }
+    fs::write(&output, rendered).with_context(|| format!("Failed to write {}", output.display()))
+}
+
+// Write a page per tag from the `tag.html` template and an index of all tags
+// from `tags.html`, each only when the site has the template
+pub(crate) fn generate_tag_pages(site: &Site, posts: &[PostMetadata]) -> Result<()> {
+    let tags = collect_tags(site, posts);
+    for tag in &tags {
+        generate_tag_feed(site, tag.name, &tag.slug, &tag.url, &tag.posts)?;
+    }
+    if site.has_template(TAG_TEMPLATE) && !tags.is_empty() {
+        for tag in &tags {
+            let path = tag_path(site, &tag.slug);
+            let mut context = base_context(site, &path, None);
+            let feeds = tag_feeds(site, tag.name, &tag.slug);
+            if !feeds.is_empty() {
+                context.insert("feeds", &feeds);
+            }
+            context.insert("title", tag.name);
+            context.insert("tag", tag);
+            context.insert("posts", &tag.posts);
+            let rendered = site
+                .render(TAG_TEMPLATE, &context)
+                .context("Failed to render tag template")?;
+            write_tag_page(site, &path, rendered)?;
+        }
+    }
+
+    if site.has_template(TAGS_TEMPLATE) {
+        let path = tags_index_path(site);
+        let mut context = base_context(site, &path, None);
+        context.insert("title", "Tags");
+        context.insert("tags", &tags);
+        let rendered = site
+            .render(TAGS_TEMPLATE, &context)
+            .context("Failed to render tags template")?;
+        write_tag_page(site, &path, rendered)?;
+    }
+    write_tags_json(site, &tags)?;
+    if site.config.tags_full_json {
+        write_tags_full_json(site, &tags)?;
+    }
+    Ok(())
+}
+
+// A tag in `tags.json`, with the URL of its page when the site has tag pages
+#[derive(Serialize)]
+struct TagSummary<'a> {
+    name: &'a str,
+    slug: &'a str,
+    count: usize,
+    url: Option<&'a str>,
+}
+
+// Write every tag with its post count to `tags.json`, the most used first and
+// ties by name, for tag filters and autocompletion
+fn write_tags_json(site: &Site, tags: &[Tag]) -> Result<()> {
+    let tag_pages = site.has_template(TAG_TEMPLATE);
+    let mut summaries: Vec<TagSummary> = tags
+        .iter()
+        .map(|tag| TagSummary {
+            name: tag.name,
+            slug: &tag.slug,
+            count: tag.posts.len(),
+            url: tag_pages.then_some(tag.url.as_str()),
+        })
+        .collect();
+    summaries.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(b.name)));
+
+    let json = serde_json::to_string_pretty(&summaries).context("Failed to serialize tags")?;
+    fs::write(site.dist_dir.join("tags.json"), json).context("Failed to write tags.json")?;
+    Ok(())
+}
+
+// A post under its tag in `tags-full.json`
+#[derive(Serialize)]
+struct TaggedPost<'a> {
+    slug: &'a str,
+    title: &'a str,
+    date: &'a str,
+    url: &'a str,
+}
+
+// Write `tags-full.json`, each tag's slug mapped to all of its posts, in the
+// order of its tag page, so a tag explorer needs a single request
+fn write_tags_full_json(site: &Site, tags: &[Tag]) -> Result<()> {
+    let tags: BTreeMap<&str, Vec<TaggedPost>> = tags
+        .iter()
+        .map(|tag| {
+            let posts = tag
+                .posts
+                .iter()
+                .map(|post| TaggedPost {
+                    slug: &post.slug,
+                    title: &post.title,
+                    date: &post.date,
+                    url: &post.url,
+                })
+                .collect();
+            (tag.slug.as_str(), posts)
+        })
+        .collect();
+    let json = serde_json::to_string_pretty(&tags).context("Failed to serialize tags")?;
+    fs::write(site.dist_dir.join("tags-full.json"), json)
+        .context("Failed to write tags-full.json")?;
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::is_html;
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::{fs, path::Path, sync::LazyLock};
+use walkdir::WalkDir;
+
+static PREFORMATTED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)<(/?)(?:pre|code)\b").unwrap());
+
+// Strip trailing whitespace and collapse runs of three or more blank lines to
+// one in every HTML file under dist, short of minifying it
+pub(crate) fn tidy_dist(dist_dir: &Path) -> Result<()> {
+    for entry in WalkDir::new(dist_dir).into_iter().filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !entry.file_type().is_file() || !is_html(path) {
+            continue;
+        }
+        let html = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let tidied = tidy_html(&html);
+        if tidied != html {
+            fs::write(path, tidied)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+// Lines inside `<pre>` and `<code>` are kept as they are, whitespace there is
+// part of the content. Blank lines at the very end are dropped.
+fn tidy_html(html: &str) -> String {
+    let mut tidied = String::with_capacity(html.len());
+    let mut depth = 0usize;
+    let mut blank_lines = 0;
+    for line in html.split_inclusive('\n') {
+        let starts_inside = depth > 0;
+        for captures in PREFORMATTED.captures_iter(line) {
+            if captures[1].is_empty() {
+                depth += 1;
+            } else {
+                depth = depth.saturating_sub(1);
+            }
+        }
+        let line = if starts_inside || depth > 0 {
+            line
+        } else {
+            line.trim_end()
+        };
+        if line.is_empty() {
+            blank_lines += 1;
+            continue;
+        }
+
+        let kept = if blank_lines >= 3 { 1 } else { blank_lines };
+        tidied.push_str(&"\n".repeat(kept));
+        blank_lines = 0;
+        tidied.push_str(line);
+        if !line.ends_with('\n') {
+            tidied.push('\n');
+        }
+    }
+    tidied
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tidy_html;
+
+    #[test]
+    fn collapses_blank_lines_and_trailing_whitespace() {
+        assert_eq!(
+            tidy_html("<p>One</p>   \n\n\n\n<p>Two</p>\n\n<p>Three</p>\t\n\n\n"),
+            "<p>One</p>\n\n<p>Two</p>\n\n<p>Three</p>\n"
+        );
+    }
+
+    #[test]
+    fn keeps_preformatted_lines() {
+        let html = "<PRE>keep   \n\n\n\nthis</PRE>\n<pre><code>a  \n</code>  \nb</pre>\n";
+        assert_eq!(tidy_html(html), html);
+        assert_eq!(tidy_html("<pre>a</pre>   \n"), "<pre>a</pre>\n");
+    }
+}
@@ -0,0 +1,198 @@
+use crate::config::TrailingSlash;
+use std::collections::HashMap;
+use tera::{Result as TeraResult, Value};
+
+// Apply the configured trailing slash policy to an internal link. External
+// links, fragments and links to files (`/feed.xml`, `/blog.html`) are kept as is.
+pub(crate) fn normalize_link(policy: TrailingSlash, base_url: &str, link: &str) -> String {
+    if policy == TrailingSlash::Preserve {
+        return link.to_string();
+    }
+
+    // Absolute links to our own site are internal too
+    let base_url = base_url.trim_end_matches('/');
+    let (origin, link) = match link.strip_prefix(base_url) {
+        Some(rest) if !base_url.is_empty() && (rest.is_empty() || rest.starts_with('/')) => {
+            (base_url, rest)
+        }
+        _ if is_external(link) => return link.to_string(),
+        _ => ("", link),
+    };
+
+    let split = link.find(['?', '#']).unwrap_or(link.len());
+    let (path, suffix) = link.split_at(split);
+    let last_segment = path.rsplit('/').next().unwrap_or_default();
+    if last_segment.contains('.') || (path.is_empty() && origin.is_empty()) {
+        return format!("{}{}", origin, link);
+    }
+
+    let path = match policy {
+        TrailingSlash::Always if !path.ends_with('/') => format!("{}/", path),
+        TrailingSlash::Never if path.len() > 1 => path.trim_end_matches('/').to_string(),
+        _ => path.to_string(),
+    };
+    format!("{}{}{}", origin, path, suffix)
+}
+
+pub(crate) fn is_external(link: &str) -> bool {
+    if link.starts_with("//") {
+        return true;
+    }
+    // Anything with a scheme (`https:`, `mailto:`, ...) before the first slash
+    match link.find(':') {
+        Some(colon) => !link[..colon].contains(['/', '?', '#']),
+        None => false,
+    }
+}
+
+// Put site-absolute links (`/assets/...`) under the path the site is served
+// from. External, relative and protocol-relative links are left alone.
+pub(crate) fn join_base_path(base_path: &str, link: &str) -> String {
+    let base_path = base_path.trim_matches('/');
+    if base_path.is_empty() || !link.starts_with('/') || link.starts_with("//") {
+        return link.to_string();
+    }
+    format!("/{}{}", base_path, link)
+}
+
+// `{{ "/assets/styles.css" | url }}` for internal links written in templates,
+// joins the base path and applies the trailing slash policy
+pub(crate) fn url_filter(
+    policy: TrailingSlash,
+    base_url: String,
+    base_path: String,
+) -> impl Fn(&Value, &HashMap<String, Value>) -> TeraResult<Value> {
+    move |value, _| {
+        let link = tera::try_get_value!("url", "value", String, value);
+        let link = join_base_path(&base_path, &link);
+        Ok(Value::String(normalize_link(policy, &base_url, &link)))
+    }
+}
+
+// `{{ "/blog" | normalize_url }}` for links written by hand in templates
+pub(crate) fn normalize_url_filter(
+    policy: TrailingSlash,
+    base_url: String,
+) -> impl Fn(&Value, &HashMap<String, Value>) -> TeraResult<Value> {
+    move |value, _| {
+        let link = tera::try_get_value!("normalize_url", "value", String, value);
+        Ok(Value::String(normalize_link(policy, &base_url, &link)))
+    }
+}
+
+// Whether two hosts are the apex and `www.` forms of the same site
+fn is_host_alias(host: &str, other: &str) -> bool {
+    host.strip_prefix("www.") == Some(other) || other.strip_prefix("www.") == Some(host)
+}
+
+// Swap the host of `base_url` for `canonical_host` when it's the other of the
+// apex and `www.` pair. Anything else, e.g. a local preview URL, is kept.
+pub(crate) fn canonicalize_host(base_url: &str, canonical_host: &str) -> String {
+    let Some((scheme, rest)) = base_url.split_once("://") else {
+        return base_url.to_string();
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    let (authority, path) = rest.split_at(authority_end);
+    let (host, port) = match authority.split_once(':') {
+        Some((host, port)) => (host, format!(":{}", port)),
+        None => (authority, String::new()),
+    };
+    if is_host_alias(host, canonical_host) {
+        format!("{}://{}{}{}", scheme, canonical_host, port, path)
+    } else {
+        base_url.to_string()
+    }
+}
+
+// Lowercase, dash separated form of a title for use in URLs, anchors and tag
+// pages. Turkish letters and common accented ones are spelled in ASCII, other
+// letters and digits are kept. Titles of only symbols, e.g. emoji, are spelled
+// out as their code points rather than left empty.
+pub(crate) fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars() {
+        // Combining accents of decomposed letters, `e` + `\u{301}` is just `e`
+        if ('\u{300}'..='\u{36f}').contains(&c) {
+            continue;
+        }
+        let transliterated = match transliterate(c) {
+            Some(ascii) => ascii.to_string(),
+            None => c.to_lowercase().collect(),
+        };
+        for c in transliterated.chars() {
+            if c.is_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if !slug.is_empty() {
+        return slug.to_string();
+    }
+
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| format!("u{:x}", c as u32))
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn transliterate(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        // Turkish, where `İ` would otherwise lowercase to `i` plus a combining dot
+        'ç' | 'Ç' => "c",
+        'ğ' | 'Ğ' => "g",
+        'ı' | 'İ' => "i",
+        'ö' | 'Ö' => "o",
+        'ş' | 'Ş' => "s",
+        'ü' | 'Ü' => "u",
+        // Other common diacritics
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' | 'À' | 'Á' | 'Â' | 'Ã' | 'Ä' | 'Å'
+        | 'Ā' | 'Ă' | 'Ą' => "a",
+        'ć' | 'č' | 'Ć' | 'Č' => "c",
+        'ď' | 'đ' | 'Ď' | 'Đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ę' | 'ě' | 'È' | 'É' | 'Ê' | 'Ë' | 'Ē' | 'Ę' | 'Ě' => {
+            "e"
+        }
+        'ì' | 'í' | 'î' | 'ï' | 'ī' | 'Ì' | 'Í' | 'Î' | 'Ï' | 'Ī' => "i",
+        'ł' | 'Ł' => "l",
+        'ñ' | 'ń' | 'ň' | 'Ñ' | 'Ń' | 'Ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ø' | 'ō' | 'ő' | 'Ò' | 'Ó' | 'Ô' | 'Õ' | 'Ø' | 'Ō' | 'Ő' => {
+            "o"
+        }
+        'ř' | 'Ř' => "r",
+        'ś' | 'š' | 'Ś' | 'Š' => "s",
+        'ť' | 'Ť' => "t",
+        'ù' | 'ú' | 'û' | 'ū' | 'ů' | 'ű' | 'Ù' | 'Ú' | 'Û' | 'Ū' | 'Ů' | 'Ű' => "u",
+        'ý' | 'ÿ' | 'Ý' => "y",
+        'ź' | 'ż' | 'ž' | 'Ź' | 'Ż' | 'Ž' => "z",
+        'ß' => "ss",
+        'æ' | 'Æ' => "ae",
+        'œ' | 'Œ' => "oe",
+        _ => return None,
+    };
+    Some(ascii)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::slugify;
+
+    #[test]
+    fn slugifies_titles() {
+        assert_eq!(slugify("Merhaba Dünya"), "merhaba-dunya");
+        assert_eq!(slugify("İstanbul'da Çay Şöleni"), "istanbul-da-cay-soleni");
+        assert_eq!(slugify("Iğdır ılık"), "igdir-ilik");
+        assert_eq!(slugify("Cafe\u{301} Crème Brûlée"), "cafe-creme-brulee");
+        assert_eq!(slugify("  Hello,  World!  "), "hello-world");
+        assert_eq!(slugify("日本語"), "日本語");
+    }
+
+    #[test]
+    fn spells_out_titles_of_only_symbols() {
+        assert_eq!(slugify("🎉🎉"), "u1f389-u1f389");
+        assert_eq!(slugify("🎉 !"), "u1f389-u21");
+    }
+}
@@ -0,0 +1,105 @@
+use html5ever::{
+    tendril::StrTendril,
+    tokenizer::{
+        states::RawKind, BufferQueue, TagKind, Token, TokenSink, TokenSinkResult, Tokenizer,
+        TokenizerOpts,
+    },
+};
+use std::{cell::RefCell, collections::HashSet};
+
+pub(crate) const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+// Closed implicitly by the next sibling or the parent's end tag, so leaving
+// them open is fine
+const OPTIONAL_END_TAGS: &[&str] = &[
+    "body", "colgroup", "dd", "dt", "head", "html", "li", "optgroup", "option", "p", "rp", "rt",
+    "tbody", "td", "tfoot", "th", "thead", "tr",
+];
+
+// Follows the tokens of a page, keeping the elements still open and the ids
+// seen so far. The tokenizer hands the sink shared references, hence the cells.
+#[derive(Default)]
+struct Checker {
+    open: RefCell<Vec<(String, u64)>>,
+    ids: RefCell<HashSet<String>>,
+    problems: RefCell<Vec<String>>,
+}
+
+impl Checker {
+    fn close(&self, elements: impl Iterator<Item = (String, u64)>) {
+        let mut problems = self.problems.borrow_mut();
+        for (name, line) in elements {
+            if !OPTIONAL_END_TAGS.contains(&name.as_str()) {
+                problems.push(format!("an unclosed `<{}>` on line {}", name, line));
+            }
+        }
+    }
+}
+
+impl TokenSink for Checker {
+    type Handle = ();
+
+    fn process_token(&self, token: Token, line: u64) -> TokenSinkResult<()> {
+        let Token::TagToken(tag) = token else {
+            return TokenSinkResult::Continue;
+        };
+        let name = tag.name.to_string();
+        match tag.kind {
+            TagKind::StartTag => {
+                for attribute in tag.attrs.iter().filter(|attr| &*attr.name.local == "id") {
+                    if !self.ids.borrow_mut().insert(attribute.value.to_string()) {
+                        self.problems.borrow_mut().push(format!(
+                            "a duplicate id `{}` on line {}",
+                            attribute.value, line
+                        ));
+                    }
+                }
+                // `<path />` and friends in inline SVG close themselves
+                if !tag.self_closing && !VOID_ELEMENTS.contains(&name.as_str()) {
+                    self.open.borrow_mut().push((name.clone(), line));
+                }
+                // The tokenizer needs telling which elements hold text rather than tags
+                match name.as_str() {
+                    "script" => return TokenSinkResult::RawData(RawKind::ScriptData),
+                    "style" | "iframe" | "noembed" | "noframes" | "xmp" => {
+                        return TokenSinkResult::RawData(RawKind::Rawtext)
+                    }
+                    "textarea" | "title" => return TokenSinkResult::RawData(RawKind::Rcdata),
+                    _ => {}
+                }
+            }
+            TagKind::EndTag => {
+                let mut open = self.open.borrow_mut();
+                match open.iter().rposition(|(open, _)| *open == name) {
+                    // Whatever was opened inside it and not closed yet
+                    Some(index) => self.close(open.drain(index..).skip(1)),
+                    None if !VOID_ELEMENTS.contains(&name.as_str()) => {
+                        self.problems
+                            .borrow_mut()
+                            .push(format!("a stray `</{}>` on line {}", name, line));
+                    }
+                    None => {}
+                }
+            }
+        }
+        TokenSinkResult::Continue
+    }
+
+    fn end(&self) {
+        self.close(self.open.borrow_mut().drain(..));
+    }
+}
+
+// Unclosed tags, end tags without a start and duplicate ids in a rendered
+// page, with the line of the page they're on
+pub(crate) fn validate_html(html: &str) -> Vec<String> {
+    let tokenizer = Tokenizer::new(Checker::default(), TokenizerOpts::default());
+    let input = BufferQueue::default();
+    input.push_back(StrTendril::from_slice(html));
+    let _ = tokenizer.feed(&input);
+    tokenizer.end();
+    tokenizer.sink.problems.take()
+}
@@ -0,0 +1,35 @@
+use anyhow::{Context, Result};
+use chrono::Local;
+use notify::{RecursiveMode, Watcher};
+use std::{path::Path, sync::mpsc, time::Duration};
+
+// Editors tend to emit a burst of events per save, wait for them to settle
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+// Rebuild whenever something under `paths` changes. Build errors are printed
+// and the loop keeps going so a bad save doesn't end the session.
+pub fn watch(paths: &[&Path], mut rebuild: impl FnMut() -> Result<()>) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).context("Failed to start file watcher")?;
+    for path in paths.iter().filter(|path| path.exists()) {
+        watcher
+            .watch(path, RecursiveMode::Recursive)
+            .with_context(|| format!("Failed to watch {}", path.display()))?;
+    }
+
+    println!("Watching for changes, press Ctrl+C to stop");
+    while let Ok(event) = rx.recv() {
+        // Only rebuild for real changes, not the watcher's own errors
+        if event.is_err() {
+            continue;
+        }
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        let time = Local::now().format("%H:%M:%S");
+        match rebuild() {
+            Ok(()) => println!("[{}] Rebuilt site", time),
+            Err(err) => eprintln!("[{}] Rebuild failed: {:#}", time, err),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,4994 @@
+use md_static_site_generator::{
+    build,
+    check::{check, check_config},
+    config::{
+        AuthorLink, AuthorProfile, CommentsProvider, CompressConfig, CompressFormat, CtaConfig,
+        ExcerptFormat, FeedContent, FirstListingPage, ListingGroup, LockedBuild, NavItem,
+        ReadingBucket, SectionConfig, SectionFeatures, SlugCollision, SortOrder, TrailingSlash,
+    },
+    migrate::migrate,
+    orphans::find_orphaned_assets,
+    serve::serve,
+    Config, ExclusionReason, Site, SiteError,
+};
+use std::{
+    fs,
+    io::{Read, Write},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+use tempfile::TempDir;
+use walkdir::WalkDir;
+
+const FIXTURE: &str = "tests/fixtures/site";
+
+fn build_fixture(config: Config) -> TempDir {
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, FIXTURE, dist.path()).unwrap();
+    build(&site).unwrap();
+    dist
+}
+
+// A writable copy of the fixture for tests that need to add sources
+fn copy_fixture() -> TempDir {
+    let source = tempfile::tempdir().unwrap();
+    for entry in WalkDir::new(FIXTURE).into_iter().filter_map(|e| e.ok()) {
+        let dest = source
+            .path()
+            .join(entry.path().strip_prefix(FIXTURE).unwrap());
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(dest).unwrap();
+        } else {
+            fs::copy(entry.path(), dest).unwrap();
+        }
+    }
+    source
+}
+
+fn read(dist: &TempDir, path: &str) -> String {
+    fs::read_to_string(dist.path().join(path))
+        .unwrap_or_else(|err| panic!("Failed to read `{}`: {}", path, err))
+}
+
+#[test]
+fn builds_homepage_with_navigation() {
+    let dist = build_fixture(Config::default());
+    let index = read(&dist, "index.html");
+
+    assert!(index.contains("<h1>Fixture home</h1>"));
+    assert!(index.contains(r#"<a href="/" class="active">Home</a>"#));
+    assert!(index.contains(r#"<a href="/blog.html">Blog</a>"#));
+}
+
+#[test]
+fn builds_section_listings() {
+    let dist = build_fixture(Config::default());
+
+    let blog = read(&dist, "blog.html");
+    assert!(blog.contains(r#"<a href="/blog/hello-world.html">Hello World</a>"#));
+    assert!(blog.contains(r#"<a href="/blog.html" class="active">Blog</a>"#));
+
+    let books = read(&dist, "books.html");
+    assert!(books.contains(r#"<a href="/books/rust-book.html">The Rust Book</a>"#));
+}
+
+#[test]
+fn builds_post_pages() {
+    let dist = build_fixture(Config::default());
+
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post.contains("<title>Hello World</title>"));
+    assert!(post.contains("<time>2024-01-15</time>"));
+    assert!(post.contains(r#"<h2 id="introduction">Introduction</h2>"#));
+    assert!(post.contains("<strong>fixture</strong>"));
+
+    let book = read(&dist, "books/rust-book.html");
+    assert!(book.contains(r#"<img src="/assets/images/cover.svg" />"#));
+}
+
+// Slugs of the posts linked from a listing, in order
+fn listed_slugs(listing: &str) -> Vec<&str> {
+    listing
+        .split(r#"<li><a href=""#)
+        .skip(1)
+        .filter_map(|item| item.split(".html").next()?.rsplit('/').next())
+        .collect()
+}
+
+#[test]
+fn sorts_listings_by_section_keys() {
+    let mut config = Config::default();
+    let blog = config.sections.get_mut("blog").unwrap();
+    blog.sort_by = Some("date".to_string());
+    blog.sort_order = SortOrder::Desc;
+    config.sections.get_mut("books").unwrap().sort_by = Some("chapter".to_string());
+    let dist = build_fixture(config);
+
+    assert_eq!(
+        listed_slugs(&read(&dist, "blog.html")),
+        ["long-read", "code-sample", "hello-world"]
+    );
+    // The book without a chapter sinks to the end
+    let books = read(&dist, "books.html");
+    assert!(books.find("second-book/intro").unwrap() < books.find("first-book/intro").unwrap());
+    assert!(books.find("first-book/intro").unwrap() < books.find("rust-book").unwrap());
+
+    let mut config = Config::default();
+    let blog = config.sections.get_mut("blog").unwrap();
+    blog.sort_by = Some("title".to_string());
+    blog.sort_order = SortOrder::Desc;
+    let dist = build_fixture(config);
+    assert_eq!(
+        listed_slugs(&read(&dist, "blog.html")),
+        ["hello-world", "code-sample", "long-read"]
+    );
+}
+
+#[test]
+fn sorts_listings_by_weight() {
+    let source = copy_fixture();
+    let set_weights = |weights: &[(&str, i64)]| {
+        for (slug, weight) in weights {
+            let path = source.path().join(format!("content/blog/{}.md", slug));
+            let markdown = fs::read_to_string(&path).unwrap();
+            let markdown = markdown
+                .lines()
+                .filter(|line| !line.starts_with("weight:"))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let markdown = markdown.replacen("---\n", &format!("---\nweight: {}\n", weight), 1);
+            fs::write(&path, markdown).unwrap();
+        }
+    };
+    let build_with = |default_weight| {
+        let mut config = Config::default();
+        let blog = config.sections.get_mut("blog").unwrap();
+        blog.sort_by = Some("weight".to_string());
+        if let Some(default_weight) = default_weight {
+            blog.default_weight = default_weight;
+        }
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        let listing = read(&dist, "blog.html");
+        listed_slugs(&listing)
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    };
+
+    // Posts without a weight sink to the end
+    set_weights(&[("long-read", 2), ("hello-world", 1)]);
+    assert_eq!(
+        build_with(None),
+        ["hello-world", "long-read", "code-sample"]
+    );
+
+    // Same weights put the newest first
+    set_weights(&[("long-read", 1)]);
+    assert_eq!(
+        build_with(None),
+        ["long-read", "hello-world", "code-sample"]
+    );
+    assert_eq!(
+        build_with(Some(0)),
+        ["code-sample", "long-read", "hello-world"]
+    );
+}
+
+#[test]
+fn keeps_nested_book_chapters_apart() {
+    let dist = build_fixture(Config::default());
+
+    let first = read(&dist, "books/first-book/intro.html");
+    assert!(first.contains("Why the first book is worth reading."));
+    let second = read(&dist, "books/second-book/intro.html");
+    assert!(second.contains("Why the second book is worth reading."));
+
+    let books = read(&dist, "books.html");
+    assert!(books.contains(r#"<a href="/books/first-book/intro.html">"#));
+    assert!(books.contains(r#"<a href="/books/second-book/intro.html">"#));
+}
+
+#[test]
+fn builds_pages_outside_sections_as_generic_pages() {
+    let dist = build_fixture(Config::default());
+    let about = read(&dist, "about.html");
+
+    assert!(about.contains("<h1>About</h1>"));
+    assert!(about.contains("A page outside of any section."));
+}
+
+#[test]
+fn resolves_partials_and_nested_layouts() {
+    let dist = build_fixture(Config::default());
+
+    // `books` extends `layouts/article.html`, which extends `base.html`
+    let book = read(&dist, "books/rust-book.html");
+    assert!(book.contains(r#"<article class="book">"#));
+    assert!(book.contains("<footer>Fixture footer</footer>"));
+}
+
+#[test]
+fn rejects_partials_as_page_templates() {
+    let mut config = Config::default();
+    config.sections.insert(
+        "notes".to_string(),
+        SectionConfig::new("partials/_footer.html", "blog_list.html"),
+    );
+
+    let dist = tempfile::tempdir().unwrap();
+    let err = Site::new(config, FIXTURE, dist.path()).err().unwrap();
+    assert!(err
+        .to_string()
+        .contains("`partials/_footer.html` is a partial"));
+}
+
+#[test]
+fn wraps_code_blocks_with_copy_button_when_enabled() {
+    let mut config = Config::default();
+    config.markdown.code_copy = true;
+    let dist = build_fixture(config);
+
+    let post = read(&dist, "blog/code-sample.html");
+    assert!(post.contains(r#"<div class="code-block"><button class="copy">Copy</button>"#));
+    assert!(post.contains(r#"<script src="/assets/code-copy.js"></script>"#));
+
+    // Pages without code don't load the script
+    let other = read(&dist, "blog/hello-world.html");
+    assert!(!other.contains("code-copy.js"));
+}
+
+#[test]
+fn leaves_code_blocks_alone_by_default() {
+    let dist = build_fixture(Config::default());
+
+    let post = read(&dist, "blog/code-sample.html");
+    assert!(post.contains(r#"<pre><code class="language-rust">"#));
+    assert!(!post.contains("code-block"));
+    assert!(!post.contains("code-copy.js"));
+}
+
+#[test]
+fn normalizes_trailing_slashes_on_internal_links() {
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        trailing_slash: TrailingSlash::Never,
+        nav: vec![NavItem {
+            label: "Notes".to_string(),
+            url: "/notes/".to_string(),
+        }],
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post.contains(r#"<a href="/notes">Notes</a>"#));
+    assert!(post.contains(r#"<a href="/books">the books</a>"#));
+    assert!(post.contains(r#"<a href="/books/first-book/intro.html">the intro</a>"#));
+    assert!(post.contains(r#"<a href="https://www.rust-lang.org/">Rust</a>"#));
+
+    // Links written in templates go through the `normalize_url` filter
+    let index = read(&dist, "index.html");
+    assert!(index.contains(r#"<a class="browse" href="/books">"#));
+
+    let config = Config {
+        trailing_slash: TrailingSlash::Always,
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post.contains(r#"<a href="/books/">the books</a>"#));
+    assert!(post.contains(r#"<a href="/">Home</a>"#));
+    assert!(post.contains(r#"<a href="https://www.rust-lang.org/">Rust</a>"#));
+}
+
+// Strip the indentation and blank lines templates leave around markup
+fn squash(html: &str) -> String {
+    html.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[test]
+fn anchors_headings_within_toc_levels() {
+    let dist = build_fixture(Config::default());
+    let post = read(&dist, "blog/long-read.html");
+
+    assert!(post.contains("<h1>Overview</h1>"));
+    assert!(post.contains(r#"<h2 id="getting-started">Getting Started</h2>"#));
+    assert!(post.contains(r#"<h3 id="install-cargo">Install <code>cargo</code></h3>"#));
+    assert!(post.contains("<h4>Platform notes</h4>"));
+    assert!(post.contains(r#"<h2 id="getting-started-1">Getting Started</h2>"#));
+
+    let toc = squash(&post);
+    assert!(toc.contains(
+        r##"<li> <a href="#getting-started">Getting Started</a> <ul> <li> <a href="#install-cargo">Install cargo</a> </li> </ul> </li>"##
+    ));
+    assert!(toc.contains(r##"<a href="#getting-started-1">"##));
+    assert!(!toc.contains("#overview"));
+}
+
+#[test]
+fn toggles_section_features() {
+    let source = copy_fixture();
+    let hello = source.path().join("content/blog/hello-world.md");
+    let content = fs::read_to_string(&hello).unwrap();
+    fs::write(
+        &hello,
+        content.replacen("---\n", "---\ncomments: false\n", 1),
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.sections.get_mut("blog").unwrap().features = SectionFeatures {
+        toc: false,
+        reading_time: false,
+        related_posts: false,
+        comments: true,
+        custom: [("newsletter".to_string(), true)].into(),
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let post = read(&dist, "blog/long-read.html");
+    assert!(!post.contains(r#"<nav class="toc">"#));
+    assert!(!post.contains("reading-time"));
+    assert!(!post.contains(r#"class="related""#));
+    assert!(post.contains(r#"<div class="comments"></div>"#));
+    assert!(post.contains(r#"<form class="newsletter"></form>"#));
+    // Frontmatter can turn comments off for one post
+    assert!(!read(&dist, "blog/hello-world.html").contains("comments"));
+
+    // Without the section's features posts get the defaults
+    let dist = build_fixture(Config::default());
+    let post = read(&dist, "blog/long-read.html");
+    assert!(post.contains("reading-time") && !post.contains("comments"));
+}
+
+#[test]
+fn embeds_comments_where_enabled() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    let hello = blog.join("hello-world.md");
+    let content = fs::read_to_string(&hello).unwrap();
+    fs::write(
+        &hello,
+        content.replacen("---\n", "---\ncomments: false\n", 1),
+    )
+    .unwrap();
+    fs::write(
+        blog.join("unfinished.md"),
+        "---\ntitle: Unfinished\ndraft: true\n---\n\nSoon.\n",
+    )
+    .unwrap();
+    let build_with = |provider| {
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            drafts: true,
+            ..Config::default()
+        };
+        config.comments.provider = Some(provider);
+        config.comments.repo = "mumudevx/site".to_string();
+        config.comments.repo_id = "R_1".to_string();
+        config.comments.category = "Posts & notes".to_string();
+        config.comments.category_id = "DIC_2".to_string();
+        config.sections.get_mut("blog").unwrap().features.comments = true;
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(CommentsProvider::Giscus);
+    let post = read(&dist, "blog/long-read.html");
+    assert!(post.contains(
+        r#"<section class="thread"><script src="https://giscus.app/client.js" data-repo="mumudevx/site" data-repo-id="R_1" data-category="Posts &amp; notes" data-category-id="DIC_2" data-mapping="pathname" data-reactions-enabled="1" data-input-position="bottom" data-theme="preferred_color_scheme" data-lang="en" crossorigin="anonymous" async></script></section>"#
+    ));
+    assert!(!read(&dist, "blog/hello-world.html").contains("giscus"));
+    assert!(!read(&dist, "blog/unfinished.html").contains("giscus"));
+    assert!(!read(&dist, "books/rust-book.html").contains("giscus"));
+
+    let dist = build_with(CommentsProvider::Utterances);
+    assert!(read(&dist, "blog/long-read.html").contains(
+        r#"<script src="https://utteranc.es/client.js" repo="mumudevx/site" issue-term="pathname" theme="preferred-color-scheme" crossorigin="anonymous" async></script>"#
+    ));
+}
+
+#[test]
+fn sets_body_classes_per_kind_of_page() {
+    let source = copy_fixture();
+    let long_read = source.path().join("content/blog/long-read.md");
+    let content = fs::read_to_string(&long_read).unwrap();
+    fs::write(
+        &long_read,
+        content.replacen("---\n", "---\nbody_class: post wide\n", 1),
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let body = |path| {
+        let page = read(&dist, path);
+        let start = page.find("<body class=\"").unwrap() + 13;
+        let end = start + page[start..].find('"').unwrap();
+        page[start..end].to_string()
+    };
+    assert_eq!(body("index.html"), "home");
+    assert_eq!(body("blog.html"), "blog listing");
+    assert_eq!(body("blog/hello-world.html"), "blog post");
+    assert_eq!(body("books/rust-book.html"), "books post");
+    assert_eq!(body("about.html"), "page");
+    assert_eq!(body("blog/long-read.html"), "post wide");
+}
+
+#[test]
+fn toggles_toc_per_section_and_post() {
+    let mut config = Config::default();
+    config.sections.get_mut("blog").unwrap().toc = false;
+    let dist = build_fixture(config);
+    let post = read(&dist, "blog/long-read.html");
+    assert!(post.contains("<h2>Getting Started</h2>"));
+    assert!(!post.contains(r#"<nav class="toc">"#));
+
+    // Frontmatter wins over the section default
+    let source = copy_fixture();
+    let long_read = source.path().join("content/blog/long-read.md");
+    let content = fs::read_to_string(&long_read).unwrap();
+    fs::write(
+        &long_read,
+        content.replacen("---\n", "---\ntoc: false\n", 1),
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    let post = read(&dist, "blog/long-read.html");
+    assert!(post.contains("<h2>Getting Started</h2>"));
+    assert!(!post.contains(r#"<nav class="toc">"#));
+}
+
+#[test]
+fn writes_toc_json_when_enabled() {
+    let config = Config {
+        toc_json: true,
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+    let toc: serde_json::Value =
+        serde_json::from_str(&read(&dist, "blog/long-read/toc.json")).unwrap();
+    assert_eq!(toc[0]["title"], "Getting Started");
+    assert_eq!(toc[0]["level"], 2);
+    assert_eq!(toc[0]["id"], "getting-started");
+    assert_eq!(toc[0]["url"], "/blog/long-read.html");
+    assert_eq!(toc[0]["children"][0]["title"], "Install cargo");
+    assert_eq!(toc[1]["id"], "getting-started-1");
+
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join("blog/long-read/toc.json").exists());
+}
+
+#[test]
+fn configures_toc_levels() {
+    let mut config = Config::default();
+    config.markdown.toc_min_level = 1;
+    config.markdown.toc_max_level = 4;
+    let dist = build_fixture(config);
+    let post = read(&dist, "blog/long-read.html");
+
+    assert!(post.contains(r#"<h1 id="overview">Overview</h1>"#));
+    assert!(post.contains(r#"<h4 id="platform-notes">Platform notes</h4>"#));
+}
+
+#[test]
+fn leaves_out_short_tables_of_contents() {
+    let source = copy_fixture();
+    let books = source.path().join("content/books");
+    fs::write(
+        books.join("short.md"),
+        "---\ntitle: Short\n---\n## Only\n\nText.\n",
+    )
+    .unwrap();
+    fs::write(
+        books.join("long.md"),
+        "---\ntitle: Long\ntoc_title: In this book\n---\n## One\n\n### Two\n\n## Three\n",
+    )
+    .unwrap();
+    fs::write(
+        books.join("plain.md"),
+        "---\ntitle: Plain\n---\n## One\n\n## Two\n\n## Three\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.markdown.toc_min_headings = 3;
+    config.markdown.toc_title = "On this page".to_string();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let short = read(&dist, "books/short.html");
+    assert!(!short.contains(r#"<nav class="toc">"#));
+    assert!(short.contains(r#"<h2 id="only">Only</h2>"#));
+    let long = read(&dist, "books/long.html");
+    assert!(long.contains(r#"<nav class="toc"><h2>In this book</h2>"#));
+    assert!(read(&dist, "books/plain.html").contains(r#"<nav class="toc"><h2>On this page</h2>"#));
+}
+
+#[test]
+fn numbers_headings() {
+    let source = copy_fixture();
+    let books = source.path().join("content/books");
+    fs::write(
+        books.join("numbered.md"),
+        "---\ntitle: Numbered\n---\n## Intro\n\n#### Aside\n\n### Setup\n\n## Usage\n\n### Again\n",
+    )
+    .unwrap();
+    fs::write(
+        books.join("unnumbered.md"),
+        "---\ntitle: Unnumbered\nnumber_headings: false\n---\n## Intro\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.markdown.toc_max_level = 4;
+    config.sections.get_mut("books").unwrap().number_headings = true;
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let page = read(&dist, "books/numbered.html");
+    for heading in [
+        r#"<h2 id="1-intro"><span class="heading-number">1</span> Intro</h2>"#,
+        r#"<h4 id="1-1-aside"><span class="heading-number">1.1</span> Aside</h4>"#,
+        r#"<h3 id="1-2-setup"><span class="heading-number">1.2</span> Setup</h3>"#,
+        r#"<h2 id="2-usage"><span class="heading-number">2</span> Usage</h2>"#,
+        r#"<h3 id="2-1-again"><span class="heading-number">2.1</span> Again</h3>"#,
+    ] {
+        assert!(page.contains(heading), "{}", heading);
+    }
+    assert!(page.contains(r##"<a href="#1-2-setup">1.2 Setup</a>"##));
+    assert!(read(&dist, "books/unnumbered.html").contains(r#"<h2 id="intro">Intro</h2>"#));
+    // Other sections keep plain headings
+    assert!(!read(&dist, "blog/hello-world.html").contains("heading-number"));
+}
+
+#[test]
+fn uses_base_url_for_canonical_links() {
+    let config = Config {
+        base_url: "https://example.com/".to_string(),
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post
+        .contains(r#"<link rel="canonical" href="https://example.com/blog/hello-world.html" />"#));
+    assert!(read(&dist, "blog.html")
+        .contains(r#"<link rel="canonical" href="https://example.com/blog.html" />"#));
+}
+
+#[test]
+fn points_the_homepage_canonical_at_the_bare_base_url() {
+    let canonical = |base_path: &str, trailing_slash: TrailingSlash| {
+        let config = Config {
+            base_url: "https://example.com".to_string(),
+            base_path: base_path.to_string(),
+            trailing_slash,
+            ..Config::default()
+        };
+        let index = read(&build_fixture(config), "index.html");
+        let start = index.find(r#"<link rel="canonical" href=""#).unwrap() + 28;
+        index[start..].split('"').next().unwrap().to_string()
+    };
+    assert_eq!(
+        canonical("", TrailingSlash::Preserve),
+        "https://example.com/"
+    );
+    assert_eq!(
+        canonical("/site", TrailingSlash::Always),
+        "https://example.com/site/"
+    );
+    assert_eq!(
+        canonical("/site", TrailingSlash::Never),
+        "https://example.com/site"
+    );
+}
+
+#[test]
+fn writes_redirect_stubs_for_old_urls() {
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        redirects_file: true,
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let target = "https://example.com/blog/hello-world.html";
+    for stub in ["hello/index.html", "2024/hello.html"] {
+        let stub = read(&dist, stub);
+        assert!(stub.contains(&format!(
+            r#"<meta http-equiv="refresh" content="0; url={}" />"#,
+            target
+        )));
+        assert!(stub.contains(&format!(r#"<link rel="canonical" href="{}" />"#, target)));
+    }
+    assert_eq!(
+        read(&dist, "_redirects"),
+        "/hello/ /blog/hello-world.html 301\n/2024/hello.html /blog/hello-world.html 301\n"
+    );
+
+    // The `_redirects` file is opt-in
+    let dist = build_fixture(Config::default());
+    assert!(dist.path().join("hello/index.html").is_file());
+    assert!(!dist.path().join("_redirects").exists());
+}
+
+#[test]
+fn writes_configured_redirects_and_headers() {
+    let config: Config = toml::from_str(
+        r#"
+        [[redirects]]
+        from = "/old-blog/*"
+        to = "/blog/:splat"
+
+        [[redirects]]
+        from = "/cv"
+        to = "/about.html"
+        status = 302
+
+        [[headers]]
+        for = "/*"
+        values = { X-Frame-Options = "DENY", Referrer-Policy = "no-referrer" }
+        "#,
+    )
+    .unwrap();
+    let dist = build_fixture(config);
+
+    // Without `redirects_file` only the site-wide rules are listed
+    assert_eq!(
+        read(&dist, "_redirects"),
+        "/old-blog/* /blog/:splat 301\n/cv /about.html 302\n"
+    );
+    assert_eq!(
+        read(&dist, "_headers"),
+        "/*\n  Referrer-Policy: no-referrer\n  X-Frame-Options: DENY\n"
+    );
+
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join("_headers").exists());
+}
+
+#[test]
+fn serves_the_site_under_a_base_path() {
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        base_path: "/site/".to_string(),
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post.contains(
+        r#"<link rel="canonical" href="https://example.com/site/blog/hello-world.html" />"#
+    ));
+    assert!(post.contains(r#"<link rel="stylesheet" href="/site/assets/styles.css" />"#));
+    assert!(post.contains(r#"<a href="/site/blog.html" class="active">Blog</a>"#));
+    assert!(post.contains(r#"<a href="/site/books/">the books</a>"#));
+    assert!(post.contains(r#"<a href="https://www.rust-lang.org/">Rust</a>"#));
+    assert!(read(&dist, "blog.html").contains(r#"<a href="/site/blog/hello-world.html">"#));
+    assert!(read(&dist, "books/rust-book.html").contains("/site/assets/images/cover.svg"));
+    assert!(read(&dist, "sitemap.xml").contains("<loc>https://example.com/site/</loc>"));
+
+    let url = serve(dist.path(), "127.0.0.1:0", "/site").unwrap();
+    assert!(url.ends_with("/site/"));
+    assert!(get(&url, "/site/blog/hello-world.html").starts_with("HTTP/1.1 200"));
+    assert!(get(&url, "/blog/hello-world.html").starts_with("HTTP/1.1 404"));
+}
+
+#[test]
+fn writes_json_sidecars_when_enabled() {
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        emit_json: true,
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let post: serde_json::Value =
+        serde_json::from_str(&read(&dist, "blog/hello-world.json")).unwrap();
+    assert_eq!(post["title"], "Hello World");
+    assert_eq!(post["url"], "/blog/hello-world.html");
+    assert_eq!(post["tags"], serde_json::json!(["rust", "web"]));
+    assert!(post["content"]
+        .as_str()
+        .unwrap()
+        .contains("Welcome to the <strong>fixture</strong> blog."));
+    assert!(dist.path().join("books/first-book/intro.json").is_file());
+    assert!(!read(&dist, "sitemap.xml").contains(".json"));
+
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join("blog/hello-world.json").exists());
+}
+
+#[test]
+fn writes_content_index() {
+    let dist = build_fixture(Config::default());
+    let index: serde_json::Value = serde_json::from_str(&read(&dist, "index.json")).unwrap();
+    let entries = index.as_array().unwrap();
+    let find = |slug: &str| {
+        entries
+            .iter()
+            .find(|entry| entry["slug"] == slug)
+            .unwrap_or_else(|| panic!("`{}` missing from index.json", slug))
+    };
+
+    let post = find("hello-world");
+    assert_eq!(post["title"], "Hello World");
+    assert_eq!(post["url"], "/blog/hello-world.html");
+    assert_eq!(post["date"], "2024-01-15");
+    assert_eq!(post["tags"], serde_json::json!(["rust", "web"]));
+    assert_eq!(post["category"], "blog");
+    assert_eq!(post["reading_time"], 1);
+    assert_eq!(
+        post["keywords"],
+        serde_json::json!(["static sites", "markdown"])
+    );
+    assert!(post.get("content").is_none());
+
+    assert!(find("about")["category"].is_null());
+    assert_eq!(find("about")["keywords"], serde_json::json!([]));
+}
+
+#[test]
+fn adds_keywords_meta_only_when_set() {
+    let dist = build_fixture(Config::default());
+    assert!(read(&dist, "blog/hello-world.html")
+        .contains(r#"<meta name="keywords" content="static sites, markdown" />"#));
+    assert!(!read(&dist, "blog/long-read.html").contains(r#"name="keywords""#));
+}
+
+#[test]
+fn writes_word_stats_when_enabled() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/turkce.md"),
+        "---\ntitle: Türkçe\n---\n\nBu yazı Rust için. Rust'ın derleyicisi ve Rust araçları için bir not.\n",
+    )
+    .unwrap();
+    let build_with = |stats: bool| {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            stats,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(true);
+    let stats: serde_json::Value = serde_json::from_str(&read(&dist, "stats.json")).unwrap();
+    let categories = &stats["category_words"];
+    let total: u64 = ["blog", "books", "standalone"]
+        .iter()
+        .map(|category| categories[category].as_u64().unwrap())
+        .sum();
+    assert_eq!(stats["total_words"].as_u64().unwrap(), total);
+    assert!(stats["average_reading_time"].as_f64().unwrap() >= 1.0);
+    let top_words: Vec<&str> = stats["top_words"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|entry| entry["word"].as_str().unwrap())
+        .collect();
+    assert_eq!(top_words[0], "rust");
+    assert!(!top_words.contains(&"için"));
+    assert!(!top_words.contains(&"the"));
+
+    assert!(!build_with(false).path().join("stats.json").exists());
+}
+
+#[test]
+fn sets_the_page_language_and_direction() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("merhaba.md"),
+        "---\ntitle: Merhaba\nlang: tr\n---\n\nSelam.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("shalom.md"),
+        "---\ntitle: Shalom\nlang: he-IL\n---\n\nShalom.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "index.html").contains(r#"<html lang="en" dir="ltr">"#));
+    assert!(read(&dist, "blog/merhaba.html").contains(r#"<html lang="tr" dir="ltr">"#));
+    assert!(read(&dist, "blog/shalom.html").contains(r#"<html lang="he-IL" dir="rtl">"#));
+
+    let config = Config {
+        lang: "ar".to_string(),
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+    assert!(read(&dist, "about.html").contains(r#"<html lang="ar" dir="rtl">"#));
+}
+
+#[test]
+fn keeps_noindex_pages_out_of_the_sitemap() {
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+
+    let thanks = read(&dist, "thanks.html");
+    assert!(thanks.contains(r#"<meta name="robots" content="noindex" />"#));
+    assert!(!read(&dist, "about.html").contains("noindex"));
+
+    let sitemap = read(&dist, "sitemap.xml");
+    assert!(sitemap.contains("<loc>https://example.com/</loc>"));
+    assert!(sitemap.contains("<loc>https://example.com/blog.html</loc>"));
+    assert!(sitemap.contains("<loc>https://example.com/blog/hello-world.html</loc>"));
+    assert!(!sitemap.contains("thanks"));
+}
+
+#[test]
+fn skips_sitemap_without_base_url() {
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join("sitemap.xml").exists());
+}
+
+#[test]
+fn copies_assets_and_writes_humans_txt() {
+    let dist = build_fixture(Config::default());
+
+    assert_eq!(
+        read(&dist, "assets/styles.css"),
+        "body {\n  margin: 0;\n}\n"
+    );
+    assert!(dist.path().join("assets/images/cover.svg").is_file());
+    assert!(read(&dist, "humans.txt").contains("Tools: Rust, Tera, pulldown-cmark"));
+}
+
+// A high quality JPEG with a fake EXIF segment right after the SOI marker
+fn write_jpeg_with_exif(path: &Path) {
+    let pixels = image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([x as u8 * 4, y as u8 * 4, 0]));
+    let mut jpeg = vec![];
+    image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, 100)
+        .encode_image(&pixels)
+        .unwrap();
+
+    let mut exif = b"Exif\0\0".to_vec();
+    exif.resize(4096, 0);
+    let length = (exif.len() + 2) as u16;
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend(length.to_be_bytes());
+    segment.extend(exif);
+    jpeg.splice(2..2, segment);
+
+    fs::write(path, jpeg).unwrap();
+}
+
+#[test]
+fn optimizes_images_when_enabled() {
+    let source = copy_fixture();
+    let photo = source.path().join("assets/images/photo.jpg");
+    write_jpeg_with_exif(&photo);
+    let original_size = fs::metadata(&photo).unwrap().len();
+
+    let mut config = Config::default();
+    config.assets.optimize_images = true;
+    config.assets.image_quality = 60;
+    config.assets.optimize_threshold = 0;
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+
+    let report = build(&site).unwrap();
+    assert_eq!(report.images_optimized, 1);
+    assert!(report.image_bytes_saved > 0);
+
+    let optimized = fs::read(dist.path().join("assets/images/photo.jpg")).unwrap();
+    assert!((optimized.len() as u64) < original_size);
+    assert!(!optimized.windows(4).any(|window| window == b"Exif"));
+
+    // Unchanged images aren't processed again on the next build
+    let report = build(&site).unwrap();
+    assert_eq!(report.images_optimized, 0);
+}
+
+#[test]
+fn reports_assets_nothing_refers_to() {
+    let source = copy_fixture();
+    let images = source.path().join("assets/images");
+    fs::copy(images.join("cover.svg"), images.join("pattern.svg")).unwrap();
+    fs::copy(images.join("cover.svg"), images.join("unused.svg")).unwrap();
+    fs::write(
+        source.path().join("assets/styles.css"),
+        "nav { background: url(\"images/pattern.svg\"); }\n",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    // The cover comes from a page, the pattern only from the stylesheet
+    let orphans = find_orphaned_assets(&site).unwrap();
+    assert_eq!(orphans, vec![Path::new("assets/images/unused.svg")]);
+    assert!(dist.path().join("assets/images/unused.svg").exists());
+}
+
+fn get(url: &str, path: &str) -> String {
+    let host = url.trim_start_matches("http://").split('/').next().unwrap();
+    let mut stream = TcpStream::connect(host).unwrap();
+    write!(
+        stream,
+        "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        path
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serves_the_built_site() {
+    let dist = build_fixture(Config::default());
+    let url = serve(dist.path(), "127.0.0.1:0", "").unwrap();
+
+    let post = get(&url, "/blog/hello-world.html");
+    assert!(post.starts_with("HTTP/1.1 200"));
+    assert!(post.contains("text/html"));
+    assert!(post.contains("Welcome to the <strong>fixture</strong> blog."));
+
+    // Directories serve their index and extensionless paths their page
+    assert!(get(&url, "/").contains("<!DOCTYPE html>"));
+    assert!(get(&url, "/blog/hello-world").starts_with("HTTP/1.1 200"));
+    assert!(get(&url, "/missing.html").starts_with("HTTP/1.1 404"));
+    assert!(get(&url, "/../Cargo.toml").starts_with("HTTP/1.1 404"));
+
+    // The index of generated files only exists on the dev server
+    let index = get(&url, "/__sitemap");
+    assert!(index.starts_with("HTTP/1.1 200"));
+    assert!(
+        index.contains(r#"<li><a href="/blog/hello-world.html">blog/hello-world.html</a></li>"#)
+    );
+    assert!(!dist.path().join("__sitemap").exists());
+}
+
+#[test]
+fn warns_about_posts_without_content() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(blog.join("stub.md"), "---\ntitle: Stub\n---\n\n  \n").unwrap();
+    fs::write(blog.join("wip.md"), "---\ntitle: WIP\ndraft: true\n---\n").unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        (build(&site), dist)
+    };
+
+    let (report, dist) = build_with(Config::default());
+    let warnings = report.unwrap().warnings;
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].ends_with("stub.md has no content"));
+    assert!(dist.path().join("blog/stub.html").is_file());
+    assert!(!dist.path().join("blog/wip.html").exists());
+    assert!(!read(&dist, "blog.html").contains("WIP"));
+
+    // Drafts are exempt from the check when they're built
+    let (report, dist) = build_with(Config {
+        drafts: true,
+        ..Config::default()
+    });
+    assert_eq!(report.unwrap().warnings.len(), 1);
+    assert!(dist.path().join("blog/wip.html").is_file());
+
+    let (report, _dist) = build_with(Config {
+        strict: true,
+        ..Config::default()
+    });
+    let err = format!("{:#}", report.unwrap_err());
+    assert!(err.contains("stub.md has no content"));
+}
+
+#[test]
+fn builds_posts_by_workflow_status() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("idea.md"),
+        "---\ntitle: Idea\nstatus: draft\n---\n\nRough.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("almost.md"),
+        "---\ntitle: Almost\nstatus: review\n---\n\nNearly.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("done.md"),
+        "---\ntitle: Done\nstatus: published\n---\n\nFinal.\n",
+    )
+    .unwrap();
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        (build(&site), dist)
+    };
+    let built =
+        |dist: &TempDir, slug: &str| dist.path().join(format!("blog/{}.html", slug)).is_file();
+
+    let (report, dist) = build_with(Config::default());
+    let report = report.unwrap();
+    assert!(built(&dist, "done") && built(&dist, "hello-world"));
+    assert!(!built(&dist, "almost") && !built(&dist, "idea"));
+    assert!(report
+        .excluded
+        .iter()
+        .any(|e| e.path.ends_with("almost.md") && e.reason == ExclusionReason::Unpublished));
+    assert!(report
+        .excluded
+        .iter()
+        .any(|e| e.path.ends_with("idea.md") && e.reason == ExclusionReason::Draft));
+
+    // A preview deploy takes posts under review, drafts still wait
+    let (report, dist) = build_with(Config {
+        status: Some("review".to_string()),
+        ..Config::default()
+    });
+    report.unwrap();
+    assert!(built(&dist, "almost") && !built(&dist, "idea"));
+
+    fs::write(
+        blog.join("odd.md"),
+        "---\ntitle: Odd\nstatus: shipped\n---\n",
+    )
+    .unwrap();
+    let (report, _dist) = build_with(Config::default());
+    let err = format!("{:#}", report.unwrap_err());
+    assert!(err.contains("unknown status `shipped`, expected one of: draft, review, published"));
+}
+
+#[test]
+fn exposes_build_info_to_templates() {
+    // The copy lives outside any git repository
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let index = read(&dist, "index.html");
+    let build_info = index.split(r#"<p class="build">"#).nth(1).unwrap();
+    let (commit, time) = build_info.split_once(' ').unwrap();
+    assert_eq!(commit, "unknown");
+    let time = time.split("</p>").next().unwrap();
+    assert!(chrono::DateTime::parse_from_rfc3339(time).is_ok());
+}
+
+#[test]
+fn fixes_the_build_time_in_reproducible_builds() {
+    let config = || Config {
+        reproducible: true,
+        ..Config::default()
+    };
+    let first = build_fixture(config());
+    let second = build_fixture(config());
+    for path in ["index.html", "humans.txt", "blog/hello-world.html"] {
+        assert_eq!(read(&first, path), read(&second, path));
+    }
+    assert!(read(&first, "index.html").contains(" 1970-01-01T00:00:00+00:00</p>"));
+
+    let dist = build_fixture(Config {
+        source_date_epoch: Some(1_700_000_000),
+        ..Config::default()
+    });
+    assert!(read(&dist, "index.html").contains(" 2023-11-14T22:13:20+00:00</p>"));
+    assert!(read(&dist, "humans.txt").contains("Last update: 2023/11/14"));
+}
+
+#[test]
+fn cuts_old_posts_from_listings_and_feeds() {
+    let build_with = |listing_cutoff_days, feed_cutoff_days| {
+        build_fixture(Config {
+            base_url: "https://example.com".to_string(),
+            // Built on 2024-04-11
+            source_date_epoch: Some(1_712_793_600),
+            listing_cutoff_days,
+            feed_cutoff_days,
+            ..Config::default()
+        })
+    };
+    let dist = build_with(Some(30), Some(60));
+
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains(r#"<a href="/blog/long-read.html">"#));
+    assert!(!listing.contains(r#"<a href="/blog/hello-world.html">"#));
+    assert!(!listing.contains(r#"<a href="/blog/code-sample.html">"#));
+    assert!(
+        listing.contains(r#"<ul class="archived"><li>code-sample</li><li>hello-world</li></ul>"#)
+    );
+    let homepage = read(&dist, "index.html");
+    assert!(homepage.contains("<li>long-read</li>"));
+    assert!(!homepage.contains("<li>hello-world</li>"));
+
+    let feed = read(&dist, "index.xml");
+    assert!(feed.contains("/blog/long-read.html") && feed.contains("/blog/code-sample.html"));
+    assert!(!feed.contains("/blog/hello-world.html") && !feed.contains("/books/rust-book.html"));
+
+    // The old posts are still built and in the sitemap
+    assert!(dist.path().join("blog/hello-world.html").exists());
+    assert!(read(&dist, "sitemap.xml").contains("https://example.com/blog/hello-world.html"));
+
+    let dist = build_with(None, None);
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains(r#"<a href="/blog/hello-world.html">"#));
+    assert!(!listing.contains("archived"));
+    assert!(read(&dist, "index.xml").contains("/blog/hello-world.html"));
+}
+
+#[test]
+fn breaks_feed_date_ties() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    let post = |name: &str, frontmatter: &str| {
+        fs::write(
+            blog.join(format!("{}.md", name)),
+            format!("---\ntitle: {}\n{}---\nBody\n", name, frontmatter),
+        )
+        .unwrap();
+    };
+    post("tie-b", "date: 2024-05-01\n");
+    post("tie-a", "date: 2024-05-01\n");
+    post("tie-weighted", "date: 2024-05-01\nweight: -1\n");
+    post("tie-timed", "date: 2024-05-01\ntime: \"09:30\"\n");
+    post("tie-evening", "date: 2024-05-01 18:45\n");
+    let dist = tempfile::tempdir().unwrap();
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        date_formats: vec!["%Y-%m-%d".to_string(), "%Y-%m-%d %H:%M".to_string()],
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let feed = read(&dist, "blog/index.xml");
+    let order: Vec<usize> = ["tie-evening", "tie-timed", "tie-weighted", "tie-a", "tie-b"]
+        .iter()
+        .map(|slug| feed.find(&format!("/blog/{}.html", slug)).unwrap())
+        .collect();
+    assert!(order.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(feed.contains("<pubDate>Wed, 01 May 2024 18:45:00 +0000</pubDate>"));
+    assert!(feed.contains("<pubDate>Wed, 01 May 2024 09:30:00 +0000</pubDate>"));
+}
+
+#[test]
+fn previews_each_section_on_the_homepage() {
+    let mut config = Config::default();
+    config.sections.get_mut("blog").unwrap().homepage_posts = 2;
+    config.sections.get_mut("books").unwrap().homepage_posts = 0;
+    let dist = build_fixture(config);
+    let index = read(&dist, "index.html");
+
+    let preview = index
+        .split(r#"<section class="preview blog">"#)
+        .nth(1)
+        .unwrap();
+    let preview = preview.split("</section>").next().unwrap();
+    assert_eq!(
+        preview,
+        r#"<a href="/blog/long-read.html"></a><a href="/blog/code-sample.html"></a>"#
+    );
+    assert!(!index.contains(r#"<section class="preview books">"#));
+
+    let index = read(&build_fixture(Config::default()), "index.html");
+    assert!(index.contains(r#"<section class="preview books">"#));
+}
+
+#[test]
+fn serializes_the_build_report() {
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), FIXTURE, dist.path()).unwrap();
+    let report = serde_json::to_value(build(&site).unwrap()).unwrap();
+
+    assert_eq!(report["dist_dir"], dist.path().to_str().unwrap());
+    assert!(report["duration_ms"].is_u64());
+    assert!(report["warnings"].is_array());
+    let published = report["published"].as_array().unwrap();
+    assert!(published
+        .iter()
+        .any(|entry| entry["section"] == "blog" && entry["pages"].as_u64() > Some(0)));
+    assert!(report["excluded"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .all(|entry| entry["path"].is_string() && entry["reason"].is_string()));
+}
+
+#[test]
+fn warns_about_links_to_missing_anchors() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/anchors.md"),
+        "---\ntitle: Anchors\ndate: 2024-02-02\n---\n## Setup\n\n\
+         [here](#setup) [typo](#setpu) [top](#top) \
+         [other](/blog/hello-world.html#nowhere) [missing page](/blog/gone.html#x)\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+    assert!(report.warnings.contains(
+        &"/blog/anchors.html links to `#setpu`, which isn't an id on the page".to_string()
+    ));
+    assert!(report.warnings.contains(
+        &"/blog/anchors.html links to `/blog/hello-world.html#nowhere`, but /blog/hello-world.html has no id `nowhere`"
+            .to_string()
+    ));
+
+    let config = Config {
+        strict: true,
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    assert!(build(&site).is_err());
+
+    let config = Config {
+        check_anchors: false,
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    assert!(build(&site).unwrap().warnings.is_empty());
+}
+
+#[test]
+fn warns_about_missing_images() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/gallery.md"),
+        "---\ntitle: Gallery\nimage: /assets/images/missing-cover.png\n---\n\
+         ![Cover](/assets/images/cover.svg) ![Gone](/assets/images/gone.png)\n\n\
+         <img src=\"../assets/raw.jpg\" alt=\"Raw\"> <img src=\"../assets/images/cover.svg\"> \
+         ![Remote](https://example.com/cat.png)\n",
+    )
+    .unwrap();
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site)
+    };
+
+    let report = build_with(Config {
+        base_path: "/site".to_string(),
+        ..Config::default()
+    })
+    .unwrap();
+    assert_eq!(
+        report.warnings,
+        [
+            "/site/blog/gallery.html shows the image `/assets/images/missing-cover.png`, which doesn't exist",
+            "/site/blog/gallery.html shows the image `/site/assets/images/gone.png`, which doesn't exist",
+            "/site/blog/gallery.html shows the image `../assets/raw.jpg`, which doesn't exist",
+        ]
+    );
+
+    assert!(build_with(Config {
+        strict: true,
+        ..Config::default()
+    })
+    .is_err());
+    let report = build_with(Config {
+        check_images: false,
+        ..Config::default()
+    })
+    .unwrap();
+    assert!(report.warnings.is_empty());
+}
+
+#[test]
+fn prefers_section_template_directories() {
+    let source = copy_fixture();
+    let books = source.path().join("template/books");
+    fs::create_dir_all(&books).unwrap();
+    fs::write(
+        books.join("book_list.html"),
+        r#"{% extends "base.html" %}{% block content %}<ol class="shelf">{% for post in posts %}<li>{{ post.slug }}</li>{% endfor %}</ol>{% endblock %}"#,
+    )
+    .unwrap();
+    let shelf = source.path().join("template/shelf");
+    fs::create_dir_all(&shelf).unwrap();
+    fs::write(
+        shelf.join("blog_detail.html"),
+        r#"{% extends "base.html" %}{% block content %}<article class="shelved">{{ title }}</article>{% endblock %}"#,
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.sections.get_mut("blog").unwrap().template_dir = Some("shelf".to_string());
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "books.html").contains(r#"<ol class="shelf">"#));
+    // Books have no detail template of their own
+    assert!(!read(&dist, "books/rust-book.html").contains("shelved"));
+    assert!(read(&dist, "blog/hello-world.html").contains(r#"<article class="shelved">"#));
+    assert!(!read(&dist, "blog.html").contains(r#"<ol class="shelf">"#));
+}
+
+#[test]
+fn marks_the_lead_paragraph() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("lead.md"),
+        "---\ntitle: Lead\n---\nFirst words.\n\nSecond words.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("picture.md"),
+        "---\ntitle: Picture\n---\n![A cat](/assets/cat.png)\n\nAfter the picture.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.markdown.lead_paragraph = true;
+    config.markdown.lead_class = "intro".to_string();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let lead = read(&dist, "blog/lead.html");
+    assert!(lead.contains(r#"<p class="intro">First words.</p>"#));
+    assert!(lead.contains("<p>Second words.</p>"));
+    assert!(!read(&dist, "blog/picture.html").contains("intro"));
+    // It opens with a heading
+    assert!(!read(&dist, "blog/hello-world.html").contains(r#"class="intro""#));
+}
+
+#[test]
+fn gives_paragraphs_stable_ids() {
+    let source = copy_fixture();
+    let post = source.path().join("content/blog/deep.md");
+    let build_with = |body: &str| {
+        fs::write(&post, format!("---\ntitle: Deep\n---\n{}", body)).unwrap();
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.markdown.paragraph_ids = true;
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "blog/deep.html")
+    };
+    let id_of = |page: &str, text: &str| {
+        let end = page.find(&format!(">{}</p>", text)).unwrap();
+        let start = page[..end].rfind("<p id=\"").unwrap() + 7;
+        page[start..end - 1].to_string()
+    };
+
+    let first = build_with("Stable words.\n\nAgain.\n\nAgain.\n\n```\n<p>code</p>\n```\n");
+    let stable = id_of(&first, "Stable words.");
+    assert!(stable.starts_with("p-") && stable.len() == 10, "{}", stable);
+    assert!(first.contains(&format!(
+        r#"<p id="{}-1">Again.</p>"#,
+        id_of(&first, "Again.")
+    )));
+    assert!(first.contains("&lt;p&gt;code&lt;/p&gt;"));
+
+    let edited = build_with("A new opening.\n\nStable words.\n");
+    assert_eq!(id_of(&edited, "Stable words."), stable);
+}
+
+#[test]
+fn autolinks_bare_urls() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/links.md"),
+        "---\ntitle: Links\n---\n\
+         See https://example.com/a_b_c?x=1&y=2. Or (https://example.org/path).\n\n\
+         Run `curl https://example.com/api` or [the docs](https://docs.rs).\n\n\
+         ```\nhttps://example.com/in-a-block\n```\n",
+    )
+    .unwrap();
+    let build_with = |autolink| {
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.markdown.autolink = autolink;
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "blog/links.html")
+    };
+
+    let post = build_with(true);
+    assert!(post.contains(
+        r#"See <a href="https://example.com/a_b_c?x=1&amp;y=2">https://example.com/a_b_c?x=1&amp;y=2</a>. Or (<a href="https://example.org/path">https://example.org/path</a>)."#
+    ));
+    assert!(post.contains("<code>curl https://example.com/api</code>"));
+    assert!(post.contains(r#"<a href="https://docs.rs">the docs</a>"#));
+    assert!(post.contains("<code>https://example.com/in-a-block\n</code>"));
+
+    assert!(!build_with(false).contains(r#"<a href="https://example.com/a_b_c"#));
+}
+
+#[test]
+fn renders_admonitions() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/callouts.md"),
+        "---\ntitle: Callouts\n---\n\
+         > [!NOTE]\n> Read **this**.\n\n\
+         > [!tip]\n>\n> - One\n> - Two\n\n\
+         > [!WARNING]\n> Careful.\n\n\
+         > [!DANGER]\n> Stop.\n\n\
+         > [!OTHER]\n> Left alone.\n\n\
+         > Just a quote.\n",
+    )
+    .unwrap();
+    let build_with = |admonitions| {
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.markdown.admonitions = admonitions;
+        config
+            .markdown
+            .admonition_labels
+            .insert("warning".to_string(), "Careful & slow".to_string());
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "blog/callouts.html")
+    };
+
+    let post = build_with(true);
+    assert!(post.contains(
+        "<div class=\"admonition note\">\n<p class=\"admonition-title\">Note</p>\n<blockquote>\n<p>Read <strong>this</strong>.</p>\n</blockquote>\n</div>"
+    ));
+    assert!(post.contains(
+        "<div class=\"admonition tip\">\n<p class=\"admonition-title\">Tip</p>\n<blockquote>\n<ul>\n<li>One</li>"
+    ));
+    assert!(post.contains(
+        "<div class=\"admonition warning\">\n<p class=\"admonition-title\">Careful &amp; slow</p>\n<blockquote>\n<p>Careful.</p>"
+    ));
+    assert!(post.contains(
+        "<div class=\"admonition danger\">\n<p class=\"admonition-title\">Danger</p>\n<blockquote>\n<p>Stop.</p>"
+    ));
+    assert!(post.contains("<blockquote>\n<p>[!OTHER]\nLeft alone.</p>\n</blockquote>"));
+    assert!(post.contains("<blockquote>\n<p>Just a quote.</p>\n</blockquote>"));
+
+    assert!(!build_with(false).contains("admonition"));
+}
+
+#[test]
+fn appends_a_call_to_action_to_posts() {
+    let source = copy_fixture();
+    fs::create_dir_all(source.path().join("includes")).unwrap();
+    fs::write(
+        source.path().join("includes/newsletter.md"),
+        "**Subscribe** for more.\n",
+    )
+    .unwrap();
+    let code_sample = source.path().join("content/blog/code-sample.md");
+    let markdown = fs::read_to_string(&code_sample).unwrap();
+    fs::write(
+        &code_sample,
+        markdown.replacen("---\n", "---\nno_cta: true\n", 1),
+    )
+    .unwrap();
+    let config = Config {
+        cta: CtaConfig {
+            snippet: Some("includes/newsletter.md".to_string()),
+            sections: vec!["blog".to_string()],
+        },
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let cta = "<p><strong>Subscribe</strong> for more.</p>\n</div>";
+    assert!(read(&dist, "blog/hello-world.html").contains(cta));
+    assert!(!read(&dist, "blog/code-sample.html").contains("Subscribe"));
+    assert!(!read(&dist, "books/rust-book.html").contains("Subscribe"));
+    assert!(!read(&dist, "about.html").contains("Subscribe"));
+}
+
+#[test]
+fn badges_recently_updated_posts() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    let post = |name: &str, updated: &str| {
+        fs::write(
+            blog.join(format!("{}.md", name)),
+            format!(
+                "---\ntitle: {}\ndate: 2024-01-01\nupdated: {}\n---\nBody\n",
+                name, updated
+            ),
+        )
+        .unwrap();
+    };
+    post("fresh", "2024-04-01");
+    post("stale", "2024-02-01");
+    let build_with = |updated_badge_days| {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            // Built on 2024-04-11
+            source_date_epoch: Some(1_712_793_600),
+            updated_badge_days,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "blog.html")
+    };
+
+    let listing = build_with(Some(30));
+    assert!(listing.contains(r#"fresh</a><span class="updated">Updated</span>"#));
+    assert!(!listing.contains(r#"stale</a><span class="updated">"#));
+    assert!(!listing.contains(r#"World</a><span class="updated">"#));
+    assert!(!build_with(None).contains(r#"<span class="updated">"#));
+}
+
+#[test]
+fn lists_the_stalest_posts() {
+    let config = |stale_after_days| Config {
+        // Built on 2024-04-11
+        source_date_epoch: Some(1_712_793_600),
+        stale_after_days,
+        stale_posts_listed: 2,
+        ..Config::default()
+    };
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config(Some(30)), source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    // code-sample is 32 days old as well, but only two are listed
+    let stale: Vec<(&str, i64)> = report
+        .stale
+        .iter()
+        .map(|post| (post.last_touched.as_str(), post.age_days))
+        .collect();
+    assert_eq!(stale, [("2024-01-15", 87), ("2024-02-01", 70)]);
+    assert!(report.stale[0].url.ends_with("hello-world.html"));
+    assert!(report.warnings.is_empty());
+
+    let site = Site::new(config(None), source.path(), dist.path()).unwrap();
+    assert!(build(&site).unwrap().stale.is_empty());
+}
+
+#[test]
+fn requires_dates_per_section() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/undated.md"),
+        "---\ntitle: Undated\n---\nBody\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.sections.get_mut("blog").unwrap().require_date = true;
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+
+    // Book chapters and standalone pages go without
+    let warnings = build(&site).unwrap().warnings;
+    assert_eq!(warnings.len(), 1, "{:?}", warnings);
+    assert!(warnings[0].ends_with("undated.md has no `date`, which section `blog` requires"));
+    let issues: Vec<String> = check(&site)
+        .unwrap()
+        .iter()
+        .map(|issue| issue.to_string())
+        .filter(|issue| issue.contains("requires"))
+        .collect();
+    assert_eq!(issues.len(), 1);
+    assert!(issues[0].starts_with("warning: "));
+    assert!(issues[0].ends_with("undated.md: no `date`, which section `blog` requires"));
+}
+
+#[test]
+fn hashes_pages_without_the_build_time() {
+    let build_at = |source_date_epoch| {
+        build_fixture(Config {
+            content_hash: true,
+            source_date_epoch: Some(source_date_epoch),
+            ..Config::default()
+        })
+    };
+    let hash = |dist: &TempDir, path: &str| {
+        let page = read(dist, path);
+        let start = page.find(r#"<meta name="content-hash" content=""#).unwrap() + 34;
+        page[start..start + 16].to_string()
+    };
+    let first = build_at(1_712_793_600);
+    let second = build_at(1_712_880_000);
+
+    assert!(read(&first, "blog/hello-world.html").contains("2024-04-11T00:00:00+00:00"));
+    assert_eq!(
+        hash(&first, "blog/hello-world.html"),
+        hash(&second, "blog/hello-world.html")
+    );
+    assert_ne!(
+        hash(&first, "blog/hello-world.html"),
+        hash(&first, "blog/code-sample.html")
+    );
+    assert!(!read(&build_fixture(Config::default()), "index.html").contains("content-hash"));
+}
+
+#[test]
+fn reports_what_changed_since_the_previous_build() {
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    let build_once = || {
+        let config = Config {
+            content_hash: true,
+            diff_report: true,
+            latest_across: 0,
+            related_posts: 0,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap().diff.unwrap()
+    };
+    let first = build_once();
+    assert!(first
+        .added
+        .contains(&PathBuf::from("blog/hello-world.html")));
+    assert!(first.removed.is_empty() && first.changed.is_empty());
+
+    let blog = source.path().join("content/blog");
+    let code_sample = fs::read_to_string(blog.join("code-sample.md")).unwrap();
+    fs::write(
+        blog.join("code-sample.md"),
+        code_sample + "\nOne more line.\n",
+    )
+    .unwrap();
+    fs::remove_file(blog.join("long-read.md")).unwrap();
+    fs::write(
+        blog.join("new.md"),
+        "---\ntitle: New\ndate: 2024-05-01\n---\nBody\n",
+    )
+    .unwrap();
+    let second = build_once();
+    assert_eq!(second.added, [PathBuf::from("blog/new.html")]);
+    assert_eq!(second.removed, [PathBuf::from("blog/long-read.html")]);
+    assert!(second
+        .changed
+        .contains(&PathBuf::from("blog/code-sample.html")));
+    assert!(!second
+        .changed
+        .contains(&PathBuf::from("blog/hello-world.html")));
+
+    // Nothing changes when nothing did
+    assert!(build_once().changed.is_empty());
+}
+
+#[test]
+fn locks_the_output_while_building() {
+    let root = tempfile::tempdir().unwrap();
+    let dist = root.path().join("dist");
+    let lock = root.path().join("dist.lock");
+    let build_with = |config: Config| build(&Site::new(config, FIXTURE, &dist).unwrap());
+
+    // Held by this test's own process, which is running
+    fs::write(&lock, std::process::id().to_string()).unwrap();
+    let err = build_with(Config::default()).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<SiteError>(),
+        Some(SiteError::Locked { running: true, .. })
+    ));
+    assert!(!dist.join("index.html").exists());
+
+    let release = {
+        let lock = lock.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(300));
+            fs::remove_file(lock).unwrap();
+        })
+    };
+    build_with(Config {
+        on_locked_build: LockedBuild::Wait,
+        ..Config::default()
+    })
+    .unwrap();
+    release.join().unwrap();
+    assert!(!lock.exists());
+
+    // No process has an id this high
+    fs::write(&lock, "4000000000").unwrap();
+    let err = build_with(Config {
+        on_locked_build: LockedBuild::Wait,
+        ..Config::default()
+    })
+    .unwrap_err();
+    assert!(format!("{}", err).contains("--force-unlock"));
+    build_with(Config {
+        force_unlock: true,
+        ..Config::default()
+    })
+    .unwrap();
+    assert!(!lock.exists());
+}
+
+#[test]
+fn inlines_remote_content_through_a_cache() {
+    let source = copy_fixture();
+    let remote = tempfile::tempdir().unwrap();
+    let readme = remote.path().join("README.md");
+    fs::write(&readme, "From **afar**.\n").unwrap();
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/README.md", server.server_addr().to_ip().unwrap());
+    let served = readme.clone();
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = match fs::read(&served) {
+                Ok(body) => tiny_http::Response::from_data(body),
+                Err(_) => tiny_http::Response::from_data(vec![]).with_status_code(404),
+            };
+            let _ = request.respond(response);
+        }
+    });
+    let embed = |url: &str| {
+        fs::write(
+            source.path().join("content/blog/embed.md"),
+            format!("---\ntitle: Embed\n---\n{{{{< remote \"{}\" >}}}}\n", url),
+        )
+        .unwrap();
+    };
+    embed(&url);
+    let cache = tempfile::tempdir().unwrap();
+    let build_with = |enabled, cache_hours| {
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.remote.enabled = enabled;
+        config.remote.cache_hours = cache_hours;
+        config.remote.cache_dir = cache.path().to_path_buf();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).map(|report| (report, read(&dist, "blog/embed.html")))
+    };
+
+    let err = build_with(false, 24).unwrap_err();
+    assert!(format!("{:#}", err).contains("`enabled = true` under `[remote]`"));
+
+    let (_, page) = build_with(true, 24).unwrap();
+    assert!(page.contains("<p>From <strong>afar</strong>.</p>"));
+
+    // Gone from the source, but fresh in the cache
+    fs::remove_file(&readme).unwrap();
+    let (report, page) = build_with(true, 24).unwrap();
+    assert!(page.contains("afar") && report.warnings.is_empty());
+    // Stale, so it's fetched again, which fails
+    let (report, page) = build_with(true, 0).unwrap();
+    assert!(page.contains("afar"));
+    assert!(report
+        .warnings
+        .iter()
+        .any(|warning| warning.contains("used the copy cached")));
+
+    for entry in fs::read_dir(cache.path()).unwrap() {
+        fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+    let err = build_with(true, 24).unwrap_err();
+    assert!(format!("{:#}", err).contains("there's no cached copy"));
+
+    // Local files and curl options aren't fetched
+    for url in [
+        format!("file://{}", readme.display()),
+        "-o/tmp/x".to_string(),
+    ] {
+        embed(&url);
+        let err = build_with(true, 24).unwrap_err();
+        assert!(format!("{:#}", err).contains("isn't an http:// or https:// URL"));
+    }
+}
+
+#[test]
+fn inlines_small_images() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/icons.md"),
+        "---\ntitle: Icons\n---\n![Cover](/assets/images/cover.svg) ![Big](/assets/styles.png)\n",
+    )
+    .unwrap();
+    fs::write(source.path().join("assets/styles.png"), vec![0u8; 4096]).unwrap();
+    fs::write(
+        source.path().join("template/page.html"),
+        r#"<img src="{{ "/assets/images/cover.svg" | inline_image | safe }}" />"#,
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config.assets.inline_images_below = 1024;
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let data_uri = "data:image/svg+xml;base64,PHN2ZyB4bWxucz0iaHR0cDovL3d3dy53My5vcmcvMjAwMC9zdmciIHdpZHRoPSIyIiBoZWlnaHQ9IjMiPjwvc3ZnPgo=";
+    let post = read(&dist, "blog/icons.html");
+    assert!(post.contains(&format!(r#"<img src="{}" alt="Cover" />"#, data_uri)));
+    assert!(post.contains(r#"<img src="/assets/styles.png" alt="Big" />"#));
+    assert_eq!(
+        read(&dist, "about.html"),
+        format!(r#"<img src="{}" />"#, data_uri)
+    );
+    // Still copied for anything that links to it
+    assert!(dist.path().join("assets/images/cover.svg").exists());
+    // The post, about.html and thanks.html
+    assert_eq!(report.images_inlined, 3);
+}
+
+#[test]
+fn builds_the_same_output_on_one_thread() {
+    let config = |threads| Config {
+        reproducible: true,
+        threads,
+        ..Config::default()
+    };
+    let serial = build_fixture(config(Some(1)));
+    let parallel = build_fixture(config(None));
+    let files = |dist: &TempDir| {
+        WalkDir::new(dist.path())
+            .sort_by_file_name()
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .map(|entry| {
+                let path = entry
+                    .path()
+                    .strip_prefix(dist.path())
+                    .unwrap()
+                    .to_path_buf();
+                (path, fs::read(entry.path()).unwrap())
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(files(&serial), files(&parallel));
+}
+
+#[test]
+fn reads_templates_from_the_configured_directory() {
+    let source = copy_fixture();
+    let theme = source.path().join("theme");
+    fs::rename(source.path().join("template"), &theme).unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let config = Config {
+        template_dir: Some(theme),
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    assert!(read(&dist, "blog/hello-world.html").contains("Fixture footer"));
+}
+
+#[test]
+fn lists_sites_sharing_one_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("site.toml");
+    fs::write(
+        &path,
+        "[[sites]]\nname = \"personal\"\n\n\
+         [[sites]]\nname = \"docs\"\nsource = \"docs/src\"\ndist = \"docs/dist\"\n\
+         base_url = \"https://docs.example.com\"\ntemplate_dir = \"themes/docs\"\n",
+    )
+    .unwrap();
+    let config = Config::load(path.to_str().unwrap(), None).unwrap();
+    let [personal, docs] = config.sites.as_slice() else {
+        panic!("expected two sites");
+    };
+    assert_eq!(personal.name, "personal");
+    assert_eq!(personal.source, Path::new("src"));
+    assert_eq!(personal.dist, Path::new("dist"));
+    assert!(personal.base_url.is_none());
+    assert_eq!(docs.source, Path::new("docs/src"));
+    assert_eq!(docs.base_url.as_deref(), Some("https://docs.example.com"));
+    assert_eq!(docs.template_dir.as_deref(), Some(Path::new("themes/docs")));
+}
+
+#[test]
+fn inlines_markdown_includes() {
+    let dist = build_fixture(Config::default());
+    let intro = read(&dist, "books/first-book/intro.html");
+    assert!(intro.contains("<p>Before reading, install <strong>Rust</strong>.</p>"));
+    assert!(!intro.contains("include"));
+
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(blog.join("loop.md"), "{{< include \"a.md\" >}}\n").unwrap();
+    fs::create_dir_all(source.path().join("includes")).unwrap();
+    fs::write(
+        source.path().join("includes/a.md"),
+        "{{< include \"b.md\" >}}",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("includes/b.md"),
+        "{{< include \"a.md\" >}}",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let err = format!("{:#}", build(&site).unwrap_err());
+    assert!(err.contains("Include cycle"), "{}", err);
+    assert!(err.contains("a.md -> "), "{}", err);
+}
+
+#[test]
+fn skips_hidden_and_ignored_assets() {
+    let source = copy_fixture();
+    let assets = source.path().join("assets");
+    fs::create_dir_all(assets.join(".cache")).unwrap();
+    for junk in [
+        ".DS_Store",
+        ".cache/data",
+        "images/Thumbs.db",
+        "styles.css.swp",
+    ] {
+        fs::write(assets.join(junk), "junk").unwrap();
+    }
+    fs::write(assets.join("drafts.psd"), "layers").unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(Config::default());
+    let copied = |dist: &TempDir, path: &str| dist.path().join("assets").join(path).exists();
+    assert!(copied(&dist, "styles.css"));
+    assert!(copied(&dist, "drafts.psd"));
+    for junk in [".DS_Store", ".cache", "images/Thumbs.db", "styles.css.swp"] {
+        assert!(!copied(&dist, junk), "`{}` was copied", junk);
+    }
+
+    let mut config = Config::default();
+    config.assets.ignore = vec!["*.psd".to_string()];
+    config.assets.include_hidden = true;
+    let dist = build_with(config);
+    assert!(!copied(&dist, "drafts.psd"));
+    assert!(copied(&dist, ".cache/data"));
+    assert!(copied(&dist, "images/Thumbs.db"));
+}
+
+#[test]
+fn checks_content_without_building() {
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), FIXTURE, dist.path()).unwrap();
+    let issues = check(&site).unwrap();
+    assert!(issues.is_empty(), "{:?}", issues);
+    assert!(fs::read_dir(dist.path()).unwrap().next().is_none());
+
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("broken.md"),
+        "---\ndate: someday\n---\n\n[Gone](/blog/gone.html), [up](../about.html) and ![](/assets/images/cover.svg)\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("Hello-World.md"),
+        "---\ntitle: Twin\ndate: 2024-01-01\n---\n\nSee [the draft](old-draft.html).\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("old-draft.md"),
+        "---\ntitle: Old\ndate: 2020-01-01\ndraft: true\n---\n",
+    )
+    .unwrap();
+
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let issues: Vec<String> = check(&site)
+        .unwrap()
+        .iter()
+        .map(|issue| {
+            let file = issue.path.file_name().unwrap().to_string_lossy();
+            format!("{:?} {}: {}", issue.severity, file, issue.message)
+        })
+        .collect();
+    let expected = [
+        "Error broken.md: missing `title`",
+        "Error broken.md: unparseable date `someday`",
+        "Error broken.md: broken link `/blog/gone.html`",
+        "Warning broken.md: image `/assets/images/cover.svg` has no alt text",
+        "Error Hello-World.md: broken link `old-draft.html`",
+        "Error hello-world.md: same URL `/blog/hello-world.html` as ",
+        "Warning old-draft.md: draft is dated in the past (2020-01-01)",
+    ];
+    for expected in expected {
+        assert!(
+            issues.iter().any(|issue| issue.starts_with(expected)),
+            "`{}` not in {:#?}",
+            expected,
+            issues
+        );
+    }
+    assert_eq!(issues.len(), expected.len(), "{:#?}", issues);
+}
+
+#[test]
+fn reports_errors_by_kind() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    let build_source = || {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+        build(&site).unwrap_err()
+    };
+
+    fs::write(blog.join("broken.md"), "---\ntitle: [unclosed\n---\n").unwrap();
+    let err = build_source();
+    match err.downcast_ref::<SiteError>() {
+        Some(SiteError::Frontmatter { path, .. }) => assert!(path.ends_with("broken.md")),
+        other => panic!("expected a frontmatter error, got {:?}", other),
+    }
+    assert!(format!("{:#}", err).contains("Invalid frontmatter in"));
+
+    fs::write(
+        blog.join("broken.md"),
+        "---\ntitle: Broken\ndate: someday\n---\n",
+    )
+    .unwrap();
+    match build_source().downcast_ref::<SiteError>() {
+        Some(SiteError::Validation { path, message }) => {
+            assert!(path.ends_with("broken.md"));
+            assert!(message.contains("Unrecognized date `someday`"));
+        }
+        other => panic!("expected a validation error, got {:?}", other),
+    }
+    fs::remove_file(blog.join("broken.md")).unwrap();
+
+    let page = source.path().join("template/page.html");
+    fs::write(&page, "{{ no_such_variable }}").unwrap();
+    assert!(matches!(
+        build_source().downcast_ref::<SiteError>(),
+        Some(SiteError::Template { .. })
+    ));
+    fs::write(&page, "{% if %}").unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let Err(err) = Site::new(Config::default(), source.path(), dist.path()) else {
+        panic!("expected the templates to fail to load");
+    };
+    assert!(matches!(
+        err.downcast_ref::<SiteError>(),
+        Some(SiteError::TemplateLoad { .. })
+    ));
+}
+
+#[test]
+fn explains_missing_templates() {
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    let new_site = |template_dir: &Path| {
+        let config = Config {
+            template_dir: Some(template_dir.to_path_buf()),
+            ..Config::default()
+        };
+        Site::new(config, source.path(), dist.path())
+    };
+
+    let missing = source.path().join("no-templates");
+    let Err(err) = new_site(&missing) else {
+        panic!("expected a missing template directory to fail");
+    };
+    match err.downcast_ref::<SiteError>() {
+        Some(SiteError::NoTemplates { dir }) => assert_eq!(dir, &missing),
+        other => panic!("expected a no templates error, got {:?}", other),
+    }
+    assert!(err
+        .to_string()
+        .starts_with(&format!("No templates found in {}", missing.display())));
+    fs::create_dir(&missing).unwrap();
+    let Err(err) = new_site(&missing) else {
+        panic!("expected an empty template directory to fail");
+    };
+    assert!(matches!(
+        err.downcast_ref::<SiteError>(),
+        Some(SiteError::NoTemplates { .. })
+    ));
+
+    // Required templates are checked before anything is built
+    let template = source.path().join("template");
+    fs::remove_file(template.join("page.html")).unwrap();
+    fs::remove_file(template.join("blog_list.html")).unwrap();
+    let site = new_site(&template).unwrap();
+    let err = build(&site).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Missing templates in {}: `page.html`, `blog_list.html`",
+            template.display()
+        )
+    );
+    assert!(!dist.path().join("blog").exists());
+}
+
+#[test]
+fn resolves_slug_collisions() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/hello-world.html"),
+        "---\ntitle: Hello Again\ndate: 2024-05-01\n---\n<p>Hand written.</p>\n",
+    )
+    .unwrap();
+    let build_with = |on_slug_collision| {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            base_url: "https://example.com".to_string(),
+            on_slug_collision,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        let result = build(&site);
+        let same_url = check(&site)
+            .unwrap()
+            .iter()
+            .any(|issue| issue.message.starts_with("same URL"));
+        (result, dist, same_url)
+    };
+
+    let (result, _, same_url) = build_with(SlugCollision::Error);
+    let err = result.unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<SiteError>(),
+        Some(SiteError::Validation { path, .. }) if path.ends_with("blog/hello-world.md")
+    ));
+    assert!(err.to_string().contains("hello-world.html and "));
+    assert!(same_url);
+
+    // The first file name keeps the slug, the other moves everywhere it's linked
+    let (result, dist, same_url) = build_with(SlugCollision::Suffix);
+    result.unwrap();
+    assert!(!same_url);
+    assert!(read(&dist, "blog/hello-world.html").contains("<h1>Hello Again</h1>"));
+    assert!(read(&dist, "blog/hello-world-2.html").contains("<h1>Hello World</h1>"));
+    assert!(read(&dist, "blog.html").contains(r#"<a href="/blog/hello-world-2.html">"#));
+    assert!(read(&dist, "sitemap.xml").contains("https://example.com/blog/hello-world-2.html"));
+
+    let (result, dist, _) = build_with(SlugCollision::Path);
+    result.unwrap();
+    assert!(read(&dist, "blog/hello-world-md.html").contains("<h1>Hello World</h1>"));
+    assert!(read(&dist, "blog.html").contains(r#"<a href="/blog/hello-world-md.html">"#));
+}
+
+#[test]
+fn parses_dates_in_configured_formats() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/old.md"),
+        "---\ntitle: Old\ndate: Jan 2, 2024\nupdated: 2024-03-05\n---\n\nFrom the archive.\n",
+    )
+    .unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        (build(&site), dist)
+    };
+
+    let err = format!("{:#}", build_with(Config::default()).0.unwrap_err());
+    assert!(err.contains("Unrecognized date `Jan 2, 2024`"), "{}", err);
+    assert!(err.contains("old.md"), "{}", err);
+
+    let config = Config {
+        date_formats: vec!["%Y-%m-%d".to_string(), "%b %d, %Y".to_string()],
+        ..Config::default()
+    };
+    let (report, dist) = build_with(config);
+    report.unwrap();
+    let index: serde_json::Value = serde_json::from_str(&read(&dist, "index.json")).unwrap();
+    let old = index
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["slug"] == "old")
+        .unwrap();
+    assert_eq!(old["date"], "2024-01-02");
+}
+
+#[test]
+fn merges_config_profiles_over_the_base() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("site.toml");
+    fs::write(
+        &path,
+        r#"
+        base_url = "https://example.com"
+
+        [markdown]
+        code_copy = true
+        toc_max_level = 4
+
+        [profile.local]
+        base_url = "http://localhost:8000"
+        drafts = true
+
+        [profile.local.markdown]
+        code_copy = false
+        "#,
+    )
+    .unwrap();
+    let path = path.to_str().unwrap();
+
+    let base = Config::load(path, None).unwrap();
+    assert_eq!(base.base_url, "https://example.com");
+    assert!(!base.drafts);
+    assert!(base.markdown.code_copy);
+
+    let local = Config::load(path, Some("local")).unwrap();
+    assert_eq!(local.base_url, "http://localhost:8000");
+    assert!(local.drafts);
+    assert!(!local.markdown.code_copy);
+    // Keys the profile doesn't mention come from the base
+    assert_eq!(local.markdown.toc_max_level, 4);
+
+    let err = Config::load(path, Some("prod")).unwrap_err();
+    assert!(err.to_string().contains("Unknown profile `prod`"));
+}
+
+#[test]
+fn builds_markdown_files_regardless_of_extension_case() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(blog.join("Shout.MD"), "---\ntitle: Shout\n---\n\nLoud.\n").unwrap();
+    fs::write(
+        blog.join("long-form.Markdown"),
+        "---\ntitle: Long Form\n---\n\nLong.\n",
+    )
+    .unwrap();
+    fs::write(blog.join("notes.txt"), "Not content.").unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "blog/Shout.html").contains("<p>Loud.</p>"));
+    assert!(read(&dist, "blog/long-form.html").contains("<p>Long.</p>"));
+    assert!(!dist.path().join("blog/notes.html").exists());
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains("Shout") && listing.contains("Long Form"));
+}
+
+#[test]
+fn maps_frontmatter_aliases_to_their_keys() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/legacy.md"),
+        "---\ntitle: Legacy\nthumbnail: /assets/images/cover.svg\nsummary: From the old site.\n---\n\nOld.\n",
+    )
+    .unwrap();
+
+    let config = Config {
+        frontmatter_aliases: [("thumbnail", "image"), ("summary", "description")]
+            .map(|(alias, key)| (alias.to_string(), key.to_string()))
+            .into(),
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let post = read(&dist, "blog/legacy.html");
+    assert!(post.contains(r#"<img src="/assets/images/cover.svg" />"#));
+    let index: serde_json::Value = serde_json::from_str(&read(&dist, "index.json")).unwrap();
+    let legacy = index
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["slug"] == "legacy")
+        .unwrap();
+    assert_eq!(legacy["description"], "From the old site.");
+
+    assert_eq!(report.warnings.len(), 2);
+    assert!(report.warnings.iter().any(|warning| warning
+        .ends_with("legacy.md uses deprecated frontmatter key `thumbnail`, use `image` instead")));
+}
+
+#[test]
+fn renders_definition_lists_and_abbreviations() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/glossary.md"),
+        "---\ntitle: Glossary\n---\n\n\
+         Crate\n: A compilation unit\n: A package on crates.io\n\n\
+         Trait\n: Shared behaviour\n\n\
+         Just a paragraph.\n\n\
+         We write HTML and more HTML, but not HTMLX.\n\n\
+         ```\n*[KEEP]: Shown in code\nHTML\n```\n\n\
+         *[HTML]: HyperText Markup Language\n",
+    )
+    .unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "glossary.html")
+    };
+
+    let mut config = Config::default();
+    config.markdown.definition_lists = true;
+    config.markdown.abbreviations = true;
+    let page = build_with(config);
+    assert!(page.contains(
+        "<dl>\n<dt>Crate</dt>\n<dd>A compilation unit</dd>\n<dd>A package on crates.io</dd>\n\
+         <dt>Trait</dt>\n<dd>Shared behaviour</dd>\n</dl>\n"
+    ));
+    assert!(page.contains("<p>Just a paragraph.</p>"));
+    assert!(page.contains(
+        r#"<p>We write <abbr title="HyperText Markup Language">HTML</abbr> and more HTML, but not HTMLX.</p>"#
+    ));
+    assert!(page.contains("*[KEEP]: Shown in code\nHTML\n</code>"));
+    assert!(!page.contains("*[HTML]"));
+
+    // Both are opt-in
+    let page = build_with(Config::default());
+    assert!(page.contains("<p>Crate\n: A compilation unit"));
+    assert!(!page.contains("<abbr"));
+}
+
+#[test]
+fn applies_attribute_lists_when_enabled() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/attrs.md"),
+        "---\ntitle: Attrs\n---\n\n## Setup {#install .big}\n\n\
+         > Mind the gap {.callout}\n\n\
+         > Whole quote\n\n{.note data-kind=\"aside\"}\n\n\
+         - one\n- two\n\n{#steps}\n\n\
+         Braces {like this} stay.\n",
+    )
+    .unwrap();
+
+    let build_with = |attributes: bool| {
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.markdown.attributes = attributes;
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "attrs.html")
+    };
+
+    let page = build_with(true);
+    assert!(page.contains(r##"<h2 id="install" class="big">Setup</h2>"##));
+    assert!(page.contains(
+        r#"<blockquote>
+<p class="callout">Mind the gap</p>"#
+    ));
+    assert!(page.contains(
+        r#"<blockquote class="note" data-kind="aside">
+<p>Whole quote</p>"#
+    ));
+    assert!(page.contains("<ul id=\"steps\">\n<li>one</li>"));
+    assert!(page.contains("<p>Braces {like this} stay.</p>"));
+    assert!(!page.contains("{."));
+
+    let page = build_with(false);
+    assert!(page.contains("Mind the gap {.callout}"));
+    assert!(page.contains("Setup {#install .big}"));
+}
+
+#[cfg(unix)]
+#[test]
+fn normalizes_output_file_modes_when_configured() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let source = copy_fixture();
+    let script = source.path().join("assets/tool.sh");
+    fs::write(&script, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+    let mode = |dist: &TempDir, path: &str| {
+        fs::metadata(dist.path().join(path))
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777
+    };
+
+    let dist = build_with(Config::default());
+    assert_eq!(mode(&dist, "assets/tool.sh"), 0o755);
+
+    let config: Config = toml::from_str("file_mode = 0o644").unwrap();
+    let dist = build_with(config);
+    assert_eq!(mode(&dist, "assets/tool.sh"), 0o644);
+    assert_eq!(mode(&dist, "index.html"), 0o644);
+}
+
+#[test]
+fn treats_html_files_as_content() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/hand-made.html"),
+        "---\ntitle: Hand Made\ndate: 2024-05-01\n---\n<section class=\"custom\"><p>Made *by hand*</p></section>\n",
+    )
+    .unwrap();
+
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    // Laid out like any post, but the body isn't run through markdown
+    let post = read(&dist, "blog/hand-made.html");
+    assert!(post.contains(r#"<article class="blog">"#));
+    assert!(post.contains(r#"<section class="custom"><p>Made *by hand*</p></section>"#));
+    assert!(!post.contains("title: Hand Made"));
+    assert!(read(&dist, "blog.html").contains(r#"<a href="/blog/hand-made.html">Hand Made</a>"#));
+    assert!(read(&dist, "sitemap.xml").contains("https://example.com/blog/hand-made.html"));
+}
+
+#[test]
+fn writes_stubs_for_removed_pages() {
+    let source = copy_fixture();
+    let gone = source.path().join("content/_gone.toml");
+    fs::write(
+        &gone,
+        "\"/blog/old-post.html\" = \"/blog/hello-world.html\"\n\"/blog/dropped.html\" = \"gone\"\n",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "blog/dropped.html").contains("This page has been removed."));
+    assert!(read(&dist, "blog/old-post.html")
+        .contains(r#"<meta http-equiv="refresh" content="0; url=/blog/hello-world.html" />"#));
+    assert_eq!(
+        read(&dist, "_redirects"),
+        "/blog/dropped.html /blog/dropped.html 410!\n/blog/old-post.html /blog/hello-world.html 301\n"
+    );
+
+    // A page that still exists can't be marked as removed
+    fs::write(&gone, "\"/blog/hello-world.html\" = \"gone\"\n").unwrap();
+    let err = build(&site).unwrap_err();
+    assert!(err.to_string().contains("still exists"));
+}
+
+#[test]
+fn skips_assets_that_are_unchanged_since_the_last_build() {
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+
+    let report = build(&site).unwrap();
+    assert_eq!((report.assets_copied, report.assets_skipped), (2, 0));
+    let report = build(&site).unwrap();
+    assert_eq!((report.assets_copied, report.assets_skipped), (0, 2));
+
+    fs::write(
+        source.path().join("assets/styles.css"),
+        "body { margin: 1rem; }\n",
+    )
+    .unwrap();
+    let report = build(&site).unwrap();
+    assert_eq!((report.assets_copied, report.assets_skipped), (1, 1));
+    assert_eq!(read(&dist, "assets/styles.css"), "body { margin: 1rem; }\n");
+}
+
+#[test]
+fn writes_a_404_page_with_recent_posts() {
+    // Without a `404.html` template a bare page is written
+    let dist = build_fixture(Config::default());
+    let page = read(&dist, "404.html");
+    assert!(page.contains("<h1>Page not found</h1>"));
+    assert!(!page.contains("Recent posts"));
+
+    let mut config = Config::default();
+    config.not_found.recent_posts = 2;
+    config.not_found.search_url = Some("/search/".to_string());
+    let dist = build_fixture(config);
+    let page = read(&dist, "404.html");
+    assert!(page.contains(r#"<a href="/search/">Search the site</a>"#));
+    let latest = page.find("A Long Read").unwrap();
+    assert!(latest < page.find("Code Sample").unwrap());
+    assert!(!page.contains("Hello World"));
+
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("template/404.html"),
+        "{% extends \"base.html\" %}{% block content %}Lost? \
+         {% for post in recent_posts %}[{{ post.title }}]{% endfor %}{% endblock %}",
+    )
+    .unwrap();
+    let mut config = Config::default();
+    config.not_found.recent_posts = 1;
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    let page = read(&dist, "404.html");
+    assert!(page.contains("<main>Lost? [A Long Read]</main>"));
+    assert!(page.contains("Fixture footer"));
+}
+
+#[test]
+fn links_per_post_stylesheets_and_scripts() {
+    let source = copy_fixture();
+    fs::write(source.path().join("assets/chart.js"), "// chart\n").unwrap();
+    fs::write(
+        source.path().join("content/blog/charts.md"),
+        "---\ntitle: Charts\nextra_css: [/assets/styles.css, missing.css]\nextra_js: chart.js\n---\n\nBars.\n",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let post = read(&dist, "blog/charts.html");
+    assert!(post.contains(r#"<link rel="stylesheet" href="/assets/missing.css" />"#));
+    assert!(post.contains(r#"<script src="/assets/chart.js" defer></script>"#));
+    assert!(!read(&dist, "blog/hello-world.html").contains("<script"));
+
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0]
+        .ends_with("charts.md lists `missing.css` in extra_css, but there is no such asset"));
+}
+
+#[test]
+fn writes_section_and_combined_feeds() {
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.feed.limit = 3;
+    let dist = build_fixture(config);
+
+    let feed = read(&dist, "index.xml");
+    let item = |slug: &str| feed.find(&format!("<link>https://example.com/{}.html</link>", slug));
+    let long_read = item("blog/long-read").unwrap();
+    let code_sample = item("blog/code-sample").unwrap();
+    // Books are mixed in by date, and the oldest post falls past the limit
+    assert!(long_read < code_sample && code_sample < item("books/rust-book").unwrap());
+    assert_eq!(item("blog/hello-world"), None);
+    assert!(feed.contains("<pubDate>Mon, 01 Apr 2024 00:00:00 +0000</pubDate>"));
+
+    let blog_feed = read(&dist, "blog/index.xml");
+    assert!(blog_feed.contains("<title>Latest posts - blog</title>"));
+    assert!(blog_feed.contains("https://example.com/blog/hello-world.html"));
+    assert!(!blog_feed.contains("rust-book"));
+
+    // Without a base URL there's nothing to link to
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join("index.xml").exists());
+}
+
+#[test]
+fn configures_feeds_per_section() {
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.feed.limit = 1;
+    let blog = config.sections.get_mut("blog").unwrap();
+    blog.feed.content = Some(FeedContent::Full);
+    blog.feed.limit = Some(50);
+    let dist = build_fixture(config);
+
+    let blog_feed = read(&dist, "blog/index.xml");
+    assert!(blog_feed.contains(r#"xmlns:content="http://purl.org/rss/1.0/modules/content/""#));
+    assert!(blog_feed.contains("<content:encoded><![CDATA[<"));
+    assert_eq!(blog_feed.matches("<item>").count(), 3);
+
+    // Other feeds keep the global summary-only settings
+    let feed = read(&dist, "index.xml");
+    assert!(!feed.contains("content:encoded"));
+    assert_eq!(feed.matches("<item>").count(), 1);
+}
+
+#[test]
+fn labels_feeds_per_section() {
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.feed.description = Some("Everything I write".to_string());
+    let blog = config.sections.get_mut("blog").unwrap();
+    blog.feed.title = Some("The blog".to_string());
+    blog.feed.description = Some("Notes on Rust & the web".to_string());
+    let dist = build_fixture(config);
+
+    let blog_feed = read(&dist, "blog/index.xml");
+    assert!(blog_feed.contains("<title>The blog</title>"));
+    assert!(blog_feed.contains("<description>Notes on Rust &amp; the web</description>"));
+    assert!(read(&dist, "blog.html").contains(r#"title="The blog""#));
+
+    // Sections without their own fall back to `[feed]`
+    let books_feed = read(&dist, "books/index.xml");
+    assert!(books_feed.contains("<title>Latest posts - books</title>"));
+    assert!(books_feed.contains("<description>Everything I write</description>"));
+    let feed = read(&dist, "index.xml");
+    assert!(feed.contains("<title>Latest posts</title>"));
+    assert!(feed.contains("<description>Everything I write</description>"));
+}
+
+#[test]
+fn advertises_feeds_in_the_head() {
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    let dist = build_fixture(config);
+    let link = |title: &str, url: &str| {
+        format!(
+            r#"<link rel="alternate" type="application/rss+xml" title="{title}" href="{url}" />"#
+        )
+    };
+    let blog = link("Latest posts - blog", "https://example.com/blog/index.xml");
+
+    let index = read(&dist, "index.html");
+    assert!(index.contains(&link("Latest posts", "https://example.com/index.xml")));
+    assert!(!index.contains(&blog));
+    assert!(read(&dist, "blog.html").contains(&blog));
+    assert!(read(&dist, "blog/hello-world.html").contains(&blog));
+    assert!(read(&dist, "books/rust-book.html").contains(&link(
+        "Latest posts - books",
+        "https://example.com/books/index.xml"
+    )));
+    assert!(!read(&dist, "about.html").contains("application/rss+xml"));
+
+    // Sections are known by name, wherever they read from and list at
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.sections.get_mut("blog").unwrap().list_path = Some("/writing.html".to_string());
+    let mut library = config.sections.remove("books").unwrap();
+    library.dir = Some("books".to_string());
+    config.sections.insert("library".to_string(), library);
+    let dist = build_fixture(config);
+    assert!(read(&dist, "writing.html").contains(&blog));
+    let library = link(
+        "Latest posts - library",
+        "https://example.com/library/index.xml",
+    );
+    assert!(read(&dist, "library.html").contains(&library));
+    assert!(read(&dist, "books/rust-book.html").contains(&library));
+
+    let dist = build_fixture(Config::default());
+    assert!(!read(&dist, "index.html").contains("application/rss+xml"));
+}
+
+#[test]
+fn tidies_blank_lines_and_trailing_whitespace() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/spaced.html"),
+        "---\ntitle: Spaced\n---\n<p>One</p>   \n\n\n\n<p>Two</p>\n\n<p>Three</p>\n<pre>keep   \n\n\n\nthis</pre>\n",
+    )
+    .unwrap();
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let page = read(&build_with(Config::default()), "spaced.html");
+    assert!(page.contains("<p>One</p>   \n\n\n\n<p>Two</p>"));
+
+    let config = Config {
+        tidy: true,
+        ..Config::default()
+    };
+    let page = read(&build_with(config), "spaced.html");
+    assert!(page.contains("<p>One</p>\n\n<p>Two</p>\n\n<p>Three</p>"));
+    assert!(page.contains("<pre>keep   \n\n\n\nthis</pre>"));
+}
+
+#[test]
+fn shows_series_progress() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    let post = |name: &str, frontmatter: &str| {
+        fs::write(
+            blog.join(format!("{}.md", name)),
+            format!("---\ntitle: {}\n{}\n---\n\nBody.\n", name, frontmatter),
+        )
+        .unwrap();
+    };
+    post("part-one", "series: Intro\nseries_index: 1");
+    post("part-two", "series: Intro\nseries_index: 2");
+    post("late", "series: Intro\ndate: 2024-02-01");
+    post("later", "series: Intro\ndate: 2024-06-01");
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let progress = |slug: &str| {
+        let page = read(&dist, &format!("blog/{}.html", slug));
+        page.split(r#"<p class="series">"#)
+            .nth(1)
+            .map(|line| line.split("</p>").next().unwrap().to_string())
+    };
+    assert_eq!(progress("part-one").as_deref(), Some("Part 1 of 4"));
+    assert_eq!(progress("part-two").as_deref(), Some("Part 2 of 4"));
+    assert_eq!(progress("late").as_deref(), Some("Part 3 of 4"));
+    assert_eq!(progress("later").as_deref(), Some("Part 4 of 4"));
+    assert_eq!(progress("hello-world"), None);
+}
+
+#[test]
+fn relates_posts_by_series_then_tags() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    let post = |name: &str, frontmatter: &str| {
+        fs::write(
+            blog.join(format!("{}.md", name)),
+            format!("---\ntitle: {}\n{}\n---\n\nBody.\n", name, frontmatter),
+        )
+        .unwrap();
+    };
+    post("part-a", "series: Intro\nseries_index: 2\ntags: [rust]");
+    post("part-b", "series: Intro\nseries_index: 1");
+    post("part-c", "series: Intro");
+    post("tagged", "date: 2024-06-01\ntags: [Rust, web]");
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(Config::default());
+    let related = |dist: &TempDir, slug: &str| {
+        let page = read(dist, &format!("blog/{}.html", slug));
+        page.split(r#"<ul class="related">"#)
+            .nth(1)
+            .map(|list| {
+                let list = list.split("</ul>").next().unwrap();
+                list.split("<li>")
+                    .skip(1)
+                    .map(|item| item.trim_end_matches("</li>").to_string())
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default()
+    };
+    // Series entries in order come first, then shared tags fill the rest
+    assert_eq!(related(&dist, "part-a"), ["part-b", "part-c", "tagged"]);
+    assert_eq!(related(&dist, "hello-world"), ["tagged", "part-a"]);
+    assert!(related(&dist, "long-read").is_empty());
+
+    let dist = build_with(Config {
+        related_posts: 0,
+        ..Config::default()
+    });
+    assert!(related(&dist, "part-a").is_empty());
+}
+
+#[test]
+fn resolves_post_authors_to_profiles() {
+    let mut config = Config::default();
+    config.authors.insert(
+        "mumu".to_string(),
+        AuthorProfile {
+            name: "Muhsin".to_string(),
+            avatar: Some("/assets/mumu.png".to_string()),
+            links: vec![AuthorLink {
+                label: "GitHub".to_string(),
+                url: "https://github.com/mumudevx".to_string(),
+            }],
+            ..AuthorProfile::default()
+        },
+    );
+    let dist = build_fixture(config);
+
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post.contains(
+        r#"<aside class="author">Muhsin <img src="/assets/mumu.png" /> <a href="https://github.com/mumudevx">GitHub</a></aside>"#
+    ));
+    // Authors without a profile still get their name
+    let post = read(&dist, "blog/code-sample.html");
+    assert!(post.contains(r#"<aside class="author">Guest Writer</aside>"#));
+    assert!(!read(&dist, "blog/long-read.html").contains(r#"class="author""#));
+}
+
+#[test]
+fn keeps_fenced_yaml_out_of_frontmatter() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("yaml-example.md"),
+        "---\n\n```yaml\n---\ntitle: Not frontmatter\n---\n```\n\nAfter.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("config-guide.md"),
+        "---\ntitle: Config guide\ntldr: |\n  ```\n  indented fences are YAML\n  ```\n---\n```yaml\n---\nkey: value\n---\n```\n",
+    )
+    .unwrap();
+    fs::write(blog.join("unclosed.md"), "---\nStill content\n").unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let post = read(&dist, "blog/yaml-example.html");
+    assert!(post.contains("<h1>Untitled</h1>"));
+    assert!(post.contains("<hr />"));
+    assert!(post.contains("title: Not frontmatter"));
+    assert!(post.contains("<p>After.</p>"));
+
+    let post = read(&dist, "blog/config-guide.html");
+    assert!(post.contains("<h1>Config guide</h1>"));
+    assert!(post.contains("indented fences are YAML"));
+    assert!(post.contains("---\nkey: value\n---"));
+
+    let post = read(&dist, "blog/unclosed.html");
+    assert!(post.contains("<h1>Untitled</h1>") && post.contains("<p>Still content</p>"));
+}
+
+#[test]
+fn renders_tldr_boxes_from_frontmatter() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/summarized.md"),
+        "---\ntitle: Summarized\ndescription: Plain description\ntldr: |\n  Use **one** thread.\n\n  - Then [measure](/blog/hello-world.html)\n---\n\nBody\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let post = read(&dist, "blog/summarized.html");
+    assert!(post.contains(
+        "<aside class=\"tldr\"><p>Use <strong>one</strong> thread.</p>\n<ul>\n<li>Then <a href=\"/blog/hello-world.html\">measure</a></li>\n</ul>\n</aside>"
+    ));
+    assert!(!post.contains("Plain description"));
+    assert!(!read(&dist, "blog/hello-world.html").contains("tldr"));
+}
+
+#[test]
+fn migrates_frontmatter_to_canonical_form() {
+    let source = copy_fixture();
+    let path = source.path().join("content/blog/legacy.md");
+    let original = "---\ntags: [rust]\nthumbnail: /a.png\ndate: 15.01.2024\nextra: kept\ntitle: Legacy\n---\n\nBody  \n";
+    fs::write(&path, original).unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    config
+        .frontmatter_aliases
+        .insert("thumbnail".to_string(), "image".to_string());
+    config.date_formats.push("%d.%m.%Y".to_string());
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+
+    // A dry run only reports the change
+    let migrations = migrate(&site, false).unwrap();
+    let migration = migrations.iter().find(|m| m.path == path).unwrap();
+    assert!(migration.diff().contains("-date: 15.01.2024\n"));
+    assert!(migration.diff().contains("+date: 2024-01-15\n"));
+    assert_eq!(fs::read_to_string(&path).unwrap(), original);
+
+    migrate(&site, true).unwrap();
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "---\ntitle: Legacy\ndate: 2024-01-15\nimage: /a.png\ntags:\n- rust\nextra: kept\n---\n\nBody  \n"
+    );
+    // Already migrated files are left alone
+    assert!(migrate(&site, false).unwrap().is_empty());
+}
+
+#[test]
+fn configures_reading_time_estimates() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/gallery.md"),
+        "---\ntitle: Gallery\nreading_time: 12\n---\n\nMostly pictures.\n",
+    )
+    .unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(Config::default());
+    assert!(read(&dist, "blog/hello-world.html").contains("1 min read"));
+    assert!(read(&dist, "blog/gallery.html").contains("12 min read"));
+
+    let dist = build_with(Config {
+        reading_time_wpm: 7,
+        reading_time_label: "{} dk okuma".to_string(),
+        ..Config::default()
+    });
+    assert!(read(&dist, "blog/gallery.html").contains("12 dk okuma"));
+    assert!(read(&dist, "blog/hello-world.html")
+        .contains(r#"<span class="reading-time">2 dk okuma</span>"#));
+}
+
+#[test]
+fn folds_comment_directives_into_the_template() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/wide.md"),
+        "---\ntitle: Wide\n---\n\n<!-- layout: wide -->\n<!-- note: keep me -->\n\nBig table.\n\n\
+         ```html\n<!-- layout: narrow -->\n```\n",
+    )
+    .unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        read(&dist, "blog/wide.html")
+    };
+
+    let mut config = Config::default();
+    config.markdown.directives = vec!["layout".to_string()];
+    let page = build_with(config);
+    assert!(page.contains(r#"<article class="blog wide">"#));
+    assert!(!page.contains("<!-- layout: wide -->"));
+    assert!(page.contains("<!-- note: keep me -->"));
+    assert!(page.contains("&lt;!-- layout: narrow --&gt;"));
+
+    // Off by default, the comment is left in the page
+    let page = build_with(Config::default());
+    assert!(page.contains(r#"<article class="blog">"#));
+    assert!(page.contains("<!-- layout: wide -->"));
+}
+
+#[test]
+fn wraps_component_blocks_in_configured_html() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/callout.md"),
+        "---\ntitle: Callout\n---\n\n<Note>\nMind the **gap**.\n</Note>\n\n<Warning>\nHot.\n</Warning>\n\n\
+         ```\n<Note>\n```\n",
+    )
+    .unwrap();
+
+    let mut config = Config::default();
+    config.markdown.components.insert(
+        "Note".to_string(),
+        r#"<div class="callout note">{}</div>"#.to_string(),
+    );
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let page = squash(&read(&dist, "blog/callout.html"));
+    assert!(
+        page.contains(r#"<div class="callout note"> <p>Mind the <strong>gap</strong>.</p> </div>"#)
+    );
+    assert!(page.contains("<Warning>"));
+    assert!(page.contains("&lt;Note&gt;"));
+    assert!(report
+        .warnings
+        .iter()
+        .any(|warning| warning.ends_with("callout.md uses unknown component `Warning`")));
+}
+
+#[test]
+fn lists_the_latest_posts_across_sections_on_every_page() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/books/undated.md"),
+        "---\ntitle: Undated\n---\n\nSome day.\n",
+    )
+    .unwrap();
+    let build_with = |latest_across: usize| {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            latest_across,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(3);
+    let aside =
+        "<aside class=\"latest-across\"> [A Long Read] [Code Sample] [The Rust Book]</aside>";
+    for path in [
+        "index.html",
+        "about.html",
+        "blog/hello-world.html",
+        "books.html",
+    ] {
+        assert!(read(&dist, path).contains(aside), "{}", path);
+    }
+    // Undated posts come after every dated one
+    let dist = build_with(10);
+    assert!(read(&dist, "index.html").contains("[Hello World] [Introduction to the First Book]"));
+    assert!(read(&dist, "index.html").contains("[Undated]</aside>"));
+
+    let dist = build_with(0);
+    assert!(!read(&dist, "about.html").contains("latest-across"));
+}
+
+#[test]
+fn writes_tag_pages_in_a_stable_order() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("zoo.md"),
+        "---\ntitle: Zoo\ndate: 2024-06-01\ntags: [Zebra, Rust, apple]\n---\n\nAnimals.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("orchard.md"),
+        "---\ntitle: Orchard\ndate: 2023-09-01\ntags: apple\n---\n\nTrees.\n",
+    )
+    .unwrap();
+    let template = source.path().join("template");
+    fs::write(
+        template.join("tag.html"),
+        "{{ tag.name }}:{% for post in posts %} {{ post.slug }}{% endfor %}",
+    )
+    .unwrap();
+    fs::write(
+        template.join("tags.html"),
+        "{% for tag in tags %}{{ tag.slug }}={{ tag.posts | length }} {% endfor %}",
+    )
+    .unwrap();
+
+    let build_tags = || {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+    let dist = build_tags();
+    let index = read(&dist, "tags.html");
+    assert_eq!(index, "apple=2 rust=2 web=1 zebra=1 ");
+    assert_eq!(read(&dist, "tags/rust.html"), "rust: zoo hello-world");
+    assert_eq!(read(&dist, "tags/apple.html"), "apple: zoo orchard");
+
+    // Identical sources give byte for byte identical pages
+    assert_eq!(read(&build_tags(), "tags.html"), index);
+
+    let tags: serde_json::Value = serde_json::from_str(&read(&dist, "tags.json")).unwrap();
+    let counts: Vec<(&str, u64)> = tags
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|tag| {
+            (
+                tag["name"].as_str().unwrap(),
+                tag["count"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        counts,
+        [("apple", 2), ("rust", 2), ("Zebra", 1), ("web", 1)]
+    );
+    assert_eq!(tags[0]["url"], "/tags/apple.html");
+
+    // Tag pages are valid link targets once the site renders them
+    fs::write(
+        blog.join("links.md"),
+        "---\ntitle: Links\n---\n\nSee [rust](/tags/rust.html) and [all tags](/tags.html).\n",
+    )
+    .unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    assert!(!check(&site)
+        .unwrap()
+        .iter()
+        .any(|issue| issue.message.contains("broken link")));
+}
+
+#[test]
+fn writes_every_tags_posts_to_a_data_file() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/zoo.md"),
+        "---\ntitle: Zoo\ndate: 2024-06-01\ntags: [Rust]\n---\n\nAnimals.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let config = Config {
+        tags_full_json: true,
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let tags: serde_json::Value = serde_json::from_str(&read(&dist, "tags-full.json")).unwrap();
+    let slugs: Vec<&String> = tags.as_object().unwrap().keys().collect();
+    assert_eq!(slugs, ["rust", "web"]);
+    assert_eq!(
+        tags["rust"],
+        serde_json::json!([
+            {"slug": "zoo", "title": "Zoo", "date": "2024-06-01", "url": "/blog/zoo.html"},
+            {"slug": "hello-world", "title": "Hello World", "date": "2024-01-15", "url": "/blog/hello-world.html"},
+        ])
+    );
+
+    assert!(!build_fixture(Config::default())
+        .path()
+        .join("tags-full.json")
+        .exists());
+}
+
+#[test]
+fn writes_a_feed_per_tag() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("template/tag.html"),
+        r#"{% extends "base.html" %}{% block content %}{{ tag.name }}{% endblock %}"#,
+    )
+    .unwrap();
+    let build_with = |tags| {
+        let dist = tempfile::tempdir().unwrap();
+        let mut config = Config {
+            base_url: "https://example.com".to_string(),
+            clean_urls: true,
+            ..Config::default()
+        };
+        config.feed.tags = tags;
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(true);
+    let feed = read(&dist, "tags/rust/feed.xml");
+    assert!(feed.contains("<title>Latest posts - rust</title>"));
+    assert!(feed.contains("<link>https://example.com/tags/rust/</link>"));
+    assert!(feed.contains(
+        r#"<atom:link href="https://example.com/tags/rust/feed.xml" rel="self" type="application/rss+xml" />"#
+    ));
+    assert!(feed.contains("<link>https://example.com/blog/hello-world.html</link>"));
+    assert!(!feed.contains("code-sample"));
+    assert!(read(&dist, "tags/rust/index.html").contains(
+        r#"<link rel="alternate" type="application/rss+xml" title="Latest posts - rust" href="https://example.com/tags/rust/feed.xml" />"#
+    ));
+
+    let dist = build_with(false);
+    assert!(!dist.path().join("tags/rust/feed.xml").exists());
+    assert!(!read(&dist, "tags/rust/index.html").contains("feed.xml"));
+}
+
+#[test]
+fn builds_tag_urls_for_both_url_schemes() {
+    let source = copy_fixture();
+    let template = source.path().join("template");
+    fs::write(template.join("tag.html"), "{{ tag.url | safe }}").unwrap();
+    fs::write(
+        template.join("tags.html"),
+        "{% for tag in tags %}{{ tag.url | safe }} {% endfor %}",
+    )
+    .unwrap();
+    let build_tags = |clean_urls| {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            base_path: "/site".to_string(),
+            clean_urls,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_tags(false);
+    assert_eq!(
+        read(&dist, "tags.html"),
+        "/site/tags/rust.html /site/tags/web.html "
+    );
+    assert_eq!(read(&dist, "tags/rust.html"), "/site/tags/rust.html");
+
+    let dist = build_tags(true);
+    assert_eq!(
+        read(&dist, "tags/index.html"),
+        "/site/tags/rust/ /site/tags/web/ "
+    );
+    assert_eq!(read(&dist, "tags/rust/index.html"), "/site/tags/rust/");
+    assert!(!dist.path().join("tags.html").exists());
+    assert!(!dist.path().join("tags/rust.html").exists());
+    let tags: serde_json::Value = serde_json::from_str(&read(&dist, "tags.json")).unwrap();
+    assert_eq!(tags[0]["url"], "/site/tags/rust/");
+
+    // Links to the directories are valid targets
+    fs::write(
+        source.path().join("content/blog/links.md"),
+        "---\ntitle: Links\n---\n\nSee [rust](/tags/rust/) and [all tags](/tags/).\n",
+    )
+    .unwrap();
+    let config = Config {
+        clean_urls: true,
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    assert!(!check(&site)
+        .unwrap()
+        .iter()
+        .any(|issue| issue.message.contains("broken link")));
+}
+
+#[test]
+fn splits_excerpts_at_configured_separators() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("teaser.md"),
+        "---\ntitle: Teaser\n---\n\nThe *hook*.\n\n{{ break }}\n\nThe rest. <!-- more --> More.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("imported.md"),
+        "---\ntitle: Imported\n---\n\nShort intro.\n<!--more-->\nLong body.\n",
+    )
+    .unwrap();
+
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    // Only the default `<!-- more -->` is recognized out of the box
+    let dist = build_with(Config::default());
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains("<p>The <em>hook</em>.</p>\n<p>{{ break }}</p>\n<p>The rest.</p>\n"));
+    assert!(!listing.contains("Short intro."));
+
+    let mut config = Config::default();
+    config.markdown.excerpt_separators = ["<!-- more -->", "<!--more-->", "{{ break }}"]
+        .map(String::from)
+        .to_vec();
+    let dist = build_with(config);
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains(r#"<div class="excerpt"><p>The <em>hook</em>.</p>"#));
+    assert!(listing.contains(r#"<div class="excerpt"><p>Short intro.</p>"#));
+    assert!(!listing.contains("Long body."));
+
+    // The full post keeps everything but the separator
+    let post = read(&dist, "blog/teaser.html");
+    assert!(post.contains("<p>The rest. <!-- more --> More.</p>"));
+    assert!(!post.contains("{{ break }}"));
+}
+
+#[test]
+fn closes_html_excerpts_and_offers_plain_text() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/card.md"),
+        "---\ntitle: Card\n---\n\n<div class=\"card\">\n\nIntro *with* emphasis & more.\n\n<!-- more -->\n\nThe rest.\n\n</div>\n",
+    )
+    .unwrap();
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(Config::default());
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains(
+        "<div class=\"excerpt\"><div class=\"card\">\n<p>Intro <em>with</em> emphasis &amp; more.</p>\n</div></div>"
+    ));
+
+    let mut config = Config::default();
+    config.markdown.excerpt_format = ExcerptFormat::Text;
+    let dist = build_with(config);
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains(r#"<div class="excerpt">Intro with emphasis & more.</div>"#));
+}
+
+#[cfg(unix)]
+#[test]
+fn runs_build_hooks_in_order() {
+    let source = copy_fixture();
+    let config: Config = toml::from_str(
+        r#"
+        base_url = "https://example.com"
+
+        [[hooks]]
+        stage = "post_build"
+        command = 'test -f "$SITE_DIST_DIR/index.html" && echo "$SITE_BASE_URL" > "$SITE_DIST_DIR/deployed.txt"'
+
+        [[hooks]]
+        stage = "pre_build"
+        command = 'echo generated > "$SITE_SOURCE_DIR/assets/generated.txt"'
+        "#,
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    // Pre-build output is picked up by the build itself
+    assert_eq!(read(&dist, "assets/generated.txt"), "generated\n");
+    assert_eq!(read(&dist, "deployed.txt"), "https://example.com\n");
+
+    let config: Config =
+        toml::from_str("[[hooks]]\nstage = \"post_build\"\ncommand = \"exit 3\"").unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let err = build(&site).unwrap_err();
+    assert!(err.to_string().contains("Hook `exit 3` failed"));
+
+    // Hooks run where site.toml is and get absolute paths, whatever the source dir
+    let config: Config = toml::from_str(
+        r#"
+        [[hooks]]
+        stage = "pre_build"
+        command = 'test -f Cargo.toml && cd / && test -d "$SITE_SOURCE_DIR/content"'
+        "#,
+    )
+    .unwrap();
+    let site = Site::new(config, FIXTURE, dist.path()).unwrap();
+    build(&site).unwrap();
+}
+
+#[test]
+fn generates_favicons_and_their_links() {
+    let source = copy_fixture();
+    image::RgbaImage::new(600, 600)
+        .save(source.path().join("assets/favicon.png"))
+        .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config {
+        base_path: "/site".to_string(),
+        ..Config::default()
+    };
+    config.favicon.enabled = true;
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    for (name, size) in [
+        ("favicon-16x16.png", 16),
+        ("favicon-512x512.png", 512),
+        ("apple-touch-icon.png", 180),
+    ] {
+        assert_eq!(
+            image::image_dimensions(dist.path().join(name)).unwrap(),
+            (size, size)
+        );
+    }
+    let ico = fs::read(dist.path().join("favicon.ico")).unwrap();
+    // Reserved, type 1 for icons and three images
+    assert_eq!(ico[..6], [0, 0, 1, 0, 3, 0]);
+
+    let index = read(&dist, "index.html");
+    assert!(
+        index.contains(r#"<link rel="icon" href="/site/favicon.ico" sizes="16x16 32x32 48x48" />"#)
+    );
+    assert!(index.contains(
+        r#"<link rel="icon" type="image/png" sizes="192x192" href="/site/favicon-192x192.png" />"#
+    ));
+    assert!(index.contains(
+        r#"<link rel="apple-touch-icon" sizes="180x180" href="/site/apple-touch-icon.png" />"#
+    ));
+
+    // Icons newer than the source aren't generated again
+    let modified = || {
+        fs::metadata(dist.path().join("favicon.ico"))
+            .unwrap()
+            .modified()
+            .unwrap()
+    };
+    let before = modified();
+    build(&site).unwrap();
+    assert_eq!(modified(), before);
+
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join("favicon.ico").exists());
+    assert!(!read(&dist, "index.html").contains("favicon"));
+}
+
+#[test]
+fn passes_image_sizes_to_listings() {
+    let source = copy_fixture();
+    let images = source.path().join("assets/images");
+    image::RgbImage::new(400, 200)
+        .save(images.join("wide.jpg"))
+        .unwrap();
+    image::RgbImage::new(30, 20)
+        .save(images.join("default.png"))
+        .unwrap();
+    fs::write(
+        source.path().join("content/blog/photo.md"),
+        "---\ntitle: Photo\ndate: 2025-01-01\nimage: /assets/images/wide.jpg\n---\n\nA photo.\n",
+    )
+    .unwrap();
+
+    let config = Config {
+        default_image: "/assets/images/default.png".to_string(),
+        emit_json: true,
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "index.html")
+        .contains(r#"<img src="/assets/images/wide.jpg" width="400" height="200" />"#));
+    let post: serde_json::Value =
+        serde_json::from_str(&read(&dist, "blog/hello-world.json")).unwrap();
+    assert_eq!(post["image"], "/assets/images/default.png");
+    assert_eq!(
+        (post["image_width"].clone(), post["image_height"].clone()),
+        (30.into(), 20.into())
+    );
+    // SVGs have no size to read
+    let book: serde_json::Value =
+        serde_json::from_str(&read(&dist, "books/rust-book.json")).unwrap();
+    assert!(book["image_width"].is_null());
+}
+
+#[test]
+fn builds_only_the_given_file() {
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(Config::default(), source.path(), dist.path()).unwrap()).unwrap();
+
+    let blog = source.path().join("content/blog");
+    for (name, title) in [
+        ("hello-world.md", "Hello World"),
+        ("long-read.md", "A Long Read"),
+    ] {
+        let path = blog.join(name);
+        let post = fs::read_to_string(&path).unwrap();
+        let edited = post.replace(&format!("title: {}", title), "title: Edited");
+        fs::write(&path, edited).unwrap();
+    }
+    let only_config = |with_listings: bool| Config {
+        only: Some(blog.join("hello-world.md")),
+        with_listings,
+        ..Config::default()
+    };
+
+    build(&Site::new(only_config(false), source.path(), dist.path()).unwrap()).unwrap();
+    assert!(read(&dist, "blog/hello-world.html").contains("Edited"));
+    assert!(!read(&dist, "blog/long-read.html").contains("Edited"));
+    assert!(!read(&dist, "blog.html").contains("Edited"));
+
+    build(&Site::new(only_config(true), source.path(), dist.path()).unwrap()).unwrap();
+    assert!(read(&dist, "blog.html").contains(r#"<a href="/blog/long-read.html">Edited</a>"#));
+    assert!(!read(&dist, "blog/long-read.html").contains("Edited"));
+
+    let config = Config {
+        only: Some(source.path().join("site.toml")),
+        ..Config::default()
+    };
+    fs::write(source.path().join("site.toml"), "").unwrap();
+    let err = build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap_err();
+    assert!(err.to_string().contains("isn't a content file under"));
+}
+
+#[test]
+fn writes_responsive_image_variants() {
+    let source = copy_fixture();
+    let images = source.path().join("assets/images");
+    image::RgbImage::new(400, 200)
+        .save(images.join("wide.jpg"))
+        .unwrap();
+    image::RgbImage::new(100, 50)
+        .save(images.join("small.png"))
+        .unwrap();
+    fs::write(
+        source.path().join("content/blog/photos.md"),
+        "---\ntitle: Photos\n---\n\n![A *wide* shot](/assets/images/wide.jpg \"Wide\")\n\n\
+         ![Small](/assets/images/small.png)\n",
+    )
+    .unwrap();
+
+    let mut config = Config::default();
+    config.assets.responsive_images = true;
+    config.assets.responsive_widths = vec![120, 240];
+    config.assets.responsive_sizes = "100vw".to_string();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    assert_eq!(report.image_variants, 2);
+    let variant = image::image_dimensions(dist.path().join("assets/images/wide-240w.jpg")).unwrap();
+    assert_eq!(variant, (240, 120));
+    assert!(dist.path().join("assets/images/wide-120w.jpg").is_file());
+    // Images narrower than the largest width are used as they are
+    assert!(!dist.path().join("assets/images/small-120w.png").exists());
+
+    let post = read(&dist, "blog/photos.html");
+    assert!(post.contains(
+        r#"<img src="/assets/images/wide.jpg" alt="A wide shot" title="Wide" srcset="/assets/images/wide-120w.jpg 120w, /assets/images/wide-240w.jpg 240w" sizes="100vw" />"#
+    ));
+    assert!(post.contains(r#"<img src="/assets/images/small.png" alt="Small" />"#));
+
+    let report = build(&site).unwrap();
+    assert_eq!(report.image_variants, 0);
+}
+
+#[test]
+fn uses_the_canonical_host_for_absolute_urls() {
+    let build_with = |base_url: &str| {
+        build_fixture(Config {
+            base_url: base_url.to_string(),
+            canonical_host: Some("www.example.com".to_string()),
+            ..Config::default()
+        })
+    };
+
+    let dist = build_with("https://example.com");
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post.contains(
+        r#"<link rel="canonical" href="https://www.example.com/blog/hello-world.html" />"#
+    ));
+    assert!(post.contains(r#"redirectTo("www.example.com")"#));
+    assert!(read(&dist, "sitemap.xml").contains("<loc>https://www.example.com/</loc>"));
+    assert!(!read(&dist, "index.xml").contains("https://example.com"));
+
+    // A local preview isn't the same site, its URLs are kept
+    let dist = build_with("http://127.0.0.1:8000");
+    assert!(read(&dist, "sitemap.xml").contains("<loc>http://127.0.0.1:8000/</loc>"));
+}
+
+#[test]
+fn resolves_citations_in_opted_in_sections() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("references.toml"),
+        "[smith2020]\nauthor = \"Smith, J.\"\nyear = 2020\ntitle = \"Ownership & You\"\n\
+         publisher = \"Rust Press\"\n\n[doe]\ntitle = \"Borrowing\"\nurl = \"https://example.com/b\"\n",
+    )
+    .unwrap();
+    let chapter = "---\ntitle: Cited\n---\n\nAs shown [@doe], and again [@smith2020] \
+                   and [@doe], but not [@nobody].\n\n```\n[@smith2020]\n```\n";
+    fs::write(source.path().join("content/books/cited.md"), chapter).unwrap();
+    fs::write(source.path().join("content/blog/cited.md"), chapter).unwrap();
+
+    let mut config = Config::default();
+    config.sections.get_mut("books").unwrap().citations = true;
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let page = read(&dist, "books/cited.html");
+    assert!(page.contains(
+        "As shown <sup class=\"citation\"><a href=\"#ref-doe\">[1]</a></sup>, and again \
+         <sup class=\"citation\"><a href=\"#ref-smith2020\">[2]</a></sup> and \
+         <sup class=\"citation\"><a href=\"#ref-doe\">[1]</a></sup>, but not [@nobody]."
+    ));
+    assert!(page.contains("<code>[@smith2020]\n</code>"));
+    assert!(page.contains(
+        "<ol>\n<li id=\"ref-doe\"><a href=\"https://example.com/b\"><cite>Borrowing</cite></a>.</li>\n\
+         <li id=\"ref-smith2020\">Smith, J. (2020). <cite>Ownership &amp; You</cite>. Rust Press.</li>\n</ol>"
+    ));
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].ends_with("cited.md cites unknown reference `nobody`"));
+
+    // Sections that don't opt in keep the markers as they are
+    assert!(read(&dist, "blog/cited.html").contains("As shown [@doe]"));
+}
+
+#[test]
+fn shows_updated_dates_past_the_threshold() {
+    let source = copy_fixture();
+    let blog = source.path().join("content/blog");
+    fs::write(
+        blog.join("typo.md"),
+        "---\ntitle: Typo\ndate: 2024-05-01\nupdated: 2024-05-03\n---\n\nFixed.\n",
+    )
+    .unwrap();
+    fs::write(
+        blog.join("revised.md"),
+        "---\ntitle: Revised\ndate: 2024-05-01\nupdated: 2024-06-01\n---\n\nRewritten.\n",
+    )
+    .unwrap();
+
+    let config = Config {
+        show_updated_after_days: 7,
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "blog/revised.html").contains("Updated on 2024-06-01"));
+    assert!(!read(&dist, "blog/typo.html").contains("Updated on"));
+    assert!(!read(&dist, "blog/hello-world.html").contains("Updated on"));
+}
+
+#[test]
+fn builds_only_a_placeholder_in_maintenance_mode() {
+    let source = copy_fixture();
+    let mut config = Config::default();
+    config.maintenance.enabled = true;
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        (build(&site), dist)
+    };
+
+    let (report, _dist) = build_with(config);
+    assert!(report
+        .unwrap_err()
+        .to_string()
+        .contains("`maintenance.html` template"));
+
+    fs::write(
+        source.path().join("template/maintenance.html"),
+        "{% extends \"base.html\" %}{% block content %}Back soon{% endblock %}",
+    )
+    .unwrap();
+    let mut config = Config::default();
+    config.maintenance.enabled = true;
+    let (report, dist) = build_with(config);
+    report.unwrap();
+
+    let index = read(&dist, "index.html");
+    assert!(index.contains("<main>Back soon</main>"));
+    assert!(index.contains(r#"<meta name="robots" content="noindex" />"#));
+    assert_eq!(read(&dist, "404.html"), index);
+    assert!(dist.path().join("assets/styles.css").is_file());
+    assert!(!dist.path().join("assets/images").exists());
+    assert!(!dist.path().join("blog.html").exists());
+    assert!(!dist.path().join("sitemap.xml").exists());
+}
+
+#[test]
+fn splits_long_chapters_at_headings() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/books/epic.md"),
+        "---\ntitle: Epic\n---\n\nPrologue.\n\n## Rise\n\nUp.\n\n### Detail\n\nMore.\n\n\
+         ```\n## Not a split\n```\n\n## Fall\n\nDown.\n\n## End\n\nDone.\n",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("content/books/whole.md"),
+        "---\ntitle: Whole\nsplit_on: none\n---\n\n## One\n\nA.\n\n## Two\n\nB.\n",
+    )
+    .unwrap();
+
+    let mut config = Config::default();
+    config.sections.get_mut("books").unwrap().split_on = Some("h2".to_string());
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let first = read(&dist, "books/epic.html");
+    assert!(first.contains("<p>Prologue.</p>") && first.contains("<p>More.</p>"));
+    assert!(first.contains("## Not a split"));
+    assert!(!first.contains("Down."));
+    assert!(first.contains(r#"1/3 <a href="/books/epic-2.html" rel="next">Next</a>"#));
+    // The table of contents reaches into the other pages
+    assert!(first.contains(r##"<a href="#rise">Rise</a>"##));
+    assert!(first.contains(r##"<a href="#detail">Detail</a>"##));
+    assert!(first.contains(r##"<a href="/books/epic-3.html#end">End</a>"##));
+
+    let second = read(&dist, "books/epic-2.html");
+    assert!(second.contains(r#"<h2 id="fall">Fall</h2>"#));
+    assert!(second.contains(r##"<a href="/books/epic.html#rise">Rise</a>"##));
+    assert!(second.contains(
+        r#"<a href="/books/epic.html" rel="prev">Previous</a> 2/3 <a href="/books/epic-3.html" rel="next">Next</a>"#
+    ));
+    assert!(read(&dist, "books/epic-3.html").contains("<p>Done.</p>"));
+
+    assert!(read(&dist, "books/whole.html").contains("<p>B.</p>"));
+    assert!(!dist.path().join("books/whole-2.html").exists());
+    // Listings still link the first page only
+    assert_eq!(read(&dist, "books.html").matches("epic").count(), 1);
+}
+
+#[test]
+fn exposes_all_frontmatter_as_meta() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/tricky.md"),
+        "---\ntitle: Tricky\ndifficulty: advanced\nlevel: 3\nverified: true\nsteps: [a, b]\n---\n\nHard.\n",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("template/page.html"),
+        "{{ meta.title }} {{ meta.difficulty | default(value=\"none\") }}",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("template/blog_detail.html"),
+        "{% if meta.difficulty %}{{ meta.difficulty }} {{ meta.level + 1 }} \
+         {% if meta.verified %}verified{% endif %} {{ meta.steps | join(sep=\"+\") }}{% endif %}",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert_eq!(read(&dist, "blog/tricky.html"), "advanced 4 verified a+b");
+    assert_eq!(read(&dist, "about.html"), "About none");
+}
+
+#[test]
+fn shifts_heading_levels_per_section_and_post() {
+    let source = copy_fixture();
+    let chapter = "# Part\n\n##### Deep\n\n###### Deepest\n";
+    fs::write(
+        source.path().join("content/books/shifted.md"),
+        format!("---\ntitle: Shifted\n---\n\n{}", chapter),
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("content/books/unshifted.md"),
+        format!(
+            "---\ntitle: Unshifted\nheading_shift: 0\n---\n\n{}",
+            chapter
+        ),
+    )
+    .unwrap();
+
+    let mut config = Config::default();
+    config.sections.get_mut("books").unwrap().heading_shift = 1;
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    // Shifted headings count for the table of contents at their new level
+    let page = read(&dist, "books/shifted.html");
+    assert!(page.contains(r#"<h2 id="part">Part</h2>"#));
+    assert!(page.contains("<h6>Deep</h6>\n<h6>Deepest</h6>"));
+    assert!(read(&dist, "books/unshifted.html").contains("<h1>Part</h1>"));
+}
+
+#[test]
+fn inlines_configured_stylesheets() {
+    let source = copy_fixture();
+    fs::create_dir_all(source.path().join("assets/css")).unwrap();
+    fs::write(
+        source.path().join("assets/css/critical.css"),
+        "body { margin: 0; }\n",
+    )
+    .unwrap();
+
+    let config = Config {
+        inline_css: vec!["css/critical.css".to_string()],
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(read(&dist, "index.html").contains("<style>body { margin: 0; }\n</style>"));
+    assert!(read(&dist, "blog/hello-world.html").contains("<style>body { margin: 0; }"));
+    assert!(dist.path().join("assets/css/critical.css").is_file());
+    assert!(!read(&build_fixture(Config::default()), "index.html").contains("<style>"));
+
+    let config = Config {
+        inline_css: vec!["missing.css".to_string()],
+        ..Config::default()
+    };
+    let err = Site::new(config, source.path(), dist.path()).err().unwrap();
+    assert!(err.to_string().contains("Failed to read inline stylesheet"));
+}
+
+#[test]
+fn lists_recently_updated_posts() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("template/updates.html"),
+        "{% for post in posts %}[{{ post.slug }} {{ post.date }} {{ post.updated }}]{% endfor %}",
+    )
+    .unwrap();
+    let build_updates = || {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    // Nothing was revised, so there's no page
+    assert!(!build_updates().path().join("updates.html").exists());
+
+    let days_ago = |days| {
+        (chrono::Local::now().date_naive() - chrono::Duration::days(days))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let blog = source.path().join("content/blog");
+    for (slug, updated) in [("recent", 3), ("latest", 1), ("stale", 400)] {
+        fs::write(
+            blog.join(format!("{}.md", slug)),
+            format!(
+                "---\ntitle: {}\ndate: 2020-01-01\nupdated: {}\n---\n\nText.\n",
+                slug,
+                days_ago(updated)
+            ),
+        )
+        .unwrap();
+    }
+
+    let page = read(&build_updates(), "updates.html");
+    assert_eq!(
+        page,
+        format!(
+            "[latest 2020-01-01 {}][recent 2020-01-01 {}]",
+            days_ago(1),
+            days_ago(3)
+        )
+    );
+}
+
+#[test]
+fn lists_featured_posts_across_sections() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("template/featured.html"),
+        "{% for post in posts %}[{{ post.slug }}]{% endfor %}",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("template/homepage.html"),
+        "{% for post in featured_posts %}({{ post.slug }}){% endfor %}",
+    )
+    .unwrap();
+    let build_featured = || {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    // Nothing is featured, so there's no page
+    let dist = build_featured();
+    assert!(!dist.path().join("featured.html").exists());
+    assert_eq!(read(&dist, "index.html"), "");
+
+    for path in ["content/blog/code-sample.md", "content/books/rust-book.md"] {
+        let path = source.path().join(path);
+        let markdown = fs::read_to_string(&path).unwrap();
+        fs::write(
+            &path,
+            markdown.replacen("---\n", "---\nfeatured: true\n", 1),
+        )
+        .unwrap();
+    }
+
+    let dist = build_featured();
+    assert_eq!(read(&dist, "featured.html"), "[code-sample][rust-book]");
+    assert_eq!(read(&dist, "index.html"), "(code-sample)(rust-book)");
+}
+
+#[test]
+fn sets_the_open_graph_type() {
+    let source = copy_fixture();
+    let about = source.path().join("content/about.md");
+    let markdown = fs::read_to_string(&about).unwrap();
+    fs::write(
+        &about,
+        markdown.replacen("---\n", "---\nog_type: profile\n", 1),
+    )
+    .unwrap();
+    let mut config = Config::default();
+    config.sections.get_mut("books").unwrap().og_type = Some("book".to_string());
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let og_type = |path| {
+        let page = read(&dist, path);
+        let start = page.find(r#"<meta property="og:type" content=""#).unwrap() + 34;
+        page[start..start + page[start..].find('"').unwrap()].to_string()
+    };
+    assert_eq!(og_type("index.html"), "website");
+    assert_eq!(og_type("blog.html"), "website");
+    assert_eq!(og_type("blog/hello-world.html"), "article");
+    assert_eq!(og_type("books/rust-book.html"), "book");
+    assert_eq!(og_type("about.html"), "profile");
+    assert_eq!(og_type("thanks.html"), "article");
+}
+
+#[test]
+fn hashes_assets_for_subresource_integrity() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("assets/app.js"),
+        "console.log(1);\n".repeat(20),
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("template/page.html"),
+        r#"<link href="/assets/styles.css" integrity="{{ "/assets/styles.css" | integrity | safe }}" crossorigin="anonymous" /><script src="/assets/app.js" integrity="{{ "/assets/app.js?v=2" | integrity | safe }}"></script>"#,
+    )
+    .unwrap();
+    let build_site = || {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+        build(&site).map(|_| dist)
+    };
+
+    let page = read(&build_site().unwrap(), "about.html");
+    assert!(page.contains(
+        r#"integrity="sha384-R5nvAe8jokeUbp5+BXihIeTrgJW8fUtXennQF0YFKBIDvXYRO9tUFBAlhGyKEXMW""#
+    ));
+    assert!(page.contains(
+        r#"integrity="sha384-SX5U6FVpCsaArCnOdwM/PUdefS4NbBwYc2zHyM61yV/HinQSSaUd/3DREDNhsGL7""#
+    ));
+
+    fs::write(
+        source.path().join("template/page.html"),
+        r#"{{ "https://cdn.example.com/app.js" | integrity }}"#,
+    )
+    .unwrap();
+    let err = build_site().err().unwrap();
+    assert!(format!("{:#}", err).contains("only works on local assets"));
+}
+
+#[test]
+fn fails_on_undefined_template_conditions() {
+    let source = copy_fixture();
+    let build_with = |strict_templates| {
+        let dist = tempfile::tempdir().unwrap();
+        let config = Config {
+            strict_templates,
+            ..Config::default()
+        };
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).map(|_| dist)
+    };
+    build_with(true).unwrap();
+
+    fs::write(
+        source.path().join("template/page.html"),
+        "{% extends \"base.html\" %}{% block content %}\
+         {% for post in [1] %}{% if loop.first and post %}{% endif %}{% endfor %}\
+         {% if edited is defined and edited %}{{ edited }}{% endif %}\
+         {% if reviewer | default(value=false) %}{% endif %}\
+         {% if updated %}Updated {{ updated }}{% endif %}{% endblock %}",
+    )
+    .unwrap();
+    // Lenient by default, so the block quietly goes missing
+    let about = read(&build_with(false).unwrap(), "about.html");
+    assert!(!about.contains("Updated"));
+
+    let err = format!("{:#}", build_with(true).err().unwrap());
+    assert!(err.contains("about.md") || err.contains("thanks.md"));
+    assert!(err.contains("'page.html' tests variables that aren't in the context: `updated`."));
+    assert!(!err.contains("edited") && !err.contains("reviewer") && !err.contains("post`"));
+}
+
+#[test]
+fn buckets_reading_times() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("template/blog_list.html"),
+        "{% for post in posts %}[{{ post.slug }} {{ post.reading_bucket }}]{% endfor %}",
+    )
+    .unwrap();
+    let long_read = source.path().join("content/blog/long-read.md");
+    let markdown = fs::read_to_string(&long_read).unwrap();
+    fs::write(
+        &long_read,
+        markdown.replacen("---\n", "---\nreading_time: 12\n", 1),
+    )
+    .unwrap();
+    let build_with = |config: Config| {
+        let dist = tempfile::tempdir().unwrap();
+        let site = Site::new(config, source.path(), dist.path()).unwrap();
+        build(&site).unwrap();
+        dist
+    };
+
+    let dist = build_with(Config::default());
+    let listing = read(&dist, "blog.html");
+    assert!(listing.contains("[hello-world Quick read]"));
+    assert!(listing.contains("[long-read Long read]"));
+
+    let config = Config {
+        reading_buckets: vec![ReadingBucket::new(Some(5), "Short")],
+        ..Config::default()
+    };
+    let listing = read(&build_with(config), "blog.html");
+    assert!(listing.contains("[hello-world Short]"));
+    assert!(listing.contains("[long-read ]"));
+}
+
+#[test]
+fn transliterates_turkish_slugs() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/turkce.md"),
+        "---\ntitle: Türkçe\n---\n\n## İstanbul'da Çay Şöleni\n\n## 🎉🎉\n",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("content/blog/yazilim.md"),
+        "---\ntitle: Yazılım\ntags: [Yazılım, Güvenlik]\n---\n\nMetin.\n",
+    )
+    .unwrap();
+    fs::write(source.path().join("template/tag.html"), "{{ tag.name }}").unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let page = read(&dist, "turkce.html");
+    for id in ["istanbul-da-cay-soleni", "u1f389-u1f389"] {
+        assert!(
+            page.contains(&format!(r#"id="{}""#, id)),
+            "missing id `{}`",
+            id
+        );
+    }
+    assert_eq!(read(&dist, "tags/yazilim.html"), "Yazılım");
+    assert_eq!(read(&dist, "tags/guvenlik.html"), "Güvenlik");
+}
+
+#[test]
+fn only_writes_posts_changed_since_a_date() {
+    let source = copy_fixture();
+    // Copies are fresh, backdate them so only the frontmatter dates count
+    let long_ago = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(86400);
+    for entry in WalkDir::new(source.path().join("content")) {
+        let entry = entry.unwrap();
+        if entry.file_type().is_file() {
+            fs::File::options()
+                .write(true)
+                .open(entry.path())
+                .unwrap()
+                .set_modified(long_ago)
+                .unwrap();
+        }
+    }
+
+    let config = Config {
+        since: chrono::NaiveDate::from_ymd_opt(2024, 3, 1),
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(dist.path().join("blog/long-read.html").is_file());
+    assert!(dist.path().join("blog/code-sample.html").is_file());
+    assert!(!dist.path().join("blog/hello-world.html").exists());
+    assert!(!dist.path().join("about.html").exists());
+    // Listings are still built from every post
+    assert_eq!(
+        listed_slugs(&read(&dist, "blog.html")),
+        ["code-sample", "hello-world", "long-read"]
+    );
+
+    // Editing a file counts as a change too
+    fs::write(
+        source.path().join("content/about.md"),
+        "---\ntitle: About\n---\n\nEdited.\n",
+    )
+    .unwrap();
+    build(&site).unwrap();
+    assert!(read(&dist, "about.html").contains("Edited."));
+}
+
+#[test]
+fn copies_images_next_to_their_posts() {
+    let source = copy_fixture();
+    let trip = source.path().join("content/blog/trip");
+    fs::create_dir_all(trip.join("images")).unwrap();
+    fs::write(trip.join("images/cat.png"), "not really a png").unwrap();
+    fs::write(
+        trip.join("index.md"),
+        "---\ntitle: Trip\ndate: 2024-05-01\n---\n\n\
+         ![Cat](images/cat.png)\n\n[Full size](./images/cat.png) [Gone](images/gone.png)\n",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    assert!(dist.path().join("blog/trip/images/cat.png").is_file());
+    let post = read(&dist, "blog/trip/index.html");
+    assert!(post.contains(r#"<img src="/blog/trip/images/cat.png" alt="Cat" />"#));
+    assert!(post.contains(r#"<a href="/blog/trip/images/cat.png">Full size</a>"#));
+    assert!(post.contains(r#"<a href="images/gone.png">Gone</a>"#));
+    // The co-located image is a valid link target, the missing one isn't
+    let broken: Vec<String> = check(&site)
+        .unwrap()
+        .into_iter()
+        .filter(|issue| issue.message.contains("broken link"))
+        .map(|issue| issue.message)
+        .collect();
+    assert_eq!(broken.len(), 1, "{:?}", broken);
+    assert!(broken[0].contains("images/gone.png"));
+}
+
+#[test]
+fn validates_rendered_html_on_request() {
+    let dist = tempfile::tempdir().unwrap();
+    let config = || Config {
+        validate_html: true,
+        ..Config::default()
+    };
+    let site = Site::new(config(), FIXTURE, dist.path()).unwrap();
+    let report = build(&site).unwrap();
+    assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/broken.md"),
+        "---\ntitle: Broken\ndate: 2024-05-01\n---\n\n\
+         <div class=\"note\">\n\nNever closed.\n\n\
+         <span id=\"twice\">One</span> <span id=\"twice\">Two</span></em>\n",
+    )
+    .unwrap();
+    let site = Site::new(config(), source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+    let warnings: Vec<&str> = report
+        .warnings
+        .iter()
+        .map(|warning| warning.split(" renders ").nth(1).unwrap())
+        .collect();
+    assert_eq!(warnings.len(), 3, "{:?}", warnings);
+    assert!(warnings[0].starts_with("a duplicate id `twice` on line "));
+    assert!(warnings[1].starts_with("a stray `</em>` on line "));
+    assert!(warnings[2].starts_with("an unclosed `<div>` on line "));
+    assert!(report.warnings[0].contains("broken.md"));
+
+    let site = Site::new(
+        Config {
+            strict: true,
+            ..config()
+        },
+        source.path(),
+        dist.path(),
+    )
+    .unwrap();
+    assert!(build(&site).is_err());
+}
+
+#[test]
+fn features_the_latest_post_on_the_homepage() {
+    let index = read(&build_fixture(Config::default()), "index.html");
+    assert!(index
+        .contains(r#"<article class="featured"><a href="/blog/long-read.html">A Long Read</a>"#));
+    let recent = index.split(r#"<ul class="recent">"#).nth(1).unwrap();
+    assert_eq!(
+        listed_slugs(recent),
+        ["code-sample", "rust-book", "hello-world"]
+    );
+
+    // Asking for more posts than there are lists what there is
+    let mut config = Config::default();
+    config.homepage.recent_posts = 10;
+    let index = read(&build_fixture(config), "index.html");
+    let recent = index.split(r#"<ul class="recent">"#).nth(1).unwrap();
+    assert_eq!(
+        listed_slugs(recent),
+        ["code-sample", "rust-book", "hello-world"]
+    );
+}
+
+#[test]
+fn groups_listings_by_month() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/march.md"),
+        "---\ntitle: March\ndate: 2024-03-20\n---\n\nAnother one.\n",
+    )
+    .unwrap();
+    let mut config = Config::default();
+    config.sections.get_mut("blog").unwrap().group_listing_by = ListingGroup::Month;
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap();
+
+    let listing = read(&dist, "blog.html");
+    let groups: Vec<&str> = listing
+        .lines()
+        .filter(|line| line.starts_with(r#"<h2 class="month">"#))
+        .collect();
+    assert_eq!(
+        groups,
+        [
+            r#"<h2 class="month">April 2024: long-read </h2>"#,
+            r#"<h2 class="month">March 2024: march code-sample </h2>"#,
+            r#"<h2 class="month">January 2024: hello-world </h2>"#,
+        ]
+    );
+    // The flat list is still there too
+    assert_eq!(listed_slugs(&listing).len(), 4);
+}
+
+#[test]
+fn looks_up_posts_by_slug_in_templates() {
+    let source = copy_fixture();
+    let page = source.path().join("template/page.html");
+    let template = fs::read_to_string(&page).unwrap().replace(
+        "{% endblock %}",
+        r#"{% set see_also = post(slug="hello-world") %}<aside><a href="{{ see_also.url | safe }}">{{ see_also.title }}</a></aside>
+{% endblock %}"#,
+    );
+    fs::write(&page, template).unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    // `about.md` comes before the blog posts it links to in file order
+    assert!(read(&dist, "about.html")
+        .contains(r#"<aside><a href="/blog/hello-world.html">Hello World</a></aside>"#));
+
+    let template = fs::read_to_string(&page)
+        .unwrap()
+        .replace("hello-world", "no-such-post");
+    fs::write(&page, template).unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let error = format!("{:?}", build(&site).unwrap_err());
+    assert!(
+        error.contains("There is no post with slug `no-such-post`"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn skips_content_files_without_a_usable_slug() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/.hidden.md"),
+        "---\ntitle: Hidden\n---\n\nBody.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0]
+        .ends_with(".hidden.md has no file name to take a slug from, skipping it"));
+    assert!(!dist.path().join("blog/.hidden.html").exists());
+}
+
+#[test]
+fn reads_legacy_encoded_content() {
+    let source = copy_fixture();
+    // "Şimdi ılık güneş." saved as Windows-1254
+    let mut content = b"---\ntitle: Eski\n---\n\n".to_vec();
+    content.extend([
+        222, 105, 109, 100, 105, 32, 253, 108, 253, 107, 32, 103, 252, 110, 101, 254, 46, 10,
+    ]);
+    fs::write(source.path().join("content/old.md"), content).unwrap();
+
+    let mut config = Config::default();
+    config.markdown.fallback_encoding = Some("windows-1254".to_string());
+    let dist = tempfile::tempdir().unwrap();
+    let report = build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap();
+    assert!(read(&dist, "old.html").contains("<p>Şimdi ılık güneş.</p>"));
+    assert_eq!(report.warnings.len(), 1);
+    assert!(report.warnings[0].ends_with("old.md isn't UTF-8, read it as windows-1254"));
+
+    let mut config = Config::default();
+    config.markdown.fallback_encoding = Some("klingon".to_string());
+    assert!(Site::new(config, source.path(), dist.path()).is_err());
+}
+
+#[test]
+fn lists_a_configurable_number_of_posts_on_the_homepage() {
+    let latest = |count| {
+        let config = Config {
+            homepage_post_count: count,
+            ..Config::default()
+        };
+        let index = read(&build_fixture(config), "index.html");
+        index
+            .split_once(r#"<ol class="latest">"#)
+            .map(|(_, rest)| rest.split("</ol>").next().unwrap().to_string())
+    };
+    // More than there are lists them all, with a link to the blog
+    assert_eq!(
+        latest(10).unwrap(),
+        "<li>long-read</li><li>code-sample</li><li>rust-book</li><li>hello-world</li>"
+    );
+    assert_eq!(latest(2).unwrap(), "<li>long-read</li><li>code-sample</li>");
+    assert!(read(&build_fixture(Config::default()), "index.html")
+        .contains(r#"<a class="view-all" href="/blog.html">View all</a>"#));
+    assert_eq!(latest(0), None);
+}
+
+#[test]
+fn paginates_listings_with_rel_links() {
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.sections.get_mut("blog").unwrap().paginate = Some(2);
+    let dist = build_fixture(config);
+
+    let first = read(&dist, "blog.html");
+    assert_eq!(listed_slugs(&first), ["code-sample", "hello-world"]);
+    assert!(first.contains(r#"<link rel="next" href="/blog-2.html" />"#));
+    assert!(!first.contains(r#"rel="prev""#));
+
+    let second = read(&dist, "blog-2.html");
+    assert_eq!(listed_slugs(&second), ["long-read"]);
+    assert!(second.contains(r#"<link rel="prev" href="/blog.html" />"#));
+    assert!(!second.contains(r#"rel="next""#));
+    assert!(second.contains(r#"<link rel="canonical" href="https://example.com/blog-2.html" />"#));
+    assert!(!dist.path().join("blog-3.html").exists());
+    assert!(read(&dist, "sitemap.xml").contains("<loc>https://example.com/blog-2.html</loc>"));
+
+    // Unpaginated sections keep a single page without rel links
+    assert!(!read(&dist, "books.html").contains(r#"rel="next""#));
+}
+
+#[test]
+fn keeps_later_listing_pages_out_of_the_index() {
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        noindex_pagination: true,
+        ..Config::default()
+    };
+    config.sections.get_mut("blog").unwrap().paginate = Some(2);
+    let dist = build_fixture(config);
+
+    assert!(!read(&dist, "blog.html").contains(r#"name="robots""#));
+    let second = read(&dist, "blog-2.html");
+    assert!(second.contains(r#"<meta name="robots" content="noindex, follow" />"#));
+    assert!(second.contains(r#"<link rel="prev" href="/blog.html" />"#));
+    let sitemap = read(&dist, "sitemap.xml");
+    assert!(sitemap.contains("<loc>https://example.com/blog.html</loc>"));
+    assert!(!sitemap.contains("blog-2.html"));
+}
+
+#[test]
+fn writes_listings_at_their_list_path() {
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    let blog = config.sections.get_mut("blog").unwrap();
+    blog.list_path = Some("/blog/".to_string());
+    blog.paginate = Some(2);
+    config.sections.get_mut("books").unwrap().list_path = Some("library.html".to_string());
+    let dist = build_fixture(config);
+
+    assert!(!dist.path().join("blog.html").exists());
+    let first = read(&dist, "blog/index.html");
+    assert!(first.contains(r#"<link rel="canonical" href="https://example.com/blog/" />"#));
+    assert!(first.contains(r#"<a href="/blog/" class="active">Blog</a>"#));
+    assert!(first.contains(r#"<link rel="next" href="/blog/2/" />"#));
+    assert!(read(&dist, "blog/2/index.html").contains(r#"<link rel="prev" href="/blog/" />"#));
+    assert!(
+        read(&dist, "library.html").contains(r#"<a href="/library.html" class="active">Books</a>"#)
+    );
+
+    let sitemap = read(&dist, "sitemap.xml");
+    assert!(sitemap.contains("<loc>https://example.com/blog/</loc>"));
+    assert!(sitemap.contains("<loc>https://example.com/blog/2/</loc>"));
+    assert!(read(&dist, "blog/index.xml").contains("<link>https://example.com/blog/</link>"));
+    assert!(read(&dist, "index.html").contains(r#"href="/blog/""#));
+}
+
+#[test]
+fn falls_back_to_the_default_language_for_untranslated_posts() {
+    let source = copy_fixture();
+    fs::create_dir_all(source.path().join("content/fr/blog")).unwrap();
+    fs::write(
+        source.path().join("content/fr/blog/hello-world.md"),
+        "---\ntitle: Bonjour\nlang: fr\n---\n\nBonjour le monde\n",
+    )
+    .unwrap();
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        locales: vec!["fr".to_string()],
+        i18n_fallback: true,
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap();
+
+    let translated = read(&dist, "fr/blog/hello-world.html");
+    assert!(translated.contains("Bonjour le monde"));
+    assert!(!translated.contains("translation-fallback"));
+    let fallback = read(&dist, "fr/blog/code-sample.html");
+    assert!(fallback.contains(r#"<html lang="fr" dir="ltr">"#));
+    assert!(fallback.contains(r#"<p class="translation-fallback">"#));
+    assert!(fallback
+        .contains(r#"<link rel="canonical" href="https://example.com/blog/code-sample.html" />"#));
+    assert!(dist.path().join("fr/books/rust-book.html").exists());
+    // Standalone pages and the listings stay in the default language only
+    assert!(!dist.path().join("fr/about.html").exists());
+    assert!(!read(&dist, "blog.html").contains("/fr/"));
+    assert!(!read(&dist, "sitemap.xml").contains("/fr/blog/code-sample.html"));
+
+    let config = Config {
+        locales: vec!["fr".to_string()],
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap();
+    assert!(!dist.path().join("fr/blog/code-sample.html").exists());
+}
+
+#[test]
+fn puts_a_listing_on_the_homepage() {
+    let source = copy_fixture();
+    fs::remove_file(source.path().join("template/homepage.html")).unwrap();
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.homepage.listing = Some("blog".to_string());
+    config.sections.get_mut("blog").unwrap().paginate = Some(2);
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let home = read(&dist, "index.html");
+    assert!(home.contains(r#"<ul class="posts">"#));
+    assert!(home.contains(r#"<link rel="canonical" href="https://example.com/" />"#));
+    assert!(home.contains(r#"<a href="/" class="active">Blog</a>"#));
+    assert!(home.contains(r#"<link rel="next" href="/2/" />"#));
+    assert!(read(&dist, "2/index.html").contains(r#"<link rel="prev" href="/" />"#));
+    assert!(read(&dist, "blog.html").contains(r#"content="0; url=https://example.com/""#));
+
+    let sitemap = read(&dist, "sitemap.xml");
+    assert_eq!(
+        sitemap.matches("<loc>https://example.com/</loc>").count(),
+        1
+    );
+    assert!(!sitemap.contains("blog.html"));
+    assert!(read(&dist, "blog/index.xml").contains("<link>https://example.com/</link>"));
+
+    let mut config = Config::default();
+    config.homepage.listing = Some("news".to_string());
+    let err = Site::new(config, source.path(), dist.path()).err().unwrap();
+    assert!(err.to_string().contains("isn't a section"));
+}
+
+#[test]
+fn redirects_the_first_listing_page() {
+    let config = |first_listing_page| {
+        let mut config = Config {
+            base_url: "https://example.com".to_string(),
+            first_listing_page,
+            ..Config::default()
+        };
+        config.sections.get_mut("blog").unwrap().paginate = Some(2);
+        config
+    };
+
+    let dist = build_fixture(config(FirstListingPage::Skip));
+    assert!(!dist.path().join("blog-1.html").exists());
+    assert!(read(&dist, "blog.html")
+        .contains(r#"<link rel="canonical" href="https://example.com/blog.html" />"#));
+
+    let dist = build_fixture(config(FirstListingPage::Redirect));
+    let stub = read(&dist, "blog-1.html");
+    assert!(stub.contains(r#"<link rel="canonical" href="https://example.com/blog.html" />"#));
+    assert!(stub.contains(r#"content="0; url=https://example.com/blog.html""#));
+    assert!(read(&dist, "blog-2.html").contains(r#"<link rel="prev" href="/blog.html" />"#));
+    assert!(!read(&dist, "sitemap.xml").contains("blog-1.html"));
+    // Unpaginated sections have no pages to number
+    assert!(!dist.path().join("books-1.html").exists());
+}
+
+#[test]
+fn applies_frontmatter_defaults_and_types() {
+    let source = copy_fixture();
+    let page = source.path().join("template/page.html");
+    let template = fs::read_to_string(&page).unwrap().replace(
+        "{% endblock %}",
+        "<p class=\"schema\">{{ meta.weight + 1 }} {{ meta.tags | length }} {{ meta.draft }}</p>\n{% endblock %}",
+    );
+    fs::write(&page, template).unwrap();
+    fs::write(
+        source.path().join("content/weighted.md"),
+        "---\ntitle: Weighted\nweight: \"41\"\n---\n\nBody.\n",
+    )
+    .unwrap();
+    let config = || -> Config {
+        toml::from_str(
+            r#"
+            [frontmatter_defaults]
+            tags = []
+            draft = false
+            weight = 0
+
+            [frontmatter_types]
+            weight = "integer"
+            "#,
+        )
+        .unwrap()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(config(), source.path(), dist.path()).unwrap()).unwrap();
+    assert!(read(&dist, "weighted.html").contains(r#"<p class="schema">42 0 false</p>"#));
+    assert!(read(&dist, "about.html").contains(r#"<p class="schema">1 0 false</p>"#));
+
+    fs::write(
+        source.path().join("content/weighted.md"),
+        "---\ntitle: Weighted\nweight: abc\n---\n\nBody.\n",
+    )
+    .unwrap();
+    let error = build(&Site::new(config(), source.path(), dist.path()).unwrap()).unwrap_err();
+    let error = format!("{:#}", error);
+    assert!(
+        error.contains("Frontmatter `weight` in ")
+            && error.contains("weighted.md should be an integer, found abc"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn points_cross_posts_at_their_original_canonical() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/cross-post.md"),
+        "---\ntitle: Cross Post\ndate: 2024-05-01\n\
+         canonical_url: https://dev.to/someone/cross-post\n---\n\nFirst published elsewhere.\n",
+    )
+    .unwrap();
+    let config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap();
+
+    let post = read(&dist, "blog/cross-post.html");
+    assert!(post.contains(r#"<link rel="canonical" href="https://dev.to/someone/cross-post" />"#));
+    assert!(post.contains(r#""mainEntityOfPage":"https://dev.to/someone/cross-post""#));
+    let sitemap = read(&dist, "sitemap.xml");
+    assert!(!sitemap.contains("cross-post"));
+
+    // Everything else keeps its own URL
+    let post = read(&dist, "blog/hello-world.html");
+    assert!(post
+        .contains(r#"<link rel="canonical" href="https://example.com/blog/hello-world.html" />"#));
+    assert!(post.contains(r#""mainEntityOfPage":"https://example.com/blog/hello-world.html""#));
+    assert!(post.contains(r#""datePublished":"2024-01-15""#));
+}
+
+#[test]
+fn reports_published_and_excluded_files() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/unfinished.md"),
+        "---\ntitle: Unfinished\ndraft: true\n---\n\nSoon.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let published: Vec<(Option<&str>, usize)> = report
+        .published
+        .iter()
+        .map(|(section, count)| (section.as_deref(), *count))
+        .collect();
+    assert_eq!(
+        published,
+        [(None, 2), (Some("blog"), 3), (Some("books"), 3)]
+    );
+    let excluded: Vec<String> = report
+        .excluded
+        .iter()
+        .map(|exclusion| {
+            let file = exclusion.path.file_name().unwrap().to_string_lossy();
+            format!("{} {}", file, exclusion.reason)
+        })
+        .collect();
+    assert_eq!(excluded, ["unfinished.md draft", "thanks.md noindex"]);
+    assert_eq!(report.excluded[0].reason, ExclusionReason::Draft);
+}
+
+#[test]
+fn writes_printable_books_in_chapter_order() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/books/first-book/setup.md"),
+        "---\ntitle: Setup\nchapter: 1\n---\n\nInstall **everything**.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config::default();
+    let books = config.sections.get_mut("books").unwrap();
+    books.sort_by = Some("chapter".to_string());
+    books.print_books = vec!["first-book".to_string(), "missing-book".to_string()];
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let print = read(&dist, "books/first-book/print.html");
+    assert!(print.starts_with("<h1>first-book</h1>"));
+    let setup = print
+        .find(r#"<section class="chapter" data-url="/books/first-book/setup.html"><h2>Setup</h2><p>Install <strong>everything</strong>.</p>"#)
+        .unwrap();
+    let intro = print
+        .find("<h2>Introduction to the First Book</h2><p>Why the first book is worth reading.</p>")
+        .unwrap();
+    assert!(setup < intro);
+    assert!(!print.contains("Second Book"));
+    // Only the books listed get one
+    assert!(!dist.path().join("books/second-book/print.html").exists());
+    assert!(!dist.path().join("books/missing-book").exists());
+}
+
+#[test]
+fn serves_local_images_from_the_cdn() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/gallery.md"),
+        "---\ntitle: Gallery\nimage: /assets/images/cover.svg\n---\n\n![Cover](/assets/images/cover.svg) ![Remote](https://other.org/a.png) [Styles](/assets/styles.css)\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let config = Config {
+        base_path: "/site".to_string(),
+        image_cdn: Some("https://cdn.example.com/".to_string()),
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let post = read(&dist, "blog/gallery.html");
+    assert!(post.contains(r#"<img src="https://cdn.example.com/assets/images/cover.svg" />"#));
+    assert!(post
+        .contains(r#"<img src="https://cdn.example.com/assets/images/cover.svg" alt="Cover" />"#));
+    assert!(post.contains(r#"<img src="https://other.org/a.png" alt="Remote" />"#));
+    assert!(post.contains(r#"<a href="/site/assets/styles.css">Styles</a>"#));
+    // The files are still there for the CDN to pull from
+    assert!(dist.path().join("assets/images/cover.svg").is_file());
+}
+
+#[test]
+fn exports_posts_as_csv() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/blog/quoted.md"),
+        "---\ntitle: 'Commas, \"quotes\"'\ndate: 2024-03-01\nupdated: 2024-04-01\ntags: [a, b]\ndraft: true\n---\n\nOne two three.\n",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let config = Config {
+        csv: true,
+        drafts: true,
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let csv = read(&dist, "posts.csv");
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("category,slug,title,date,updated,tags,word_count,reading_time,draft")
+    );
+    assert!(csv.contains(
+        "\nblog,quoted,\"Commas, \"\"quotes\"\"\",2024-03-01,2024-04-01,\"a, b\",3,1,true\n"
+    ));
+    assert!(csv.contains("\n,about,About,"));
+    let hello = lines
+        .find(|line| line.starts_with("blog,hello-world,"))
+        .unwrap();
+    assert!(hello.starts_with("blog,hello-world,Hello World,2024-01-15,,\"rust, web\","));
+    assert!(hello.ends_with(",false"));
+
+    assert!(!build_fixture(Config::default())
+        .path()
+        .join("posts.csv")
+        .exists());
+}
+
+#[test]
+fn maps_sources_to_their_outputs() {
+    let source = copy_fixture();
+    image::RgbImage::new(300, 150)
+        .save(source.path().join("assets/images/wide.jpg"))
+        .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let mut config = Config {
+        build_manifest: true,
+        emit_json: true,
+        toc_json: true,
+        ..Config::default()
+    };
+    config.assets.responsive_images = true;
+    config.assets.responsive_widths = vec![120];
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let manifest: serde_json::Value = serde_json::from_str(&read(&dist, ".manifest.json")).unwrap();
+    let outputs = |path: &str| {
+        let key = source.path().join(path).to_string_lossy().into_owned();
+        manifest[key.as_str()].clone()
+    };
+    assert_eq!(
+        outputs("content/blog/hello-world.md"),
+        serde_json::json!([
+            "blog/hello-world/toc.json",
+            "blog/hello-world.html",
+            "blog/hello-world.json"
+        ])
+    );
+    assert_eq!(
+        outputs("assets/images/wide.jpg"),
+        serde_json::json!(["assets/images/wide-120w.jpg", "assets/images/wide.jpg"])
+    );
+    // Listings come from many sources and aren't attributed to any
+    assert!(!manifest.to_string().contains("\"blog.html\""));
+
+    let dist = build_fixture(Config::default());
+    assert!(!dist.path().join(".manifest.json").exists());
+}
+
+#[test]
+fn links_posts_to_their_source_for_editing() {
+    let source = copy_fixture();
+    let dist = tempfile::tempdir().unwrap();
+    let config = Config {
+        edit_url_template: Some(
+            "https://github.com/mumudevx/personal-website/edit/main/{path}".to_string(),
+        ),
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let name = source.path().file_name().unwrap().to_string_lossy();
+    assert!(read(&dist, "blog/hello-world.html").contains(&format!(
+        r#"<a class="edit" href="https://github.com/mumudevx/personal-website/edit/main/{}/content/blog/hello-world.md">Edit this page</a>"#,
+        name
+    )));
+    assert!(
+        !read(&build_fixture(Config::default()), "blog/hello-world.html")
+            .contains("Edit this page")
+    );
+}
+
+#[test]
+fn writes_amp_versions_of_section_posts() {
+    let source = copy_fixture();
+    image::RgbImage::new(40, 20)
+        .save(source.path().join("assets/images/wide.png"))
+        .unwrap();
+    fs::write(
+        source.path().join("content/blog/amped.md"),
+        "---\ntitle: Amped\ndate: 2024-05-01\n---\n\n\
+         <p style=\"color: red\" onclick=\"boom()\">Hi <b>there</b> &amp; all</p>\n\n\
+         <script>if (a < b) { alert(1) }</script>\n\n\
+         <iframe src=\"https://example.com\"><p>inside</p></iframe>\n\n\
+         ![Wide](/assets/images/wide.png) ![Gone](/assets/images/gone.png)\n",
+    )
+    .unwrap();
+    let mut config = Config {
+        base_url: "https://example.com".to_string(),
+        ..Config::default()
+    };
+    config.amp.enabled = true;
+    let dist = tempfile::tempdir().unwrap();
+    build(&Site::new(config, source.path(), dist.path()).unwrap()).unwrap();
+
+    assert!(read(&dist, "blog/amped.html")
+        .contains(r#"<link rel="amphtml" href="/blog/amped/amp.html" />"#));
+    let amp = read(&dist, "blog/amped/amp.html");
+    assert!(amp.contains(r#"<link rel="canonical" href="https://example.com/blog/amped.html" />"#));
+    assert!(amp.contains("<style amp-custom>body {\n  margin: 0;\n}"));
+    assert!(amp.contains("<p>Hi <b>there</b> &amp; all</p>"));
+    assert!(!amp.contains("alert") && !amp.contains("iframe") && !amp.contains("inside"));
+    assert!(amp.contains(
+        r#"<amp-img src="/assets/images/wide.png" alt="Wide" width="40" height="20" layout="responsive"></amp-img>"#
+    ));
+    assert!(amp.contains(r#"<a href="/assets/images/gone.png">Gone</a>"#));
+    // Standalone pages don't get one
+    assert!(!dist.path().join("about/amp.html").exists());
+}
+
+#[test]
+fn checks_config_against_templates_and_directories() {
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), FIXTURE, dist.path()).unwrap();
+    assert!(check_config(&site, Path::new("site.toml")).is_empty());
+
+    let mut config = Config::default();
+    let mut notes = SectionConfig::new("notes.html", "blog_list.html");
+    notes.split_on = Some("h9".to_string());
+    config.sections.insert("notes".to_string(), notes);
+    let site = Site::new(config, FIXTURE, dist.path()).unwrap();
+    let issues: Vec<String> = check_config(&site, Path::new("site.toml"))
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect();
+    assert_eq!(
+        issues,
+        [
+            "error: site.toml: Section `notes` uses template `notes.html`, which doesn't exist",
+            "warning: site.toml: Section `notes` reads `notes` under src/content, which doesn't exist",
+            "error: site.toml: Section `notes` has unknown split_on `h9`, expected h1 to h6 or none",
+        ]
+    );
+    assert!(fs::read_dir(dist.path()).unwrap().next().is_none());
+}
+
+#[test]
+fn precompresses_text_files() {
+    let config = Config {
+        compress: CompressConfig {
+            enabled: true,
+            min_size: 1024,
+            ..CompressConfig::default()
+        },
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, FIXTURE, dist.path()).unwrap();
+    let report = build(&site).unwrap();
+
+    let page = fs::read(dist.path().join("blog/hello-world.html")).unwrap();
+    let gzipped = fs::read(dist.path().join("blog/hello-world.html.gz")).unwrap();
+    let mut gunzipped = vec![];
+    flate2::read::GzDecoder::new(&gzipped[..])
+        .read_to_end(&mut gunzipped)
+        .unwrap();
+    assert_eq!(gunzipped, page);
+    let brotli = fs::read(dist.path().join("blog/hello-world.html.br")).unwrap();
+    let mut unbrotlied = vec![];
+    brotli::Decompressor::new(&brotli[..], 4096)
+        .read_to_end(&mut unbrotlied)
+        .unwrap();
+    assert_eq!(unbrotlied, page);
+
+    // Small files are left alone
+    assert!(fs::metadata(dist.path().join("about.html")).unwrap().len() < 1024);
+    assert!(!dist.path().join("about.html.gz").exists());
+    assert!(!dist.path().join("about.html.br").exists());
+
+    let formats: Vec<CompressFormat> = report.compressed.iter().map(|total| total.format).collect();
+    assert_eq!(formats, [CompressFormat::Gzip, CompressFormat::Brotli]);
+    let gzip = &report.compressed[0];
+    assert!(gzip.files > 0 && gzip.compressed_bytes < gzip.bytes);
+
+    // Copies go away once their format is dropped
+    let config = Config {
+        compress: CompressConfig {
+            enabled: true,
+            formats: vec![CompressFormat::Gzip],
+            min_size: 1024,
+            ..CompressConfig::default()
+        },
+        ..Config::default()
+    };
+    let site = Site::new(config, FIXTURE, dist.path()).unwrap();
+    build(&site).unwrap();
+    assert!(dist.path().join("blog/hello-world.html.gz").exists());
+    assert!(!dist.path().join("blog/hello-world.html.br").exists());
+
+    let config = Config {
+        compress: CompressConfig {
+            brotli_quality: 12,
+            ..CompressConfig::default()
+        },
+        ..Config::default()
+    };
+    let err = Site::new(config, FIXTURE, dist.path()).err().unwrap();
+    assert!(err.to_string().starts_with("`brotli_quality = 12`"));
+}
+
+#[test]
+fn uses_section_index_as_listing_intro() {
+    let source = copy_fixture();
+    fs::write(
+        source.path().join("content/books/_index.md"),
+        "---\ntitle: Reading shelf\n---\nBooks I keep coming back to.\n",
+    )
+    .unwrap();
+    fs::write(
+        source.path().join("template/book_list.html"),
+        "<h1>{{ title }}</h1>{% if intro is defined %}{{ intro | safe }}{% endif %}\
+         {% for post in posts %}[{{ post.title }}]{% endfor %}",
+    )
+    .unwrap();
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+
+    let books = read(&dist, "books.html");
+    assert!(books.starts_with("<h1>Reading shelf</h1><p>Books I keep coming back to.</p>\n["));
+    assert!(books.contains("[The Rust Book]"));
+    assert!(!dist.path().join("books/_index.html").exists());
+
+    // Only one of them can be the intro
+    fs::write(
+        source.path().join("content/books/index.md"),
+        "Another intro.\n",
+    )
+    .unwrap();
+    let err = build(&site).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<SiteError>(),
+        Some(SiteError::Validation { path, .. }) if path.ends_with("books/index.md")
+    ));
+    assert!(err.to_string().contains("has both"));
+}
+
+#[test]
+fn finds_sections_by_their_directory() {
+    let source = copy_fixture();
+    fs::create_dir_all(source.path().join("content/notes")).unwrap();
+    fs::write(
+        source.path().join("content/notes/loose.md"),
+        "---\ntitle: Loose note\ndate: 2024-05-01\n---\nNot in any section.\n",
+    )
+    .unwrap();
+    fs::create_dir_all(source.path().join("content/books/series/blog")).unwrap();
+    fs::write(
+        source.path().join("content/books/series/blog/nested.md"),
+        "---\ntitle: Nested\ndate: 2024-05-02\n---\nStill a book.\n",
+    )
+    .unwrap();
+
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(Config::default(), source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    assert!(read(&dist, "notes/loose.html").contains("Not in any section."));
+    assert!(!read(&dist, "blog.html").contains(">Loose note</a>"));
+    assert!(read(&dist, "books.html").contains(">Nested</a>"));
+
+    let config = Config {
+        default_section: Some("blog".to_string()),
+        ..Config::default()
+    };
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    let blog = read(&dist, "blog.html");
+    assert!(blog.contains(">Loose note</a>"));
+    assert!(!blog.contains(">Nested</a>"));
+    let books = read(&dist, "books.html");
+    assert!(books.contains(">Nested</a>"));
+    assert!(!books.contains(">Loose note</a>"));
+
+    let config = Config {
+        default_section: Some("notes".to_string()),
+        ..Config::default()
+    };
+    let err = Site::new(config, source.path(), dist.path()).err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "`default_section = \"notes\"` isn't a section"
+    );
+
+    // A section inside another's directory takes the files under it
+    let mut config = Config::default();
+    let mut series = SectionConfig::new("book_detail.html", "book_list.html");
+    series.dir = Some("books/series".to_string());
+    config.sections.insert("series".to_string(), series);
+    let dist = tempfile::tempdir().unwrap();
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    build(&site).unwrap();
+    assert!(read(&dist, "series.html").contains(">Nested</a>"));
+    assert!(!read(&dist, "books.html").contains(">Nested</a>"));
+
+    let mut config = Config::default();
+    config.sections.get_mut("books").unwrap().dir = Some("../books".to_string());
+    let err = Site::new(config, source.path(), dist.path()).err().unwrap();
+    assert!(err
+        .to_string()
+        .contains("isn't a directory under src/content"));
+
+    // Only pages right in src/content may be outside of every section
+    let config = Config {
+        require_section: true,
+        ..Config::default()
+    };
+    let site = Site::new(config, source.path(), dist.path()).unwrap();
+    let err = build(&site).unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<SiteError>(),
+        Some(SiteError::Validation { path, .. }) if path.ends_with("notes/loose.md")
+    ));
+    let issues: Vec<String> = check(&site)
+        .unwrap()
+        .iter()
+        .map(|issue| issue.to_string())
+        .collect();
+    assert!(issues
+        .iter()
+        .any(|issue| issue.contains("loose.md") && issue.contains("`require_section`")));
+    fs::remove_dir_all(source.path().join("content/notes")).unwrap();
+    build(&site).unwrap();
+    assert!(read(&dist, "about.html").contains("About"));
+}